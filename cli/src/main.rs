@@ -1,4 +1,5 @@
 use clap::{Parser, Subcommand};
+use crypto::address::{Address, Network};
 use ed25519_dalek::{SigningKey, VerifyingKey};
 use rand::rngs::OsRng;
 
@@ -12,17 +13,24 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Generate a new Ed25519 keypair
-    Keygen,
+    Keygen {
+        /// Network the address is derived for (mainnet, testnet, devnet)
+        #[arg(long, default_value = "mainnet")]
+        network: Network,
+    },
+    /// Check that an address is well-formed and has a valid checksum
+    ValidateAddress { address: String },
 }
 
 fn main() {
     let cli = Cli::parse();
 
     match &cli.command {
-        Commands::Keygen => {
+        Commands::Keygen { network } => {
             let mut csprng = OsRng;
             let signing_key = SigningKey::generate(&mut csprng);
             let verifying_key: VerifyingKey = signing_key.verifying_key();
+            let address = Address::from_public_key(&verifying_key.to_bytes(), *network);
 
             println!("New Keypair Generated:");
             println!(
@@ -30,9 +38,17 @@ fn main() {
                 hex::encode(signing_key.to_bytes())
             );
             println!(
-                "Public Key (Address):     {}",
+                "Public Key:               {}",
                 hex::encode(verifying_key.to_bytes())
             );
+            println!("{:<25} {}", format!("Address ({}):", network), address);
         }
+        Commands::ValidateAddress { address } => match Address::parse(address) {
+            Ok(parsed) => println!("Valid {} address", parsed.network()),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
     }
 }
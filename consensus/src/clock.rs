@@ -0,0 +1,312 @@
+use crate::engine::ConsensusError;
+use ledger::block::BlockHeader;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{mpsc, watch};
+use tokio_stream::wrappers::ReceiverStream;
+
+/// Default allowance for clock skew between nodes when accepting blocks.
+pub const DEFAULT_MAX_FUTURE_DRIFT: Duration = Duration::from_millis(500);
+
+/// Where the clock reads the current time from. Times are Unix milliseconds.
+pub trait TimeSource: Send + Sync + 'static {
+    fn now_millis(&self) -> u64;
+
+    /// Resolves once `now_millis() >= target`.
+    fn sleep_until(&self, target: u64) -> impl Future<Output = ()> + Send;
+}
+
+/// Wall-clock time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemTimeSource;
+
+impl TimeSource for SystemTimeSource {
+    fn now_millis(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+    }
+
+    fn sleep_until(&self, target: u64) -> impl Future<Output = ()> + Send {
+        let wait = target.saturating_sub(self.now_millis());
+        tokio::time::sleep(Duration::from_millis(wait))
+    }
+}
+
+/// Time that only moves when told to, for tests and simulations.
+#[derive(Debug, Clone)]
+pub struct ManualTimeSource {
+    now: Arc<watch::Sender<u64>>,
+}
+
+impl ManualTimeSource {
+    pub fn new(start_millis: u64) -> Self {
+        Self {
+            now: Arc::new(watch::channel(start_millis).0),
+        }
+    }
+
+    pub fn set(&self, millis: u64) {
+        self.now.send_replace(millis);
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.now.send_modify(|now| *now += by.as_millis() as u64);
+    }
+}
+
+impl TimeSource for ManualTimeSource {
+    fn now_millis(&self) -> u64 {
+        *self.now.borrow()
+    }
+
+    fn sleep_until(&self, target: u64) -> impl Future<Output = ()> + Send {
+        let mut receiver = self.now.subscribe();
+        async move {
+            let _ = receiver.wait_for(|now| *now >= target).await;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlotTick {
+    pub slot: u64,
+    pub epoch: u64,
+    /// True for the first tick seen in `epoch` (including the very first tick).
+    pub new_epoch: bool,
+}
+
+/// Maps wall time to slots and epochs. Slot 0 starts at genesis.
+pub struct SlotClock<T: TimeSource = SystemTimeSource> {
+    genesis_time: u64,
+    slot_duration: u64,
+    slots_per_epoch: u64,
+    max_future_drift: u64,
+    time: Arc<T>,
+}
+
+impl<T: TimeSource> Clone for SlotClock<T> {
+    fn clone(&self) -> Self {
+        Self {
+            genesis_time: self.genesis_time,
+            slot_duration: self.slot_duration,
+            slots_per_epoch: self.slots_per_epoch,
+            max_future_drift: self.max_future_drift,
+            time: self.time.clone(),
+        }
+    }
+}
+
+impl SlotClock<SystemTimeSource> {
+    pub fn new(genesis_time: u64, slot_duration: Duration, slots_per_epoch: u64) -> Self {
+        Self::with_time_source(
+            genesis_time,
+            slot_duration,
+            slots_per_epoch,
+            SystemTimeSource,
+        )
+    }
+}
+
+impl<T: TimeSource> SlotClock<T> {
+    pub fn with_time_source(
+        genesis_time: u64,
+        slot_duration: Duration,
+        slots_per_epoch: u64,
+        time: T,
+    ) -> Self {
+        Self {
+            genesis_time,
+            slot_duration: (slot_duration.as_millis() as u64).max(1),
+            slots_per_epoch: slots_per_epoch.max(1),
+            max_future_drift: DEFAULT_MAX_FUTURE_DRIFT.as_millis() as u64,
+            time: Arc::new(time),
+        }
+    }
+
+    pub fn with_max_future_drift(mut self, drift: Duration) -> Self {
+        self.max_future_drift = drift.as_millis() as u64;
+        self
+    }
+
+    pub fn now_millis(&self) -> u64 {
+        self.time.now_millis()
+    }
+
+    pub fn slots_per_epoch(&self) -> u64 {
+        self.slots_per_epoch
+    }
+
+    /// The slot containing `millis`, or `None` before genesis.
+    pub fn slot_at(&self, millis: u64) -> Option<u64> {
+        millis
+            .checked_sub(self.genesis_time)
+            .map(|elapsed| elapsed / self.slot_duration)
+    }
+
+    pub fn current_slot(&self) -> Option<u64> {
+        self.slot_at(self.now_millis())
+    }
+
+    pub fn current_epoch(&self) -> Option<u64> {
+        self.current_slot().map(|slot| self.epoch_of(slot))
+    }
+
+    pub fn epoch_of(&self, slot: u64) -> u64 {
+        slot / self.slots_per_epoch
+    }
+
+    pub fn first_slot_of_epoch(&self, epoch: u64) -> u64 {
+        epoch.saturating_mul(self.slots_per_epoch)
+    }
+
+    /// Unix milliseconds at which `slot` begins, saturating for slots too far
+    /// out to represent.
+    pub fn slot_start(&self, slot: u64) -> u64 {
+        self.checked_slot_start(slot).unwrap_or(u64::MAX)
+    }
+
+    fn checked_slot_start(&self, slot: u64) -> Option<u64> {
+        slot.checked_mul(self.slot_duration)?
+            .checked_add(self.genesis_time)
+    }
+
+    /// Rejects headers for slots that haven't started yet (allowing for
+    /// `max_future_drift` of clock skew) and timestamps outside their slot.
+    pub fn verify_block_time(&self, header: &BlockHeader) -> Result<(), ConsensusError> {
+        // Signed headers can carry any slot; one past u64 time is just far off
+        let future = || ConsensusError::FutureBlock { slot: header.slot };
+        let start = self.checked_slot_start(header.slot).ok_or_else(future)?;
+        let end = start.checked_add(self.slot_duration).ok_or_else(future)?;
+        if header.timestamp < start || header.timestamp >= end {
+            return Err(ConsensusError::InvalidTimestamp);
+        }
+        if start > self.now_millis().saturating_add(self.max_future_drift) {
+            return Err(ConsensusError::FutureBlock { slot: header.slot });
+        }
+        Ok(())
+    }
+
+    /// Emits a tick at the start of every slot. Slots missed while the
+    /// consumer lagged are skipped rather than replayed, but `new_epoch` is
+    /// still set on the first tick of each epoch. Must be called from within
+    /// a tokio runtime; the background task stops when the stream is dropped.
+    pub fn ticks(&self) -> ReceiverStream<SlotTick> {
+        let (sender, receiver) = mpsc::channel(16);
+        let clock = self.clone();
+        tokio::spawn(async move {
+            let mut last_epoch = None;
+            loop {
+                let Some(slot) = clock.current_slot() else {
+                    clock.time.sleep_until(clock.genesis_time).await;
+                    continue;
+                };
+                let epoch = clock.epoch_of(slot);
+                let tick = SlotTick {
+                    slot,
+                    epoch,
+                    new_epoch: last_epoch != Some(epoch),
+                };
+                last_epoch = Some(epoch);
+                if sender.send(tick).await.is_err() {
+                    break;
+                }
+                tokio::select! {
+                    _ = clock.time.sleep_until(clock.slot_start(slot + 1)) => {}
+                    _ = sender.closed() => break,
+                }
+            }
+        });
+        ReceiverStream::new(receiver)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_stream::StreamExt;
+
+    const GENESIS: u64 = 1_000_000;
+
+    fn clock() -> (SlotClock<ManualTimeSource>, ManualTimeSource) {
+        let time = ManualTimeSource::new(GENESIS - 100);
+        let clock = SlotClock::with_time_source(GENESIS, Duration::from_secs(2), 4, time.clone());
+        (clock, time)
+    }
+
+    fn header(slot: u64, timestamp: u64) -> BlockHeader {
+        BlockHeader {
+            chain_id: "devnet".to_string(),
+            parent_hash: "0".to_string(),
+            timestamp,
+            slot,
+            state_root: "".to_string(),
+            transactions_root: "".to_string(),
+            evidence_root: "".to_string(),
+            randao_reveal: Vec::new(),
+            randao_mix: [0u8; 32],
+            validator_public_key: vec![],
+            signature: vec![],
+        }
+    }
+
+    #[test]
+    fn test_slot_and_epoch_math() {
+        let (clock, time) = clock();
+        assert_eq!(clock.current_slot(), None);
+
+        time.set(GENESIS + 9_999);
+        assert_eq!(clock.current_slot(), Some(4));
+        assert_eq!(clock.current_epoch(), Some(1));
+        assert_eq!(clock.first_slot_of_epoch(3), 12);
+        assert_eq!(clock.slot_start(4), GENESIS + 8_000);
+    }
+
+    #[test]
+    fn test_rejects_future_blocks() {
+        let (clock, time) = clock();
+        time.set(GENESIS + 3_700); // slot 1, 300ms before slot 2
+        let slot_start = clock.slot_start(2);
+
+        assert!(clock.verify_block_time(&header(1, GENESIS + 2_000)).is_ok());
+        // Within the default 500ms drift
+        assert!(clock.verify_block_time(&header(2, slot_start)).is_ok());
+        assert!(matches!(
+            clock.verify_block_time(&header(3, clock.slot_start(3))),
+            Err(ConsensusError::FutureBlock { slot: 3 })
+        ));
+        assert!(matches!(
+            clock.verify_block_time(&header(1, slot_start)),
+            Err(ConsensusError::InvalidTimestamp)
+        ));
+
+        // Slots whose start overflows are rejected rather than panicking
+        for slot in [u64::MAX, u64::MAX / 2_000 + 1] {
+            assert!(matches!(
+                clock.verify_block_time(&header(slot, u64::MAX)),
+                Err(ConsensusError::FutureBlock { .. })
+            ));
+        }
+        assert_eq!(clock.slot_start(u64::MAX), u64::MAX);
+    }
+
+    #[tokio::test]
+    async fn test_ticks_follow_time_source() {
+        let (clock, time) = clock();
+        let mut ticks = clock.ticks();
+
+        time.set(GENESIS + 6_000); // slot 3
+        let tick = ticks.next().await.unwrap();
+        assert_eq!((tick.slot, tick.epoch, tick.new_epoch), (3, 0, true));
+
+        time.advance(Duration::from_secs(2));
+        let tick = ticks.next().await.unwrap();
+        assert_eq!((tick.slot, tick.epoch, tick.new_epoch), (4, 1, true));
+
+        time.advance(Duration::from_secs(2));
+        let tick = ticks.next().await.unwrap();
+        assert_eq!((tick.slot, tick.epoch, tick.new_epoch), (5, 1, false));
+    }
+}
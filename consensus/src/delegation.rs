@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+/// Commission rates are expressed in basis points (1/100th of a percent).
+pub const MAX_COMMISSION_BPS: u16 = 10_000;
+
+/// A delegator's claim on a validator's bonded pool. Shares rather than token
+/// amounts are stored so rewards (and later slashes) apply to every delegator
+/// pro rata just by changing the pool's token total.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Delegation {
+    pub delegator: Vec<u8>,
+    pub validator: Vec<u8>,
+    pub shares: u64,
+}
+
+/// Tokens that have left a validator's pool but can't be withdrawn until
+/// `completion_epoch`. They remain slashable for offenses committed while bonded.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UnbondingEntry {
+    pub delegator: Vec<u8>,
+    pub validator: Vec<u8>,
+    pub amount: u64,
+    pub creation_epoch: u64,
+    pub completion_epoch: u64,
+}
+
+/// Shares minted for `amount` tokens entering a pool that holds `pool_tokens`
+/// backed by `pool_shares`. The first deposit is minted 1:1.
+pub fn shares_for_tokens(amount: u64, pool_tokens: u64, pool_shares: u64) -> u64 {
+    if pool_shares == 0 || pool_tokens == 0 {
+        return amount;
+    }
+    ((amount as u128 * pool_shares as u128) / pool_tokens as u128) as u64
+}
+
+/// Shares to burn when `amount` tokens leave a pool. Rounds up, so whoever
+/// leaves pays the rounding rather than the delegators who stay.
+pub fn shares_to_burn(amount: u64, pool_tokens: u64, pool_shares: u64) -> u64 {
+    if pool_shares == 0 || pool_tokens == 0 {
+        return amount;
+    }
+    (amount as u128 * pool_shares as u128).div_ceil(pool_tokens as u128) as u64
+}
+
+/// Tokens currently backing `shares` of a pool.
+pub fn tokens_for_shares(shares: u64, pool_tokens: u64, pool_shares: u64) -> u64 {
+    if pool_shares == 0 {
+        return 0;
+    }
+    ((shares as u128 * pool_tokens as u128) / pool_shares as u128) as u64
+}
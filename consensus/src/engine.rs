@@ -9,12 +9,39 @@ pub enum ConsensusError {
     UnknownValidator,
     #[error("Validator is slashed")]
     SlashedValidator,
+    #[error("Validator is jailed")]
+    JailedValidator,
+    #[error("Block signed by {actual}, but slot {slot} belongs to {expected}")]
+    WrongProposer {
+        slot: u64,
+        expected: String,
+        actual: String,
+    },
+    #[error("No validator with voting power to propose")]
+    NoEligibleProposer,
+    #[error("Validator voted for two different blocks in the same round")]
+    ConflictingVote,
+    #[error("Block at slot {slot} is too far in the future")]
+    FutureBlock { slot: u64 },
+    #[error("Block timestamp does not match its slot")]
+    InvalidTimestamp,
+    #[error("RANDAO reveal does not verify against the proposer's key")]
+    InvalidRandaoReveal,
+    #[error("RANDAO mix does not follow from the parent's mix")]
+    InvalidRandaoMix,
+    #[error("Invalid quorum certificate: {0}")]
+    InvalidCertificate(String),
+    #[error("Vote is for chain {0}")]
+    WrongChain(String),
 }
 
 pub trait ConsensusEngine {
     /// Verifies that a block header is valid according to the consensus rules.
     fn verify_block_header(&self, header: &BlockHeader) -> Result<(), ConsensusError>;
 
+    /// Public key of the validator expected to propose at `slot`, if any.
+    fn proposer_for_slot(&self, slot: u64) -> Option<Vec<u8>>;
+
     /// Given a list of fork tips/headers, chooses the best one (fork choice rule).
     fn choose_fork<'a>(&self, forks: &'a [BlockHeader]) -> Option<&'a BlockHeader>;
 }
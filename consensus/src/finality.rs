@@ -0,0 +1,486 @@
+//! Tendermint-style finality gadget.
+//!
+//! Validators sign a prevote and then a precommit for a block. Once votes
+//! carrying more than 2/3 of the total voting power agree on a block they form a
+//! `QuorumCertificate`; a precommit certificate makes the block a finalized
+//! checkpoint that can never be reverted. Each checkpoint has to build on the
+//! one before it.
+
+use crate::engine::ConsensusError;
+use crate::validator::ValidatorSet;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::watch;
+
+pub use ledger::vote::{Vote, VoteKind};
+
+/// Votes from more than 2/3 of the voting power for the same block.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct QuorumCertificate {
+    pub chain_id: String,
+    pub kind: VoteKind,
+    pub slot: u64,
+    pub round: u32,
+    pub block_hash: String,
+    pub votes: Vec<Vote>,
+}
+
+impl QuorumCertificate {
+    /// Re-checks every signature and the 2/3 threshold against `validators`, so a
+    /// certificate received from a peer can be trusted on its own.
+    pub fn verify(&self, validators: &ValidatorSet) -> Result<(), ConsensusError> {
+        let mut signers = Vec::new();
+        let mut power = 0u64;
+
+        for vote in &self.votes {
+            if vote.chain_id != self.chain_id
+                || vote.kind != self.kind
+                || vote.slot != self.slot
+                || vote.round != self.round
+                || vote.block_hash != self.block_hash
+            {
+                return Err(ConsensusError::InvalidCertificate(
+                    "vote does not match certificate".to_string(),
+                ));
+            }
+            if signers.contains(&&vote.validator_public_key) {
+                return Err(ConsensusError::InvalidCertificate(
+                    "duplicate signer".to_string(),
+                ));
+            }
+            vote.verify()
+                .map_err(|_| ConsensusError::InvalidSignature)?;
+            signers.push(&vote.validator_public_key);
+            power += validators.voting_power(&vote.validator_public_key);
+        }
+
+        if !has_quorum(power, validators.total_voting_power()) {
+            return Err(ConsensusError::InvalidCertificate(
+                "insufficient voting power".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A block that has a precommit certificate and can no longer be reverted.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub slot: u64,
+    pub block_hash: String,
+    pub certificate: QuorumCertificate,
+}
+
+/// Block ancestry, so a checkpoint is only accepted on top of the last one.
+pub trait BlockTree {
+    /// Whether `descendant` is `ancestor` or built on it. False if either is
+    /// unknown.
+    fn descends_from(&self, descendant: &str, ancestor: &str) -> bool;
+}
+
+fn has_quorum(power: u64, total: u64) -> bool {
+    total > 0 && (power as u128) * 3 > (total as u128) * 2
+}
+
+type VoteKey = (VoteKind, u64, u32);
+
+pub struct FinalityGadget {
+    /// Only votes for this chain count.
+    chain_id: String,
+    /// Votes per (kind, slot, round), keyed by validator public key.
+    votes: HashMap<VoteKey, HashMap<Vec<u8>, Vote>>,
+    prevote_certificates: HashMap<(u64, u32), QuorumCertificate>,
+    /// Only the latest checkpoint is kept; earlier ones are its ancestors.
+    finalized: Option<Checkpoint>,
+    finalized_sender: watch::Sender<Option<Checkpoint>>,
+}
+
+impl FinalityGadget {
+    pub fn new(chain_id: impl Into<String>) -> Self {
+        let (finalized_sender, _) = watch::channel(None);
+        Self {
+            chain_id: chain_id.into(),
+            votes: HashMap::new(),
+            prevote_certificates: HashMap::new(),
+            finalized: None,
+            finalized_sender,
+        }
+    }
+
+    /// Records a vote. Returns the certificate if this vote completed a quorum.
+    pub fn add_vote(
+        &mut self,
+        vote: Vote,
+        validators: &ValidatorSet,
+        blocks: &impl BlockTree,
+    ) -> Result<Option<QuorumCertificate>, ConsensusError> {
+        if vote.chain_id != self.chain_id {
+            return Err(ConsensusError::WrongChain(vote.chain_id));
+        }
+        vote.verify()
+            .map_err(|_| ConsensusError::InvalidSignature)?;
+        if validators.voting_power(&vote.validator_public_key) == 0 {
+            return Err(ConsensusError::UnknownValidator);
+        }
+
+        // Votes at or below the last finalized slot can't change anything
+        if let Some(latest) = self.latest_finalized() {
+            if vote.slot <= latest.slot {
+                return Ok(None);
+            }
+        }
+
+        let key = (vote.kind, vote.slot, vote.round);
+        let round_votes = self.votes.entry(key).or_default();
+        if let Some(existing) = round_votes.get(&vote.validator_public_key) {
+            if existing.block_hash != vote.block_hash {
+                return Err(ConsensusError::ConflictingVote);
+            }
+            return Ok(None); // Duplicate
+        }
+        round_votes.insert(vote.validator_public_key.clone(), vote.clone());
+
+        let supporting: Vec<Vote> = round_votes
+            .values()
+            .filter(|v| v.block_hash == vote.block_hash)
+            .cloned()
+            .collect();
+        let power: u64 = supporting
+            .iter()
+            .map(|v| validators.voting_power(&v.validator_public_key))
+            .sum();
+
+        if !has_quorum(power, validators.total_voting_power()) {
+            return Ok(None);
+        }
+
+        let certificate = QuorumCertificate {
+            chain_id: vote.chain_id.clone(),
+            kind: vote.kind,
+            slot: vote.slot,
+            round: vote.round,
+            block_hash: vote.block_hash.clone(),
+            votes: supporting,
+        };
+
+        match vote.kind {
+            VoteKind::Prevote => {
+                if self
+                    .prevote_certificates
+                    .contains_key(&(vote.slot, vote.round))
+                {
+                    return Ok(None);
+                }
+                self.prevote_certificates
+                    .insert((vote.slot, vote.round), certificate.clone());
+            }
+            VoteKind::Precommit => self.finalize(certificate.clone(), blocks)?,
+        }
+        Ok(Some(certificate))
+    }
+
+    /// Imports a precommit certificate produced elsewhere (e.g. received during sync).
+    pub fn import_certificate(
+        &mut self,
+        certificate: QuorumCertificate,
+        validators: &ValidatorSet,
+        blocks: &impl BlockTree,
+    ) -> Result<(), ConsensusError> {
+        if certificate.chain_id != self.chain_id {
+            return Err(ConsensusError::WrongChain(certificate.chain_id));
+        }
+        if certificate.kind != VoteKind::Precommit {
+            return Err(ConsensusError::InvalidCertificate(
+                "only precommit certificates finalize".to_string(),
+            ));
+        }
+        certificate.verify(validators)?;
+        if self
+            .latest_finalized()
+            .is_none_or(|latest| certificate.slot > latest.slot)
+        {
+            self.finalize(certificate, blocks)?;
+        }
+        Ok(())
+    }
+
+    /// Makes `certificate` the latest checkpoint if it builds on the current one.
+    fn finalize(
+        &mut self,
+        certificate: QuorumCertificate,
+        blocks: &impl BlockTree,
+    ) -> Result<(), ConsensusError> {
+        if let Some(latest) = &self.finalized {
+            if !blocks.descends_from(&certificate.block_hash, &latest.block_hash) {
+                return Err(ConsensusError::InvalidCertificate(
+                    "target does not extend the finalized chain".to_string(),
+                ));
+            }
+        }
+        let checkpoint = Checkpoint {
+            slot: certificate.slot,
+            block_hash: certificate.block_hash.clone(),
+            certificate,
+        };
+        let slot = checkpoint.slot;
+        self.finalized = Some(checkpoint.clone());
+        self.finalized_sender.send_replace(Some(checkpoint));
+
+        // Votes for finalized slots are no longer needed
+        self.votes.retain(|(_, s, _), _| *s > slot);
+        self.prevote_certificates.retain(|(s, _), _| *s > slot);
+        Ok(())
+    }
+
+    /// The prevote certificate for (slot, round), i.e. the signal to precommit.
+    pub fn prevote_certificate(&self, slot: u64, round: u32) -> Option<&QuorumCertificate> {
+        self.prevote_certificates.get(&(slot, round))
+    }
+
+    pub fn latest_finalized(&self) -> Option<&Checkpoint> {
+        self.finalized.as_ref()
+    }
+
+    /// Whether `block_hash` is the latest checkpoint or one of its ancestors.
+    pub fn is_finalized(&self, block_hash: &str, blocks: &impl BlockTree) -> bool {
+        self.finalized
+            .as_ref()
+            .is_some_and(|c| blocks.descends_from(&c.block_hash, block_hash))
+    }
+
+    /// Receives every new finalized checkpoint; lets exchanges and the bridge
+    /// wait for irreversibility instead of counting confirmations.
+    pub fn subscribe(&self) -> watch::Receiver<Option<Checkpoint>> {
+        self.finalized_sender.subscribe()
+    }
+}
+
+/// Waits until a checkpoint at or beyond `slot` is finalized.
+pub async fn wait_for_finality(
+    receiver: &mut watch::Receiver<Option<Checkpoint>>,
+    slot: u64,
+) -> Option<Checkpoint> {
+    receiver
+        .wait_for(|c| c.as_ref().is_some_and(|c| c.slot >= slot))
+        .await
+        .ok()
+        .and_then(|c| c.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::staking::StakeManager;
+    use crypto::KeyPair;
+
+    /// Child -> parent links.
+    #[derive(Default)]
+    struct Parents(HashMap<String, String>);
+
+    impl Parents {
+        fn chain(hashes: &[&str]) -> Self {
+            let mut parents = Self::default();
+            for pair in hashes.windows(2) {
+                parents.0.insert(pair[1].to_string(), pair[0].to_string());
+            }
+            parents
+        }
+    }
+
+    impl BlockTree for Parents {
+        fn descends_from(&self, descendant: &str, ancestor: &str) -> bool {
+            let mut hash = descendant;
+            loop {
+                if hash == ancestor {
+                    return true;
+                }
+                match self.0.get(hash) {
+                    Some(parent) => hash = parent,
+                    None => return false,
+                }
+            }
+        }
+    }
+
+    fn setup() -> (ValidatorSet, Vec<KeyPair>) {
+        let keys: Vec<KeyPair> = (0..4).map(|_| KeyPair::generate()).collect();
+        let mut stake = StakeManager::new();
+        for key in &keys {
+            stake.add_stake(key.public_key.clone(), 100);
+        }
+        (stake.process_epoch(1).clone(), keys)
+    }
+
+    fn vote(kind: VoteKind, slot: u64, hash: &str, key: &KeyPair) -> Vote {
+        Vote::new("devnet", kind, slot, 0, hash.to_string(), key).unwrap()
+    }
+
+    #[test]
+    fn test_precommit_quorum_finalizes() {
+        let (validators, keys) = setup();
+        let blocks = Parents::chain(&["0", "abc"]);
+        let mut gadget = FinalityGadget::new("devnet");
+
+        for key in &keys[..2] {
+            let qc = gadget
+                .add_vote(
+                    vote(VoteKind::Precommit, 5, "abc", key),
+                    &validators,
+                    &blocks,
+                )
+                .unwrap();
+            assert!(qc.is_none());
+        }
+        assert!(gadget.latest_finalized().is_none());
+
+        // 3 of 4 equal-power validators is > 2/3
+        let qc = gadget
+            .add_vote(
+                vote(VoteKind::Precommit, 5, "abc", &keys[2]),
+                &validators,
+                &blocks,
+            )
+            .unwrap()
+            .unwrap();
+        assert!(qc.verify(&validators).is_ok());
+        assert!(gadget.is_finalized("abc", &blocks));
+        assert!(gadget.is_finalized("0", &blocks));
+        assert_eq!(gadget.latest_finalized().unwrap().slot, 5);
+    }
+
+    #[test]
+    fn test_prevote_quorum_does_not_finalize() {
+        let (validators, keys) = setup();
+        let blocks = Parents::chain(&["0", "abc"]);
+        let mut gadget = FinalityGadget::new("devnet");
+        for key in &keys[..3] {
+            gadget
+                .add_vote(vote(VoteKind::Prevote, 1, "abc", key), &validators, &blocks)
+                .unwrap();
+        }
+        assert!(gadget.prevote_certificate(1, 0).is_some());
+        assert!(!gadget.is_finalized("abc", &blocks));
+    }
+
+    #[test]
+    fn test_conflicting_and_forged_votes() {
+        let (validators, keys) = setup();
+        let blocks = Parents::default();
+        let mut gadget = FinalityGadget::new("devnet");
+
+        gadget
+            .add_vote(
+                vote(VoteKind::Precommit, 2, "a", &keys[0]),
+                &validators,
+                &blocks,
+            )
+            .unwrap();
+        assert!(matches!(
+            gadget.add_vote(
+                vote(VoteKind::Precommit, 2, "b", &keys[0]),
+                &validators,
+                &blocks
+            ),
+            Err(ConsensusError::ConflictingVote)
+        ));
+
+        let mut forged = vote(VoteKind::Precommit, 2, "a", &keys[1]);
+        forged.block_hash = "b".to_string();
+        assert!(matches!(
+            gadget.add_vote(forged, &validators, &blocks),
+            Err(ConsensusError::InvalidSignature)
+        ));
+
+        let outsider = KeyPair::generate();
+        assert!(matches!(
+            gadget.add_vote(
+                vote(VoteKind::Precommit, 2, "a", &outsider),
+                &validators,
+                &blocks
+            ),
+            Err(ConsensusError::UnknownValidator)
+        ));
+
+        let other_chain = Vote::new("mainnet", VoteKind::Precommit, 2, 0, "a".into(), &keys[1]);
+        assert!(matches!(
+            gadget.add_vote(other_chain.unwrap(), &validators, &blocks),
+            Err(ConsensusError::WrongChain(_))
+        ));
+    }
+
+    #[test]
+    fn test_checkpoints_must_extend_the_last() {
+        let (validators, keys) = setup();
+        let blocks = Parents::chain(&["0", "a", "b"]);
+        let mut gadget = FinalityGadget::new("devnet");
+        let precommit = |gadget: &mut FinalityGadget, slot, hash: &str| {
+            keys[..3]
+                .iter()
+                .map(|key| {
+                    gadget.add_vote(
+                        vote(VoteKind::Precommit, slot, hash, key),
+                        &validators,
+                        &blocks,
+                    )
+                })
+                .last()
+                .unwrap()
+        };
+
+        precommit(&mut gadget, 1, "a").unwrap();
+        // "x" is not built on "a"
+        assert!(matches!(
+            precommit(&mut gadget, 2, "x"),
+            Err(ConsensusError::InvalidCertificate(_))
+        ));
+        assert_eq!(gadget.latest_finalized().unwrap().block_hash, "a");
+
+        precommit(&mut gadget, 3, "b").unwrap();
+        assert_eq!(gadget.latest_finalized().unwrap().slot, 3);
+        assert!(gadget.is_finalized("a", &blocks));
+        // Votes at or below the checkpoint are gone
+        assert!(gadget.votes.keys().all(|(_, slot, _)| *slot > 3));
+    }
+
+    #[test]
+    fn test_certificate_below_quorum_rejected() {
+        let (validators, keys) = setup();
+        let mut gadget = FinalityGadget::new("devnet");
+        let qc = QuorumCertificate {
+            chain_id: "devnet".to_string(),
+            kind: VoteKind::Precommit,
+            slot: 9,
+            round: 0,
+            block_hash: "abc".to_string(),
+            votes: keys[..2]
+                .iter()
+                .map(|k| vote(VoteKind::Precommit, 9, "abc", k))
+                .collect(),
+        };
+        assert!(gadget
+            .import_certificate(qc, &validators, &Parents::default())
+            .is_err());
+        assert!(gadget.latest_finalized().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_subscribers_notified() {
+        let (validators, keys) = setup();
+        let blocks = Parents::default();
+        let mut gadget = FinalityGadget::new("devnet");
+        let mut receiver = gadget.subscribe();
+
+        for key in &keys[..3] {
+            gadget
+                .add_vote(
+                    vote(VoteKind::Precommit, 8, "abc", key),
+                    &validators,
+                    &blocks,
+                )
+                .unwrap();
+        }
+
+        let checkpoint = wait_for_finality(&mut receiver, 8).await.unwrap();
+        assert_eq!(checkpoint.block_hash, "abc");
+    }
+}
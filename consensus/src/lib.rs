@@ -1,3 +1,10 @@
+pub mod clock;
+pub mod delegation;
 pub mod engine;
+pub mod finality;
+pub mod persistence;
+pub mod poa;
+pub mod pos;
+pub mod randao;
 pub mod staking;
 pub mod validator;
@@ -0,0 +1,174 @@
+use crate::delegation::{tokens_for_shares, Delegation};
+use crate::staking::StakeManager;
+use crate::validator::{Validator, ValidatorSet};
+use serde::{Deserialize, Serialize};
+use storage::db::{StateStore, StorageError};
+
+const STATE_KEY: &[u8] = b"staking/state";
+const SNAPSHOT_PREFIX: &[u8] = b"staking/epoch/";
+
+fn snapshot_key(epoch: u64) -> Vec<u8> {
+    // Big-endian so keys sort by epoch
+    let mut key = SNAPSHOT_PREFIX.to_vec();
+    key.extend_from_slice(&epoch.to_be_bytes());
+    key
+}
+
+/// Stake as it stood at the end of an epoch boundary. Used to answer
+/// historical queries (governance tallies, reward payouts, slashing for old
+/// offenses) after the live state has moved on.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StakeSnapshot {
+    pub epoch: u64,
+    /// Sorted by public key.
+    pub validators: Vec<Validator>,
+    /// Sorted by (validator, delegator).
+    pub delegations: Vec<Delegation>,
+    pub validator_set: ValidatorSet,
+}
+
+impl StakeSnapshot {
+    pub fn validator(&self, public_key: &[u8]) -> Option<&Validator> {
+        self.validators
+            .binary_search_by(|v| v.public_key.as_slice().cmp(public_key))
+            .ok()
+            .map(|idx| &self.validators[idx])
+    }
+
+    pub fn stake_of(&self, public_key: &[u8]) -> u64 {
+        self.validator(public_key).map_or(0, |v| v.stake)
+    }
+
+    pub fn delegation_value(&self, delegator: &[u8], validator: &[u8]) -> u64 {
+        let Some(pool) = self.validator(validator) else {
+            return 0;
+        };
+        self.delegations
+            .iter()
+            .find(|d| d.delegator == delegator && d.validator == validator)
+            .map_or(0, |d| {
+                tokens_for_shares(d.shares, pool.stake, pool.delegator_shares)
+            })
+    }
+}
+
+impl StakeManager {
+    /// Writes the live staking state, plus the snapshot for the current epoch
+    /// if it has none yet. Call right after `process_epoch` so the snapshot
+    /// holds stake as it was at the boundary; later persists in the same
+    /// epoch leave it alone.
+    pub fn persist<S: StateStore>(&self, store: &S) -> Result<(), StorageError> {
+        store.put(STATE_KEY, &bincode::serialize(self)?)?;
+        let key = snapshot_key(self.current_epoch());
+        if store.get(&key)?.is_none() {
+            store.put(&key, &bincode::serialize(&self.snapshot())?)?;
+        }
+        store.flush()
+    }
+
+    /// Restores the state written by `persist`, if any.
+    pub fn load<S: StateStore>(store: &S) -> Result<Option<Self>, StorageError> {
+        store
+            .get(STATE_KEY)?
+            .map(|bytes| bincode::deserialize(&bytes).map_err(StorageError::from))
+            .transpose()
+    }
+
+    /// The snapshot persisted at the boundary of `epoch`.
+    pub fn snapshot_at<S: StateStore>(
+        store: &S,
+        epoch: u64,
+    ) -> Result<Option<StakeSnapshot>, StorageError> {
+        store
+            .get(&snapshot_key(epoch))?
+            .map(|bytes| bincode::deserialize(&bytes).map_err(StorageError::from))
+            .transpose()
+    }
+
+    /// A validator's bonded stake at `epoch`, or 0 if no snapshot was taken then.
+    pub fn stake_at_epoch<S: StateStore>(
+        store: &S,
+        public_key: &[u8],
+        epoch: u64,
+    ) -> Result<u64, StorageError> {
+        Ok(Self::snapshot_at(store, epoch)?.map_or(0, |s| s.stake_of(public_key)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use storage::db::SledStore;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_state_survives_restart() {
+        let dir = tempdir().unwrap();
+        let store = SledStore::new(dir.path()).unwrap();
+        let validator = vec![1];
+        let delegator = vec![10];
+        {
+            let mut manager = StakeManager::new();
+            manager.add_stake(validator.clone(), 100);
+            manager
+                .delegate(delegator.clone(), &validator, 300)
+                .unwrap();
+            manager.process_epoch(1);
+            manager.persist(&store).unwrap();
+        }
+
+        // Reopening the path could race sled releasing its file lock
+        let manager = StakeManager::load(&store).unwrap().unwrap();
+        assert_eq!(manager.current_epoch(), 1);
+        assert_eq!(manager.get_validator(&validator).unwrap().stake, 400);
+        assert_eq!(manager.delegation_value(&delegator, &validator), 300);
+        assert_eq!(manager.get_voting_power(&validator), 20);
+        assert!(manager.validator_set().verify_hash());
+    }
+
+    #[test]
+    fn test_historical_snapshots() {
+        let dir = tempdir().unwrap();
+        let store = SledStore::new(dir.path()).unwrap();
+        assert!(StakeManager::load(&store).unwrap().is_none());
+
+        let validator = vec![1];
+        let mut manager = StakeManager::new();
+        manager.add_stake(validator.clone(), 100);
+        manager.process_epoch(1);
+        manager.persist(&store).unwrap();
+
+        manager.add_stake(validator.clone(), 300);
+        manager.process_epoch(2);
+        manager.persist(&store).unwrap();
+        // Rewards later in the epoch don't rewrite its snapshot
+        manager.distribute_reward(&validator, 100).unwrap();
+        manager.persist(&store).unwrap();
+
+        assert_eq!(
+            StakeManager::stake_at_epoch(&store, &validator, 1).unwrap(),
+            100
+        );
+        assert_eq!(
+            StakeManager::stake_at_epoch(&store, &validator, 2).unwrap(),
+            400
+        );
+        assert_eq!(
+            StakeManager::load(&store)
+                .unwrap()
+                .unwrap()
+                .get_validator(&validator)
+                .unwrap()
+                .stake,
+            500
+        );
+        assert_eq!(
+            StakeManager::stake_at_epoch(&store, &validator, 3).unwrap(),
+            0
+        );
+
+        let snapshot = StakeManager::snapshot_at(&store, 1).unwrap().unwrap();
+        assert_eq!(snapshot.validator_set.total_voting_power(), 10);
+        assert_eq!(snapshot.delegation_value(&validator, &validator), 100);
+    }
+}
@@ -0,0 +1,201 @@
+use crate::engine::{ConsensusEngine, ConsensusError};
+use crypto::KeyPair;
+use ledger::block::BlockHeader;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum AuthorityError {
+    #[error("Key is already an authority")]
+    AlreadyAuthority,
+    #[error("Key is not an authority")]
+    NotAuthority,
+    #[error("Cannot remove the last authority")]
+    LastAuthority,
+    #[error("Malformed authority change")]
+    Malformed,
+}
+
+/// Edit to the authority list, carried as governance proposal call data.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuthorityChange {
+    Add(Vec<u8>),
+    Remove(Vec<u8>),
+}
+
+impl AuthorityChange {
+    pub fn encode(&self) -> Vec<u8> {
+        bincode::serialize(self).unwrap()
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, AuthorityError> {
+        bincode::deserialize(bytes).map_err(|_| AuthorityError::Malformed)
+    }
+}
+
+/// Proof-of-authority engine for devnets and consortium chains: a fixed list
+/// of authorities takes turns proposing, slot by slot. No stake involved.
+#[derive(Debug, Clone)]
+pub struct PoaEngine {
+    authorities: Vec<Vec<u8>>,
+}
+
+impl PoaEngine {
+    pub fn new(authorities: Vec<Vec<u8>>) -> Self {
+        let mut engine = Self {
+            authorities: Vec::new(),
+        };
+        for authority in authorities {
+            let _ = engine.add_authority(authority);
+        }
+        engine
+    }
+
+    pub fn authorities(&self) -> &[Vec<u8>] {
+        &self.authorities
+    }
+
+    pub fn is_authority(&self, public_key: &[u8]) -> bool {
+        self.authorities.iter().any(|a| a == public_key)
+    }
+
+    pub fn add_authority(&mut self, public_key: Vec<u8>) -> Result<(), AuthorityError> {
+        if self.is_authority(&public_key) {
+            return Err(AuthorityError::AlreadyAuthority);
+        }
+        self.authorities.push(public_key);
+        Ok(())
+    }
+
+    pub fn remove_authority(&mut self, public_key: &[u8]) -> Result<(), AuthorityError> {
+        let index = self
+            .authorities
+            .iter()
+            .position(|a| a == public_key)
+            .ok_or(AuthorityError::NotAuthority)?;
+        if self.authorities.len() == 1 {
+            return Err(AuthorityError::LastAuthority);
+        }
+        self.authorities.remove(index);
+        Ok(())
+    }
+
+    pub fn apply_change(&mut self, change: &AuthorityChange) -> Result<(), AuthorityError> {
+        match change {
+            AuthorityChange::Add(key) => self.add_authority(key.clone()),
+            AuthorityChange::Remove(key) => self.remove_authority(key),
+        }
+    }
+}
+
+impl ConsensusEngine for PoaEngine {
+    fn verify_block_header(&self, header: &BlockHeader) -> Result<(), ConsensusError> {
+        if !self.is_authority(&header.validator_public_key) {
+            return Err(ConsensusError::UnknownValidator);
+        }
+
+        let expected = self
+            .proposer_for_slot(header.slot)
+            .ok_or(ConsensusError::NoEligibleProposer)?;
+        if expected != header.validator_public_key {
+            return Err(ConsensusError::WrongProposer {
+                slot: header.slot,
+                expected: hex::encode(expected),
+                actual: hex::encode(&header.validator_public_key),
+            });
+        }
+
+        KeyPair::verify(
+            &header.validator_public_key,
+            &header.signing_bytes(),
+            &header.signature,
+        )
+        .map_err(|_| ConsensusError::InvalidSignature)?;
+
+        Ok(())
+    }
+
+    /// Round-robin over the authority list in the order it was configured.
+    fn proposer_for_slot(&self, slot: u64) -> Option<Vec<u8>> {
+        if self.authorities.is_empty() {
+            return None;
+        }
+        let index = (slot % self.authorities.len() as u64) as usize;
+        Some(self.authorities[index].clone())
+    }
+
+    /// Same rule as PoS: highest slot wins, lowest hash breaks ties.
+    fn choose_fork<'a>(&self, forks: &'a [BlockHeader]) -> Option<&'a BlockHeader> {
+        forks
+            .iter()
+            .max_by(|a, b| a.slot.cmp(&b.slot).then_with(|| b.hash().cmp(&a.hash())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ledger::block::Block;
+
+    fn signed(slot: u64, proposer: &KeyPair) -> BlockHeader {
+        let mut block = Block::new(
+            "devnet",
+            "0".to_string(),
+            0,
+            slot,
+            "root".to_string(),
+            vec![],
+            proposer.public_key.clone(),
+        );
+        block.header.signature = proposer.sign(&block.header.signing_bytes()).unwrap();
+        block.header
+    }
+
+    #[test]
+    fn test_round_robin_schedule() {
+        let keys: Vec<KeyPair> = (0..3).map(|_| KeyPair::generate()).collect();
+        let engine = PoaEngine::new(keys.iter().map(|k| k.public_key.clone()).collect());
+
+        for slot in 0..6 {
+            let proposer = &keys[slot as usize % 3];
+            assert_eq!(
+                engine.proposer_for_slot(slot),
+                Some(proposer.public_key.clone())
+            );
+            assert!(engine.verify_block_header(&signed(slot, proposer)).is_ok());
+        }
+        assert!(matches!(
+            engine.verify_block_header(&signed(1, &keys[0])),
+            Err(ConsensusError::WrongProposer { .. })
+        ));
+        assert!(matches!(
+            engine.verify_block_header(&signed(0, &KeyPair::generate())),
+            Err(ConsensusError::UnknownValidator)
+        ));
+    }
+
+    #[test]
+    fn test_authority_changes() {
+        let a = KeyPair::generate().public_key;
+        let b = KeyPair::generate().public_key;
+        let mut engine = PoaEngine::new(vec![a.clone()]);
+
+        let add = AuthorityChange::decode(&AuthorityChange::Add(b.clone()).encode()).unwrap();
+        engine.apply_change(&add).unwrap();
+        assert_eq!(
+            engine.apply_change(&add),
+            Err(AuthorityError::AlreadyAuthority)
+        );
+        assert_eq!(engine.proposer_for_slot(1), Some(b.clone()));
+
+        engine.remove_authority(&a).unwrap();
+        assert_eq!(
+            engine.remove_authority(&b),
+            Err(AuthorityError::LastAuthority)
+        );
+        assert_eq!(
+            AuthorityChange::decode(&[0xff]),
+            Err(AuthorityError::Malformed)
+        );
+    }
+}
@@ -0,0 +1,214 @@
+use crate::engine::{ConsensusEngine, ConsensusError};
+use crate::staking::StakeManager;
+use crypto::KeyPair;
+use ledger::block::BlockHeader;
+
+/// Proof-of-stake engine: each slot has exactly one proposer, sampled with
+/// probability proportional to voting power.
+pub struct PosEngine {
+    stake_manager: StakeManager,
+    /// Mixed into proposer sampling so the schedule isn't a pure function of the slot.
+    seed: [u8; 32],
+}
+
+impl PosEngine {
+    pub fn new(stake_manager: StakeManager) -> Self {
+        Self {
+            stake_manager,
+            seed: [0u8; 32],
+        }
+    }
+
+    pub fn with_seed(mut self, seed: [u8; 32]) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn set_seed(&mut self, seed: [u8; 32]) {
+        self.seed = seed;
+    }
+
+    pub fn stake_manager(&self) -> &StakeManager {
+        &self.stake_manager
+    }
+
+    pub fn stake_manager_mut(&mut self) -> &mut StakeManager {
+        &mut self.stake_manager
+    }
+}
+
+impl ConsensusEngine for PosEngine {
+    fn verify_block_header(&self, header: &BlockHeader) -> Result<(), ConsensusError> {
+        let validator = self
+            .stake_manager
+            .get_validator(&header.validator_public_key)
+            .ok_or(ConsensusError::UnknownValidator)?;
+
+        if validator.is_tombstoned {
+            return Err(ConsensusError::SlashedValidator);
+        }
+        if validator.jailed_until.is_some() {
+            return Err(ConsensusError::JailedValidator);
+        }
+
+        let expected = self
+            .proposer_for_slot(header.slot)
+            .ok_or(ConsensusError::NoEligibleProposer)?;
+        if expected != header.validator_public_key {
+            return Err(ConsensusError::WrongProposer {
+                slot: header.slot,
+                expected: hex::encode(expected),
+                actual: hex::encode(&header.validator_public_key),
+            });
+        }
+
+        KeyPair::verify(
+            &header.validator_public_key,
+            &header.signing_bytes(),
+            &header.signature,
+        )
+        .map_err(|_| ConsensusError::InvalidSignature)?;
+
+        Ok(())
+    }
+
+    /// Picks the proposer for `slot` by hashing (seed, slot) to a point in
+    /// [0, total_power) and walking the cumulative voting power of the current
+    /// epoch's validator set.
+    fn proposer_for_slot(&self, slot: u64) -> Option<Vec<u8>> {
+        let validator_set = self.stake_manager.validator_set();
+        let weighted: Vec<(&[u8], u64)> = validator_set
+            .entries()
+            .iter()
+            .map(|e| (e.public_key.as_slice(), e.voting_power))
+            .collect();
+
+        let total: u64 = weighted.iter().map(|(_, power)| power).sum();
+        if total == 0 {
+            return None;
+        }
+
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&self.seed);
+        hasher.update(&slot.to_le_bytes());
+        let digest = hasher.finalize();
+        let sample = u64::from_le_bytes(digest.as_bytes()[..8].try_into().unwrap());
+        let mut target = sample % total;
+
+        for (public_key, power) in weighted {
+            if target < power {
+                return Some(public_key.to_vec());
+            }
+            target -= power;
+        }
+        None
+    }
+
+    /// Prefers the tip at the highest slot; ties are broken by the lowest block
+    /// hash so every node picks the same fork.
+    fn choose_fork<'a>(&self, forks: &'a [BlockHeader]) -> Option<&'a BlockHeader> {
+        forks
+            .iter()
+            .max_by(|a, b| a.slot.cmp(&b.slot).then_with(|| b.hash().cmp(&a.hash())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(slot: u64, proposer: &KeyPair) -> BlockHeader {
+        let mut header = BlockHeader {
+            chain_id: "devnet".to_string(),
+            parent_hash: "0".to_string(),
+            timestamp: 0,
+            slot,
+            state_root: "".to_string(),
+            transactions_root: "".to_string(),
+            evidence_root: "".to_string(),
+            randao_reveal: Vec::new(),
+            randao_mix: [0u8; 32],
+            validator_public_key: proposer.public_key.clone(),
+            signature: vec![],
+        };
+        header.signature = proposer.sign(&header.signing_bytes()).unwrap();
+        header
+    }
+
+    fn setup() -> (PosEngine, Vec<KeyPair>) {
+        let keys: Vec<KeyPair> = (0..3).map(|_| KeyPair::generate()).collect();
+        let mut stake = StakeManager::new();
+        stake.add_stake(keys[0].public_key.clone(), 100); // power 10
+        stake.add_stake(keys[1].public_key.clone(), 400); // power 20
+        stake.add_stake(keys[2].public_key.clone(), 900); // power 30
+        stake.process_epoch(1);
+        (PosEngine::new(stake), keys)
+    }
+
+    fn key_for<'a>(keys: &'a [KeyPair], public_key: &[u8]) -> &'a KeyPair {
+        keys.iter().find(|k| k.public_key == public_key).unwrap()
+    }
+
+    #[test]
+    fn test_proposer_sampling_is_stake_weighted() {
+        let (engine, keys) = setup();
+        let mut counts = [0u32; 3];
+        for slot in 0..6000 {
+            let proposer = engine.proposer_for_slot(slot).unwrap();
+            let idx = keys.iter().position(|k| k.public_key == proposer).unwrap();
+            counts[idx] += 1;
+        }
+        // Expected 1000 / 2000 / 3000
+        assert!((800..1200).contains(&counts[0]));
+        assert!((1800..2200).contains(&counts[1]));
+        assert!((2800..3200).contains(&counts[2]));
+    }
+
+    #[test]
+    fn test_verify_assigned_proposer() {
+        let (engine, keys) = setup();
+        let slot = 7;
+        let proposer = engine.proposer_for_slot(slot).unwrap();
+        let good = header(slot, key_for(&keys, &proposer));
+        assert!(engine.verify_block_header(&good).is_ok());
+
+        let other = keys.iter().find(|k| k.public_key != proposer).unwrap();
+        assert!(matches!(
+            engine.verify_block_header(&header(slot, other)),
+            Err(ConsensusError::WrongProposer { .. })
+        ));
+
+        let mut forged = good.clone();
+        forged.state_root = "tampered".to_string();
+        assert!(matches!(
+            engine.verify_block_header(&forged),
+            Err(ConsensusError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn test_slashed_proposer_rejected() {
+        let (mut engine, keys) = setup();
+        let slot = 3;
+        let proposer = engine.proposer_for_slot(slot).unwrap();
+        let block = header(slot, key_for(&keys, &proposer));
+
+        engine.stake_manager_mut().tombstone(&proposer);
+        assert!(matches!(
+            engine.verify_block_header(&block),
+            Err(ConsensusError::SlashedValidator)
+        ));
+    }
+
+    #[test]
+    fn test_choose_fork_prefers_highest_slot() {
+        let (engine, keys) = setup();
+        let forks = vec![
+            header(5, &keys[0]),
+            header(9, &keys[1]),
+            header(7, &keys[2]),
+        ];
+        assert_eq!(engine.choose_fork(&forks).unwrap().slot, 9);
+        assert!(engine.choose_fork(&[]).is_none());
+    }
+}
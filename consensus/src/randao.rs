@@ -0,0 +1,118 @@
+use crate::engine::ConsensusError;
+use ledger::block::BlockHeader;
+use ledger::randao::{self, RandaoMix};
+use std::collections::BTreeMap;
+
+/// Checks that `header` carries a valid reveal for `epoch` from its proposer
+/// and that its mix is `parent_mix` with that reveal folded in.
+pub fn verify_randao(
+    header: &BlockHeader,
+    parent_mix: &RandaoMix,
+    epoch: u64,
+) -> Result<(), ConsensusError> {
+    if !randao::verify_reveal(&header.validator_public_key, epoch, &header.randao_reveal) {
+        return Err(ConsensusError::InvalidRandaoReveal);
+    }
+    if header.randao_mix != randao::mix_in(parent_mix, &header.randao_reveal) {
+        return Err(ConsensusError::InvalidRandaoMix);
+    }
+    Ok(())
+}
+
+/// Follows the mix along the canonical chain and remembers the final mix of
+/// each finished epoch, which is what consumers should sample from: it can't
+/// be influenced any more by the proposers still to come.
+#[derive(Debug, Clone, Default)]
+pub struct RandaoBeacon {
+    mix: RandaoMix,
+    epoch: u64,
+    epoch_mixes: BTreeMap<u64, RandaoMix>,
+}
+
+impl RandaoBeacon {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mix as of the latest imported block.
+    pub fn current_mix(&self) -> RandaoMix {
+        self.mix
+    }
+
+    /// Records an imported block from `epoch`. Moving into a later epoch seals
+    /// the previous one.
+    pub fn on_block(&mut self, header: &BlockHeader, epoch: u64) {
+        if epoch > self.epoch {
+            self.epoch_mixes.insert(self.epoch, self.mix);
+            self.epoch = epoch;
+        }
+        self.mix = header.randao_mix;
+    }
+
+    /// Final mix of a finished epoch.
+    pub fn epoch_mix(&self, epoch: u64) -> Option<RandaoMix> {
+        self.epoch_mixes.get(&epoch).copied()
+    }
+
+    pub fn prune_below(&mut self, epoch: u64) {
+        self.epoch_mixes = self.epoch_mixes.split_off(&epoch);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::KeyPair;
+    use ledger::block::Block;
+
+    fn block(keypair: &KeyPair, slot: u64, epoch: u64, parent_mix: &RandaoMix) -> Block {
+        let reveal = randao::reveal(keypair, epoch).unwrap();
+        Block::new(
+            "devnet",
+            "0".to_string(),
+            0,
+            slot,
+            "root".to_string(),
+            vec![],
+            keypair.public_key.clone(),
+        )
+        .with_randao(reveal, parent_mix)
+    }
+
+    #[test]
+    fn test_verify_randao() {
+        let proposer = KeyPair::generate();
+        let parent_mix = [7u8; 32];
+        let good = block(&proposer, 1, 0, &parent_mix);
+        assert!(verify_randao(&good.header, &parent_mix, 0).is_ok());
+
+        // Reveal for the wrong epoch
+        assert!(matches!(
+            verify_randao(&good.header, &parent_mix, 1),
+            Err(ConsensusError::InvalidRandaoReveal)
+        ));
+        // Mix built on a different parent
+        assert!(matches!(
+            verify_randao(&good.header, &[0u8; 32], 0),
+            Err(ConsensusError::InvalidRandaoMix)
+        ));
+    }
+
+    #[test]
+    fn test_beacon_seals_epochs() {
+        let proposer = KeyPair::generate();
+        let mut beacon = RandaoBeacon::new();
+
+        let first = block(&proposer, 0, 0, &beacon.current_mix());
+        beacon.on_block(&first.header, 0);
+        let second = block(&proposer, 5, 1, &beacon.current_mix());
+        beacon.on_block(&second.header, 1);
+
+        assert_eq!(beacon.epoch_mix(0), Some(first.header.randao_mix));
+        assert_eq!(beacon.epoch_mix(1), None);
+        assert_eq!(beacon.current_mix(), second.header.randao_mix);
+
+        beacon.prune_below(1);
+        assert_eq!(beacon.epoch_mix(0), None);
+    }
+}
@@ -1,50 +1,666 @@
-use crate::validator::Validator;
-use std::collections::HashMap;
+use crate::delegation::{
+    shares_for_tokens, shares_to_burn, tokens_for_shares, Delegation, UnbondingEntry,
+    MAX_COMMISSION_BPS,
+};
+use crate::persistence::StakeSnapshot;
+use crate::validator::{Validator, ValidatorSet, ValidatorSetEntry, ValidatorStatus};
+use crypto::KeyPair;
+use identity::credential::VerifiableCredential;
+use identity::did::DIDDocument;
+use ledger::transaction::{StakingAction, StakingTransaction};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use thiserror::Error;
 
+/// Default number of validators that may enter (and, separately, leave) the
+/// active set per epoch.
+pub const DEFAULT_CHURN_LIMIT: usize = 4;
+
+/// Default number of epochs undelegated tokens stay locked (and slashable).
+pub const DEFAULT_UNBONDING_PERIOD: u64 = 14;
+
+const IDENTITY_BINDING_DOMAIN: &[u8] = b"vajra/identity-binding/1";
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum StakingError {
+    #[error("Validator not found")]
+    UnknownValidator,
+    #[error("Validator is slashed or has exited")]
+    ValidatorUnavailable,
+    #[error("Delegation not found")]
+    UnknownDelegation,
+    #[error("Insufficient delegated stake")]
+    InsufficientStake,
+    #[error("Commission must be at most {MAX_COMMISSION_BPS} basis points")]
+    InvalidCommission,
+    #[error("Amount must be non-zero")]
+    ZeroAmount,
+    #[error("Validator is not jailed")]
+    NotJailed,
+    #[error("Validator is jailed until epoch {0}")]
+    StillJailed(u64),
+    #[error("Invalid staking transaction signature")]
+    InvalidSignature,
+    #[error("Invalid staking transaction nonce")]
+    InvalidNonce,
+    #[error("Staking transaction is for chain {0}")]
+    WrongChain(String),
+    #[error("Credential issuer is not trusted")]
+    UntrustedIssuer,
+    #[error("Credential does not verify for this identity")]
+    InvalidCredential,
+    #[error("Validator is already bound to an identity")]
+    AlreadyBound,
+    #[error("Identity's controller did not approve the binding")]
+    UnapprovedBinding,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PendingDeposit {
+    delegator: Vec<u8>,
+    validator: Vec<u8>,
+    amount: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct StakeManager {
     validators: HashMap<Vec<u8>, Validator>,
+    /// Keyed by (delegator, validator).
+    delegations: HashMap<(Vec<u8>, Vec<u8>), Delegation>,
+    /// Deposits waiting for the next epoch boundary, in arrival order.
+    pending_deposits: VecDeque<PendingDeposit>,
+    exit_queue: VecDeque<Vec<u8>>,
+    unbonding: Vec<UnbondingEntry>,
+    /// Matured unbondings and commission, ready to be paid out.
+    withdrawable: HashMap<Vec<u8>, u64>,
+    /// Next expected nonce for each validator's staking transactions.
+    staking_nonces: HashMap<Vec<u8>, u64>,
+    /// When set, voting power is computed per identity rather than per key.
+    identity_weighting: bool,
+    /// DIDs whose credentials are accepted when binding validators.
+    trusted_issuers: HashSet<String>,
+    /// Validator public key -> DID it is bound to.
+    identities: HashMap<Vec<u8>, String>,
+    /// Bindings accepted since the last epoch boundary.
+    pending_identities: Vec<(Vec<u8>, String)>,
+    churn_limit: usize,
+    unbonding_period: u64,
+    current_epoch: u64,
+    validator_set: ValidatorSet,
 }
 
 impl StakeManager {
     pub fn new() -> Self {
         Self {
             validators: HashMap::new(),
+            delegations: HashMap::new(),
+            pending_deposits: VecDeque::new(),
+            exit_queue: VecDeque::new(),
+            unbonding: Vec::new(),
+            withdrawable: HashMap::new(),
+            staking_nonces: HashMap::new(),
+            identity_weighting: false,
+            trusted_issuers: HashSet::new(),
+            identities: HashMap::new(),
+            pending_identities: Vec::new(),
+            churn_limit: DEFAULT_CHURN_LIMIT,
+            unbonding_period: DEFAULT_UNBONDING_PERIOD,
+            current_epoch: 0,
+            validator_set: ValidatorSet::new(0, Vec::new()),
+        }
+    }
+
+    pub fn with_churn_limit(mut self, churn_limit: usize) -> Self {
+        self.churn_limit = churn_limit.max(1);
+        self
+    }
+
+    pub fn with_unbonding_period(mut self, epochs: u64) -> Self {
+        self.unbonding_period = epochs;
+        self
+    }
+
+    /// Applies the sqrt curve to the total stake of each identity instead of
+    /// each key, so splitting stake across keys gains nothing. Validators must
+    /// be bound to an identity (via a credential from one of `trusted_issuers`)
+    /// to get any voting power in this mode.
+    pub fn with_identity_weighting(
+        mut self,
+        trusted_issuers: impl IntoIterator<Item = String>,
+    ) -> Self {
+        self.identity_weighting = true;
+        self.trusted_issuers = trusted_issuers.into_iter().collect();
+        self
+    }
+
+    /// Binds a validator key to `did`, backed by a credential issued to it by a
+    /// trusted issuer. Both the validator and the DID's controller sign
+    /// `identity_binding_bytes`, so neither side can be bound without its
+    /// consent. A key is bound once, and the binding takes effect at the next
+    /// epoch boundary.
+    pub fn bind_identity(
+        &mut self,
+        validator: &[u8],
+        did: &DIDDocument,
+        credential: &VerifiableCredential,
+        signature: &[u8],
+        controller_signature: &[u8],
+    ) -> Result<(), StakingError> {
+        if !self.validators.contains_key(validator) {
+            return Err(StakingError::UnknownValidator);
+        }
+        if self.identities.contains_key(validator)
+            || self
+                .pending_identities
+                .iter()
+                .any(|(key, _)| key == validator)
+        {
+            return Err(StakingError::AlreadyBound);
+        }
+        let signed = identity_binding_bytes(validator, &did.id, credential);
+        if !KeyPair::verify(validator, &signed, signature).unwrap_or(false) {
+            return Err(StakingError::InvalidSignature);
+        }
+        // The controller key comes from the DID itself, not the document we were handed
+        let controller = DIDDocument::resolve(&did.id).ok_or(StakingError::UnapprovedBinding)?;
+        if !KeyPair::verify(&controller.public_key, &signed, controller_signature).unwrap_or(false)
+        {
+            return Err(StakingError::UnapprovedBinding);
+        }
+        if !self.trusted_issuers.contains(&credential.issuer) {
+            return Err(StakingError::UntrustedIssuer);
         }
+        let issuer =
+            DIDDocument::resolve(&credential.issuer).ok_or(StakingError::UntrustedIssuer)?;
+        if credential.subject != did.id || !credential.verify(&issuer.public_key) {
+            return Err(StakingError::InvalidCredential);
+        }
+        self.pending_identities
+            .push((validator.to_vec(), did.id.clone()));
+        Ok(())
+    }
+
+    pub fn identity_of(&self, validator: &[u8]) -> Option<&str> {
+        self.identities.get(validator).map(String::as_str)
     }
 
+    /// Queues a self-stake deposit, registering the validator if needed. It only
+    /// counts towards voting power once activated by `process_epoch`.
     pub fn add_stake(&mut self, public_key: Vec<u8>, amount: u64) {
         let validator = self
             .validators
             .entry(public_key.clone())
-            .or_insert_with(|| Validator::new(public_key, 0));
+            .or_insert_with(|| Validator::new(public_key.clone(), 0));
+
+        if !validator.is_tombstoned && validator.status != ValidatorStatus::Exited {
+            self.pending_deposits.push_back(PendingDeposit {
+                delegator: public_key.clone(),
+                validator: public_key,
+                amount,
+            });
+        }
+    }
+
+    /// Queues a delegation to an existing validator; shares are minted when it
+    /// is bonded at the next epoch boundary.
+    pub fn delegate(
+        &mut self,
+        delegator: Vec<u8>,
+        validator: &[u8],
+        amount: u64,
+    ) -> Result<(), StakingError> {
+        if amount == 0 {
+            return Err(StakingError::ZeroAmount);
+        }
+        let target = self
+            .validators
+            .get(validator)
+            .ok_or(StakingError::UnknownValidator)?;
+        if target.is_tombstoned
+            || matches!(
+                target.status,
+                ValidatorStatus::Exiting | ValidatorStatus::Exited
+            )
+        {
+            return Err(StakingError::ValidatorUnavailable);
+        }
+
+        self.pending_deposits.push_back(PendingDeposit {
+            delegator,
+            validator: validator.to_vec(),
+            amount,
+        });
+        Ok(())
+    }
+
+    /// Starts unbonding `amount` tokens of a delegation. The tokens leave the
+    /// validator's pool now but only become withdrawable after the unbonding period.
+    pub fn undelegate(
+        &mut self,
+        delegator: &[u8],
+        validator: &[u8],
+        amount: u64,
+    ) -> Result<UnbondingEntry, StakingError> {
+        if amount == 0 {
+            return Err(StakingError::ZeroAmount);
+        }
+        let key = (delegator.to_vec(), validator.to_vec());
+        let pool = self
+            .validators
+            .get_mut(validator)
+            .ok_or(StakingError::UnknownValidator)?;
+        let delegation = self
+            .delegations
+            .get_mut(&key)
+            .ok_or(StakingError::UnknownDelegation)?;
+
+        let owned = tokens_for_shares(delegation.shares, pool.stake, pool.delegator_shares);
+        if amount > owned {
+            return Err(StakingError::InsufficientStake);
+        }
+
+        // Burn all shares when withdrawing everything, so rounding can't leave dust
+        let shares = if amount == owned {
+            delegation.shares
+        } else {
+            shares_to_burn(amount, pool.stake, pool.delegator_shares).min(delegation.shares)
+        };
+        delegation.shares -= shares;
+        pool.delegator_shares -= shares;
+        pool.stake -= amount;
+        if delegation.shares == 0 {
+            self.delegations.remove(&key);
+        }
+
+        let entry = UnbondingEntry {
+            delegator: delegator.to_vec(),
+            validator: validator.to_vec(),
+            amount,
+            creation_epoch: self.current_epoch,
+            completion_epoch: self.current_epoch + self.unbonding_period,
+        };
+        self.unbonding.push(entry.clone());
+        Ok(entry)
+    }
+
+    pub fn set_commission(
+        &mut self,
+        validator: &[u8],
+        commission_bps: u16,
+    ) -> Result<(), StakingError> {
+        if commission_bps > MAX_COMMISSION_BPS {
+            return Err(StakingError::InvalidCommission);
+        }
+        let validator = self
+            .validators
+            .get_mut(validator)
+            .ok_or(StakingError::UnknownValidator)?;
+        validator.commission_bps = commission_bps;
+        Ok(())
+    }
+
+    /// Attributes a block/epoch reward to a validator's pool. The commission is
+    /// paid to the operator; the rest compounds into the pool, raising the value
+    /// of every delegator's shares. Returns the commission taken.
+    pub fn distribute_reward(
+        &mut self,
+        validator: &[u8],
+        amount: u64,
+    ) -> Result<u64, StakingError> {
+        let pool = self
+            .validators
+            .get_mut(validator)
+            .ok_or(StakingError::UnknownValidator)?;
+        if !pool.is_active() {
+            return Err(StakingError::ValidatorUnavailable);
+        }
+
+        let commission =
+            ((amount as u128 * pool.commission_bps as u128) / MAX_COMMISSION_BPS as u128) as u64;
+        pool.stake += amount - commission;
+        *self.withdrawable.entry(validator.to_vec()).or_default() += commission;
+        Ok(commission)
+    }
 
-        if !validator.is_slashed {
-            validator.stake += amount;
+    /// Queues an active validator to leave the set at a future epoch boundary.
+    pub fn request_exit(&mut self, public_key: &[u8]) -> bool {
+        match self.validators.get_mut(public_key) {
+            Some(validator) if validator.status == ValidatorStatus::Active => {
+                validator.status = ValidatorStatus::Exiting;
+                self.exit_queue.push_back(public_key.to_vec());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Runs the epoch boundary: bonds pending deposits (new validators are
+    /// bounded by the churn limit), processes exits, releases matured
+    /// unbondings and then snapshots the resulting validator set.
+    pub fn process_epoch(&mut self, epoch: u64) -> &ValidatorSet {
+        let mut activations = 0;
+        let mut still_pending = VecDeque::new();
+
+        while let Some(deposit) = self.pending_deposits.pop_front() {
+            let Some(validator) = self.validators.get_mut(&deposit.validator) else {
+                continue;
+            };
+            if validator.is_tombstoned || validator.status == ValidatorStatus::Exited {
+                // The pool is gone; return the funds instead of bonding them
+                *self.withdrawable.entry(deposit.delegator).or_default() += deposit.amount;
+                continue;
+            }
+
+            match validator.status {
+                // Top-ups for validators already in the set don't consume churn
+                ValidatorStatus::Active | ValidatorStatus::Exiting => {}
+                ValidatorStatus::Pending if activations < self.churn_limit => {
+                    validator.status = ValidatorStatus::Active;
+                    validator.activation_epoch = Some(epoch);
+                    activations += 1;
+                }
+                _ => {
+                    still_pending.push_back(deposit);
+                    continue;
+                }
+            }
+
+            let shares =
+                shares_for_tokens(deposit.amount, validator.stake, validator.delegator_shares);
+            validator.stake += deposit.amount;
+            validator.delegator_shares += shares;
+            self.delegations
+                .entry((deposit.delegator.clone(), deposit.validator.clone()))
+                .or_insert_with(|| Delegation {
+                    delegator: deposit.delegator,
+                    validator: deposit.validator,
+                    shares: 0,
+                })
+                .shares += shares;
+        }
+        self.pending_deposits = still_pending;
+
+        for _ in 0..self.churn_limit {
+            let Some(public_key) = self.exit_queue.pop_front() else {
+                break;
+            };
+            if let Some(validator) = self.validators.get_mut(&public_key) {
+                validator.status = ValidatorStatus::Exited;
+                validator.exit_epoch = Some(epoch);
+            }
+        }
+
+        let (matured, unbonding): (Vec<_>, Vec<_>) = std::mem::take(&mut self.unbonding)
+            .into_iter()
+            .partition(|entry| entry.completion_epoch <= epoch);
+        self.unbonding = unbonding;
+        for entry in matured {
+            *self.withdrawable.entry(entry.delegator).or_default() += entry.amount;
+        }
+
+        self.identities.extend(self.pending_identities.drain(..));
+
+        self.current_epoch = epoch;
+        self.validator_set = self.build_validator_set(epoch);
+        &self.validator_set
+    }
+
+    fn build_validator_set(&self, epoch: u64) -> ValidatorSet {
+        let entries = self
+            .validators
+            .values()
+            .filter(|v| v.is_active())
+            .map(|v| ValidatorSetEntry {
+                public_key: v.public_key.clone(),
+                stake: v.stake,
+                voting_power: self.get_voting_power(&v.public_key),
+            })
+            .collect();
+        ValidatorSet::new(epoch, entries)
+    }
+
+    /// The snapshot taken at the most recent epoch boundary.
+    pub fn validator_set(&self) -> &ValidatorSet {
+        &self.validator_set
+    }
+
+    pub fn current_epoch(&self) -> u64 {
+        self.current_epoch
+    }
+
+    /// Bonded stake and delegations as of the current epoch.
+    pub fn snapshot(&self) -> StakeSnapshot {
+        let mut delegations: Vec<Delegation> = self.delegations.values().cloned().collect();
+        delegations.sort_by(|a, b| (&a.validator, &a.delegator).cmp(&(&b.validator, &b.delegator)));
+        StakeSnapshot {
+            epoch: self.current_epoch,
+            validators: self.validators().into_iter().cloned().collect(),
+            delegations,
+            validator_set: self.validator_set.clone(),
         }
     }
 
     /// Calculates voting power using a square root function to dampen whale influence.
-    /// Returns floor(sqrt(stake)).
+    /// Returns floor(sqrt(stake)) for active validators and 0 otherwise. With
+    /// identity weighting, an identity's sqrt(total stake) is split across its
+    /// keys in proportion to their stake.
     pub fn get_voting_power(&self, public_key: &[u8]) -> u64 {
         if let Some(validator) = self.validators.get(public_key) {
-            if validator.is_slashed {
+            if !validator.is_active() {
                 return 0;
             }
-            // Anti-centralization curve: power = sqrt(stake)
-            (validator.stake as f64).sqrt() as u64
+            if !self.identity_weighting {
+                // Anti-centralization curve: power = sqrt(stake)
+                return (validator.stake as f64).sqrt() as u64;
+            }
+
+            let Some(did) = self.identities.get(public_key) else {
+                return 0;
+            };
+            let identity_stake: u64 = self
+                .identities
+                .iter()
+                .filter(|(_, other)| *other == did)
+                .filter_map(|(pk, _)| self.validators.get(pk))
+                .filter(|v| v.is_active())
+                .map(|v| v.stake)
+                .sum();
+            if identity_stake == 0 {
+                return 0;
+            }
+            let identity_power = (identity_stake as f64).sqrt() as u64;
+            ((identity_power as u128 * validator.stake as u128) / identity_stake as u128) as u64
         } else {
             0
         }
     }
 
-    pub fn slash(&mut self, public_key: &[u8]) {
+    pub fn get_validator(&self, public_key: &[u8]) -> Option<&Validator> {
+        self.validators.get(public_key)
+    }
+
+    /// All known validators, sorted by public key so iteration order is deterministic.
+    pub fn validators(&self) -> Vec<&Validator> {
+        let mut validators: Vec<&Validator> = self.validators.values().collect();
+        validators.sort_by(|a, b| a.public_key.cmp(&b.public_key));
+        validators
+    }
+
+    pub fn total_voting_power(&self) -> u64 {
+        self.validators
+            .keys()
+            .map(|pk| self.get_voting_power(pk))
+            .sum()
+    }
+
+    pub fn get_delegation(&self, delegator: &[u8], validator: &[u8]) -> Option<&Delegation> {
+        self.delegations
+            .get(&(delegator.to_vec(), validator.to_vec()))
+    }
+
+    /// Current token value of a delegation, including compounded rewards.
+    pub fn delegation_value(&self, delegator: &[u8], validator: &[u8]) -> u64 {
+        match (
+            self.get_delegation(delegator, validator),
+            self.validators.get(validator),
+        ) {
+            (Some(delegation), Some(pool)) => {
+                tokens_for_shares(delegation.shares, pool.stake, pool.delegator_shares)
+            }
+            _ => 0,
+        }
+    }
+
+    pub fn unbonding_entries(&self, delegator: &[u8]) -> Vec<&UnbondingEntry> {
+        self.unbonding
+            .iter()
+            .filter(|entry| entry.delegator == delegator)
+            .collect()
+    }
+
+    pub fn withdrawable(&self, account: &[u8]) -> u64 {
+        self.withdrawable.get(account).copied().unwrap_or(0)
+    }
+
+    /// Pays out matured unbondings and earned commission, returning the amount.
+    pub fn withdraw(&mut self, account: &[u8]) -> u64 {
+        self.withdrawable.remove(account).unwrap_or(0)
+    }
+
+    /// Stake that was bonded to `public_key` at `infraction_epoch` and can
+    /// still be slashed: the pool plus unbonding entries created since then.
+    pub fn slashable_stake(&self, public_key: &[u8], infraction_epoch: u64) -> u64 {
+        let pool = self.validators.get(public_key).map_or(0, |v| v.stake);
+        let unbonding: u64 = self
+            .unbonding
+            .iter()
+            .filter(|e| e.validator == public_key && e.creation_epoch >= infraction_epoch)
+            .map(|e| e.amount)
+            .sum();
+        pool + unbonding
+    }
+
+    /// Burns `penalty` tokens, spread proportionally over the validator's pool
+    /// (and therefore its delegators' shares) and the unbonding entries that were
+    /// still bonded at `infraction_epoch`. Returns the amount actually burned.
+    pub fn slash(&mut self, public_key: &[u8], penalty: u64, infraction_epoch: u64) -> u64 {
+        let slashable = self.slashable_stake(public_key, infraction_epoch);
+        if slashable == 0 || penalty == 0 {
+            return 0;
+        }
+        let penalty = penalty.min(slashable);
+
+        let mut burned = 0;
+        for entry in self
+            .unbonding
+            .iter_mut()
+            .filter(|e| e.validator == public_key && e.creation_epoch >= infraction_epoch)
+        {
+            let cut = ((entry.amount as u128 * penalty as u128) / slashable as u128) as u64;
+            entry.amount -= cut;
+            burned += cut;
+        }
+
+        if let Some(validator) = self.validators.get_mut(public_key) {
+            let cut = (penalty - burned).min(validator.stake);
+            validator.stake -= cut;
+            burned += cut;
+        }
+        burned
+    }
+
+    /// Removes the validator from the set (from the next epoch boundary) until
+    /// it unjails at or after `until_epoch`.
+    pub fn jail(&mut self, public_key: &[u8], until_epoch: u64) {
         if let Some(validator) = self.validators.get_mut(public_key) {
-            validator.is_slashed = true;
-            validator.stake = 0; // Full slash for now
+            let until = validator
+                .jailed_until
+                .map_or(until_epoch, |e| e.max(until_epoch));
+            validator.jailed_until = Some(until);
+        }
+    }
+
+    /// Permanently removes the validator. Reserved for double signing.
+    pub fn tombstone(&mut self, public_key: &[u8]) {
+        if let Some(validator) = self.validators.get_mut(public_key) {
+            validator.is_tombstoned = true;
+            validator.jailed_until = None;
+            if validator.status == ValidatorStatus::Exiting {
+                self.exit_queue.retain(|pk| pk != public_key);
+            }
+            validator.status = ValidatorStatus::Exited;
+            validator.exit_epoch.get_or_insert(self.current_epoch);
+        }
+    }
+
+    pub fn unjail(&mut self, public_key: &[u8], current_epoch: u64) -> Result<(), StakingError> {
+        let validator = self
+            .validators
+            .get_mut(public_key)
+            .ok_or(StakingError::UnknownValidator)?;
+        if validator.is_tombstoned {
+            return Err(StakingError::ValidatorUnavailable);
+        }
+        match validator.jailed_until {
+            None => Err(StakingError::NotJailed),
+            Some(until) if current_epoch < until => Err(StakingError::StillJailed(until)),
+            Some(_) => {
+                validator.jailed_until = None;
+                Ok(())
+            }
+        }
+    }
+
+    /// Nonce the validator's next staking transaction has to carry.
+    pub fn staking_nonce(&self, validator: &[u8]) -> u64 {
+        self.staking_nonces.get(validator).copied().unwrap_or(0)
+    }
+
+    /// Applies a signed staking transaction submitted by a validator. It must
+    /// name `chain_id`, the chain being executed.
+    pub fn apply_staking_transaction(
+        &mut self,
+        tx: &StakingTransaction,
+        chain_id: &str,
+        current_epoch: u64,
+    ) -> Result<(), StakingError> {
+        if tx.chain_id != chain_id {
+            return Err(StakingError::WrongChain(tx.chain_id.clone()));
+        }
+        if !self.validators.contains_key(&tx.validator_public_key) {
+            return Err(StakingError::UnknownValidator);
+        }
+        if !tx.verify_signature().unwrap_or(false) {
+            return Err(StakingError::InvalidSignature);
         }
+        let expected_nonce = self.staking_nonce(&tx.validator_public_key);
+        if tx.nonce != expected_nonce {
+            return Err(StakingError::InvalidNonce);
+        }
+
+        match tx.action {
+            StakingAction::Unjail => self.unjail(&tx.validator_public_key, current_epoch)?,
+        }
+        self.staking_nonces
+            .insert(tx.validator_public_key.clone(), expected_nonce + 1);
+        Ok(())
     }
 }
 
+/// Bytes a validator signs to bind its key to `did` using `credential`.
+pub fn identity_binding_bytes(
+    validator: &[u8],
+    did: &str,
+    credential: &VerifiableCredential,
+) -> Vec<u8> {
+    let mut bytes = IDENTITY_BINDING_DOMAIN.to_vec();
+    for field in [validator, did.as_bytes(), credential.id.as_bytes()] {
+        bytes.extend_from_slice(&(field.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(field);
+    }
+    bytes
+}
+
 impl Default for StakeManager {
     fn default() -> Self {
         Self::new()
@@ -61,9 +677,12 @@ mod tests {
         let pk = vec![1, 2, 3];
 
         manager.add_stake(pk.clone(), 100);
+        assert_eq!(manager.get_voting_power(&pk), 0); // Not active until the epoch boundary
+        manager.process_epoch(1);
         assert_eq!(manager.get_voting_power(&pk), 10); // sqrt(100) = 10
 
         manager.add_stake(pk.clone(), 300); // Total 400
+        manager.process_epoch(2);
         assert_eq!(manager.get_voting_power(&pk), 20); // sqrt(400) = 20
     }
 
@@ -73,10 +692,376 @@ mod tests {
         let pk = vec![1, 2, 3];
 
         manager.add_stake(pk.clone(), 100);
+        manager.process_epoch(1);
         assert_eq!(manager.get_voting_power(&pk), 10);
 
-        manager.slash(&pk);
+        // Double signing: full penalty and permanent removal
+        assert_eq!(manager.slash(&pk, 100, 1), 100);
+        manager.tombstone(&pk);
         assert_eq!(manager.get_voting_power(&pk), 0);
-        assert_eq!(manager.validators.get(&pk).unwrap().is_slashed, true);
+        assert!(manager.validators.get(&pk).unwrap().is_tombstoned);
+        assert_eq!(
+            manager.unjail(&pk, 100),
+            Err(StakingError::ValidatorUnavailable)
+        );
+    }
+
+    #[test]
+    fn test_activation_is_churn_limited() {
+        let mut manager = StakeManager::new().with_churn_limit(2);
+        for i in 0..5u8 {
+            manager.add_stake(vec![i], 100);
+        }
+
+        assert_eq!(manager.process_epoch(1).len(), 2);
+        assert_eq!(manager.process_epoch(2).len(), 4);
+        assert_eq!(manager.process_epoch(3).len(), 5);
+        assert_eq!(
+            manager.get_validator(&[0]).unwrap().activation_epoch,
+            Some(1)
+        );
+        assert_eq!(
+            manager.get_validator(&[4]).unwrap().activation_epoch,
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn test_exit_processed_at_epoch_boundary() {
+        let mut manager = StakeManager::new().with_churn_limit(1);
+        for i in 0..3u8 {
+            manager.add_stake(vec![i], 100);
+        }
+        manager.process_epoch(1);
+        manager.process_epoch(2);
+        manager.process_epoch(3);
+
+        assert!(manager.request_exit(&[0]));
+        assert!(manager.request_exit(&[1]));
+        assert!(!manager.request_exit(&[1])); // Already queued
+
+        // Exiting validators keep validating until their exit is processed
+        assert_eq!(manager.get_voting_power(&[0]), 10);
+
+        assert_eq!(manager.process_epoch(4).len(), 2);
+        assert_eq!(manager.get_voting_power(&[0]), 0);
+        assert_eq!(manager.process_epoch(5).len(), 1);
+        assert_eq!(
+            manager.get_validator(&[1]).unwrap().status,
+            ValidatorStatus::Exited
+        );
+    }
+
+    #[test]
+    fn test_validator_set_snapshot_is_hash_committed() {
+        let mut manager = StakeManager::new();
+        manager.add_stake(vec![1], 100);
+        manager.add_stake(vec![2], 400);
+
+        let set = manager.process_epoch(7).clone();
+        assert_eq!(set.epoch(), 7);
+        assert_eq!(set.total_voting_power(), 30);
+        assert!(set.verify_hash());
+
+        // Changes after the boundary don't leak into the snapshot
+        manager.add_stake(vec![3], 900);
+        assert_eq!(manager.validator_set(), &set);
+
+        let next = manager.process_epoch(8);
+        assert_ne!(next.hash(), set.hash());
+    }
+
+    #[test]
+    fn test_delegation_shares_and_commission() {
+        let mut manager = StakeManager::new();
+        let validator = vec![1];
+        let alice = vec![10];
+
+        manager.add_stake(validator.clone(), 1000);
+        manager.process_epoch(1);
+        manager.set_commission(&validator, 1000).unwrap(); // 10%
+
+        manager.delegate(alice.clone(), &validator, 1000).unwrap();
+        assert_eq!(manager.delegation_value(&alice, &validator), 0); // Pending
+        manager.process_epoch(2);
+        assert_eq!(manager.delegation_value(&alice, &validator), 1000);
+        assert_eq!(manager.get_validator(&validator).unwrap().stake, 2000);
+
+        // 200 reward: 20 commission to operator, 180 split pro rata by shares
+        let commission = manager.distribute_reward(&validator, 200).unwrap();
+        assert_eq!(commission, 20);
+        assert_eq!(manager.withdrawable(&validator), 20);
+        assert_eq!(manager.delegation_value(&alice, &validator), 1090);
+        assert_eq!(manager.delegation_value(&validator, &validator), 1090);
+
+        // A later delegator buys in at the new share price and doesn't dilute earlier rewards
+        let bob = vec![11];
+        manager.delegate(bob.clone(), &validator, 545).unwrap();
+        manager.process_epoch(3);
+        assert_eq!(manager.delegation_value(&bob, &validator), 545);
+        assert_eq!(manager.delegation_value(&alice, &validator), 1090);
+    }
+
+    #[test]
+    fn test_undelegating_does_not_take_from_the_pool() {
+        let mut manager = StakeManager::new();
+        let validator = vec![1];
+        let alice = vec![10];
+        manager.add_stake(validator.clone(), 1000);
+        manager.process_epoch(1);
+        manager.set_commission(&validator, 1000).unwrap();
+        manager.delegate(alice.clone(), &validator, 1000).unwrap();
+        manager.process_epoch(2);
+        manager.distribute_reward(&validator, 200).unwrap();
+
+        // 2180 tokens over 2000 shares: 2 tokens are worth 1.83 shares
+        for _ in 0..10 {
+            manager.undelegate(&alice, &validator, 2).unwrap();
+            assert_eq!(manager.delegation_value(&validator, &validator), 1090);
+        }
+        assert!(manager.delegation_value(&alice, &validator) <= 1070);
+    }
+
+    #[test]
+    fn test_unbonding_period() {
+        let mut manager = StakeManager::new().with_unbonding_period(3);
+        let validator = vec![1];
+        let alice = vec![10];
+
+        manager.add_stake(validator.clone(), 1000);
+        manager.delegate(alice.clone(), &validator, 500).unwrap();
+        manager.process_epoch(1);
+
+        let entry = manager.undelegate(&alice, &validator, 200).unwrap();
+        assert_eq!(entry.completion_epoch, 4);
+        assert_eq!(manager.delegation_value(&alice, &validator), 300);
+        assert_eq!(manager.get_validator(&validator).unwrap().stake, 1300);
+        assert_eq!(
+            manager.undelegate(&alice, &validator, 301),
+            Err(StakingError::InsufficientStake)
+        );
+
+        manager.process_epoch(3);
+        assert_eq!(manager.withdrawable(&alice), 0);
+        assert_eq!(manager.unbonding_entries(&alice).len(), 1);
+
+        manager.process_epoch(4);
+        assert!(manager.unbonding_entries(&alice).is_empty());
+        assert_eq!(manager.withdraw(&alice), 200);
+        assert_eq!(manager.withdraw(&alice), 0);
+    }
+
+    #[test]
+    fn test_delegate_to_unknown_validator() {
+        let mut manager = StakeManager::new();
+        assert_eq!(
+            manager.delegate(vec![10], &[1], 100),
+            Err(StakingError::UnknownValidator)
+        );
+        manager.add_stake(vec![1], 100);
+        assert_eq!(
+            manager.set_commission(&[1], 10_001),
+            Err(StakingError::InvalidCommission)
+        );
+    }
+
+    #[test]
+    fn test_slash_is_proportional_across_delegators_and_unbonding() {
+        let mut manager = StakeManager::new().with_unbonding_period(10);
+        let validator = vec![1];
+        let alice = vec![10];
+
+        manager.add_stake(validator.clone(), 600);
+        manager.delegate(alice.clone(), &validator, 600).unwrap();
+        manager.process_epoch(1);
+
+        // Alice starts unbonding after the infraction epoch; still slashable
+        manager.process_epoch(2);
+        manager.undelegate(&alice, &validator, 400).unwrap();
+        assert_eq!(manager.slashable_stake(&validator, 2), 1200);
+
+        // 10% penalty: 40 from the unbonding entry, 80 from the pool
+        assert_eq!(manager.slash(&validator, 120, 2), 120);
+        assert_eq!(manager.unbonding_entries(&alice)[0].amount, 360);
+        assert_eq!(manager.get_validator(&validator).unwrap().stake, 720);
+        assert_eq!(manager.delegation_value(&alice, &validator), 180);
+        assert_eq!(manager.delegation_value(&validator, &validator), 540);
+
+        // Unbonding that started before the infraction epoch is out of reach
+        assert_eq!(manager.slashable_stake(&validator, 3), 720);
+    }
+
+    #[test]
+    fn test_jail_and_unjail_with_transaction() {
+        let keypair = crypto::KeyPair::generate();
+        let validator = keypair.public_key.clone();
+        let mut manager = StakeManager::new();
+        manager.add_stake(validator.clone(), 100);
+        manager.add_stake(vec![2], 100);
+        manager.process_epoch(1);
+
+        manager.jail(&validator, 4);
+        assert_eq!(manager.get_voting_power(&validator), 0);
+        assert_eq!(manager.process_epoch(2).len(), 1);
+
+        let mut tx = StakingTransaction {
+            chain_id: "devnet".to_string(),
+            action: StakingAction::Unjail,
+            validator_public_key: validator.clone(),
+            nonce: 0,
+            signature: vec![],
+        };
+        tx.signature = keypair.sign(&tx.signing_bytes().unwrap()).unwrap();
+
+        assert_eq!(
+            manager.apply_staking_transaction(&tx, "devnet", 3),
+            Err(StakingError::StillJailed(4))
+        );
+        // It only counts on its own chain, and the chain id is signed
+        assert_eq!(
+            manager.apply_staking_transaction(&tx, "mainnet", 4),
+            Err(StakingError::WrongChain("devnet".to_string()))
+        );
+        let mut moved = tx.clone();
+        moved.chain_id = "mainnet".to_string();
+        assert_eq!(
+            manager.apply_staking_transaction(&moved, "mainnet", 4),
+            Err(StakingError::InvalidSignature)
+        );
+        manager.apply_staking_transaction(&tx, "devnet", 4).unwrap();
+        assert_eq!(manager.process_epoch(4).len(), 2);
+
+        // Replaying the same transaction is rejected
+        assert_eq!(
+            manager.apply_staking_transaction(&tx, "devnet", 4),
+            Err(StakingError::InvalidNonce)
+        );
+    }
+
+    #[test]
+    fn test_identity_weighting_defeats_key_splitting() {
+        let issuer_key = KeyPair::generate();
+        let issuer = DIDDocument::new(issuer_key.public_key.clone());
+        let mut manager = StakeManager::new().with_identity_weighting([issuer.id.clone()]);
+        let whale_key = KeyPair::generate();
+        let whale = DIDDocument::new(whale_key.public_key.clone());
+        let credential = VerifiableCredential::issue(
+            "vc:whale".to_string(),
+            issuer.id.clone(),
+            whale.id.clone(),
+            "{}".to_string(),
+            &issuer_key,
+        )
+        .unwrap();
+        let sign = |key: &KeyPair,
+                    validator: &KeyPair,
+                    did: &DIDDocument,
+                    credential: &VerifiableCredential| {
+            key.sign(&identity_binding_bytes(
+                &validator.public_key,
+                &did.id,
+                credential,
+            ))
+            .unwrap()
+        };
+        // Validator and identity controller both sign
+        let bind = |manager: &mut StakeManager,
+                    validator: &KeyPair,
+                    did: &DIDDocument,
+                    controller: &KeyPair,
+                    credential: &VerifiableCredential| {
+            manager.bind_identity(
+                &validator.public_key,
+                did,
+                credential,
+                &sign(validator, validator, did, credential),
+                &sign(controller, validator, did, credential),
+            )
+        };
+
+        // 10,000 tokens split over 4 keys of the same identity
+        let keys: Vec<KeyPair> = (0..4).map(|_| KeyPair::generate()).collect();
+        for key in &keys {
+            manager.add_stake(key.public_key.clone(), 2_500);
+            bind(&mut manager, key, &whale, &whale_key, &credential).unwrap();
+        }
+        // Unbound validators get nothing in this mode
+        let loner = KeyPair::generate();
+        manager.add_stake(loner.public_key.clone(), 10_000);
+        manager.process_epoch(1);
+
+        for key in &keys {
+            assert_eq!(manager.get_voting_power(&key.public_key), 25);
+        }
+        assert_eq!(manager.get_voting_power(&loner.public_key), 0);
+        assert_eq!(manager.total_voting_power(), 100); // sqrt(10,000)
+
+        // A key is bound once
+        assert_eq!(
+            bind(&mut manager, &keys[0], &whale, &whale_key, &credential),
+            Err(StakingError::AlreadyBound)
+        );
+
+        // Someone else can't bind the validator's key for it
+        assert_eq!(
+            manager.bind_identity(
+                &loner.public_key,
+                &whale,
+                &credential,
+                &sign(&keys[0], &keys[0], &whale, &credential),
+                &sign(&whale_key, &loner, &whale, &credential),
+            ),
+            Err(StakingError::InvalidSignature)
+        );
+
+        // Nor can a foreign key reuse the whale's public credential, even
+        // with a document that names its own key as the whale's
+        assert_eq!(
+            bind(&mut manager, &loner, &whale, &loner, &credential),
+            Err(StakingError::UnapprovedBinding)
+        );
+        let impostor = DIDDocument {
+            public_key: loner.public_key.clone(),
+            ..whale.clone()
+        };
+        assert_eq!(
+            bind(&mut manager, &loner, &impostor, &loner, &credential),
+            Err(StakingError::UnapprovedBinding)
+        );
+
+        let self_issued = VerifiableCredential::issue(
+            "vc:self".to_string(),
+            whale.id.clone(),
+            whale.id.clone(),
+            "{}".to_string(),
+            &whale_key,
+        )
+        .unwrap();
+        assert_eq!(
+            bind(&mut manager, &loner, &whale, &whale_key, &self_issued),
+            Err(StakingError::UntrustedIssuer)
+        );
+        assert_eq!(
+            bind(&mut manager, &loner, &issuer, &issuer_key, &credential),
+            Err(StakingError::InvalidCredential)
+        );
+        let mut forged = credential.clone();
+        forged.proof = vec![1];
+        assert_eq!(
+            bind(&mut manager, &loner, &whale, &whale_key, &forged),
+            Err(StakingError::InvalidCredential)
+        );
+
+        // A valid binding waits for the next epoch boundary
+        bind(&mut manager, &loner, &whale, &whale_key, &credential).unwrap();
+        assert_eq!(manager.get_voting_power(&loner.public_key), 0);
+        assert_eq!(manager.identity_of(&loner.public_key), None);
+        manager.process_epoch(2);
+        assert_eq!(
+            manager.identity_of(&loner.public_key),
+            Some(whale.id.as_str())
+        );
+        // Half of the identity's sqrt(20,000)
+        assert_eq!(manager.get_voting_power(&loner.public_key), 70);
     }
 }
@@ -1,10 +1,31 @@
+use crypto::Blake3;
 use serde::{Deserialize, Serialize};
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ValidatorStatus {
+    /// Deposit received, waiting for an epoch boundary with free churn.
+    Pending,
+    Active,
+    /// Exit requested, still validating until the exit is processed.
+    Exiting,
+    Exited,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Validator {
     pub public_key: Vec<u8>,
+    /// Total bonded tokens: self-stake plus delegations plus compounded rewards.
     pub stake: u64,
-    pub is_slashed: bool,
+    /// Shares issued against `stake` to the operator and its delegators.
+    pub delegator_shares: u64,
+    pub commission_bps: u16,
+    /// Permanently removed for double signing; can never rejoin.
+    pub is_tombstoned: bool,
+    /// Temporarily removed from the set; may unjail from this epoch on.
+    pub jailed_until: Option<u64>,
+    pub status: ValidatorStatus,
+    pub activation_epoch: Option<u64>,
+    pub exit_epoch: Option<u64>,
 }
 
 impl Validator {
@@ -12,7 +33,104 @@ impl Validator {
         Self {
             public_key,
             stake,
-            is_slashed: false,
+            delegator_shares: stake,
+            commission_bps: 0,
+            is_tombstoned: false,
+            jailed_until: None,
+            status: ValidatorStatus::Pending,
+            activation_epoch: None,
+            exit_epoch: None,
         }
     }
+
+    /// Whether the validator participates in consensus (proposing and voting).
+    pub fn is_active(&self) -> bool {
+        !self.is_tombstoned
+            && self.jailed_until.is_none()
+            && matches!(
+                self.status,
+                ValidatorStatus::Active | ValidatorStatus::Exiting
+            )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ValidatorSetEntry {
+    pub public_key: Vec<u8>,
+    pub stake: u64,
+    pub voting_power: u64,
+}
+
+/// The validators that run consensus for one epoch. Built once at the epoch
+/// boundary and committed to by `hash`, so consensus and light clients can
+/// refer to the exact same set.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ValidatorSet {
+    epoch: u64,
+    entries: Vec<ValidatorSetEntry>,
+    total_voting_power: u64,
+    hash: String,
+}
+
+impl ValidatorSet {
+    pub fn new(epoch: u64, mut entries: Vec<ValidatorSetEntry>) -> Self {
+        entries.retain(|e| e.voting_power > 0);
+        entries.sort_by(|a, b| a.public_key.cmp(&b.public_key));
+        let total_voting_power = entries.iter().map(|e| e.voting_power).sum();
+        let hash = Self::compute_hash(epoch, &entries);
+        Self {
+            epoch,
+            entries,
+            total_voting_power,
+            hash,
+        }
+    }
+
+    fn compute_hash(epoch: u64, entries: &[ValidatorSetEntry]) -> String {
+        let serialized = bincode::serialize(&(epoch, entries)).unwrap();
+        Blake3::hash(&serialized)
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Entries sorted by public key.
+    pub fn entries(&self) -> &[ValidatorSetEntry] {
+        &self.entries
+    }
+
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+
+    pub fn total_voting_power(&self) -> u64 {
+        self.total_voting_power
+    }
+
+    pub fn voting_power(&self, public_key: &[u8]) -> u64 {
+        self.entries
+            .binary_search_by(|e| e.public_key.as_slice().cmp(public_key))
+            .map(|idx| self.entries[idx].voting_power)
+            .unwrap_or(0)
+    }
+
+    pub fn contains(&self, public_key: &[u8]) -> bool {
+        self.voting_power(public_key) > 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Checks that the committed hash matches the contents (e.g. after
+    /// deserializing a set received from a peer).
+    pub fn verify_hash(&self) -> bool {
+        Self::compute_hash(self.epoch, &self.entries) == self.hash
+            && self.entries.iter().map(|e| e.voting_power).sum::<u64>() == self.total_voting_power
+    }
 }
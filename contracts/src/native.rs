@@ -1,5 +1,6 @@
 use crate::registry::SystemContract;
 use storage::state_compliance::AccountState;
+use vm::context::BlockContext;
 
 pub trait NativeExecution {
     fn execute(
@@ -10,11 +11,26 @@ pub trait NativeExecution {
     ) -> Result<Vec<u8>, String>;
 }
 
-pub struct NativeExecutor;
+pub struct NativeExecutor {
+    context: BlockContext,
+}
 
 impl NativeExecutor {
     pub fn new() -> Self {
-        Self
+        Self {
+            context: BlockContext::default(),
+        }
+    }
+
+    pub fn with_context(mut self, context: BlockContext) -> Self {
+        self.context = context;
+        self
+    }
+}
+
+impl Default for NativeExecutor {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -31,7 +47,7 @@ impl NativeExecution for NativeExecutor {
                 // Input: [Action(1 byte), Amount(8 bytes)]
                 // If Action == 0x01 (Stake), deduct balance (simulated)
                 // This is a simplified example.
-                if input.len() >= 1 && input[0] == 0x01 {
+                if !input.is_empty() && input[0] == 0x01 {
                     // Logic to lock funds/update nonce would go here
                     // verifying state.balance sufficient etc.
                     state.nonce += 1; // dummy side effect
@@ -40,6 +56,7 @@ impl NativeExecution for NativeExecutor {
                     Err("Invalid staking input".to_string())
                 }
             }
+            SystemContract::Randomness => Ok(self.context.randao_mix.to_vec()),
             _ => Ok(b"NoOp".to_vec()),
         }
     }
@@ -62,4 +79,15 @@ mod tests {
         assert_eq!(res.unwrap(), b"Staked".to_vec());
         assert_eq!(state.nonce, 1);
     }
+
+    #[test]
+    fn test_randomness_returns_mix() {
+        let mut state = AccountState::new(0, 0, 0);
+        let executor = NativeExecutor::new().with_context(BlockContext {
+            randao_mix: [9u8; 32],
+            ..Default::default()
+        });
+        let res = executor.execute(SystemContract::Randomness, &[], &mut state);
+        assert_eq!(res.unwrap(), vec![9u8; 32]);
+    }
 }
@@ -5,6 +5,8 @@ pub enum SystemContract {
     Staking,
     Governance,
     IdentityRegistry,
+    /// Returns the current block's RANDAO mix.
+    Randomness,
 }
 
 impl SystemContract {
@@ -13,11 +15,13 @@ impl SystemContract {
         // 0x01 -> Staking
         // 0x02 -> Governance
         // 0x03 -> Identity
+        // 0x04 -> Randomness
         if addr.len() == 1 {
             match addr[0] {
                 0x01 => Some(Self::Staking),
                 0x02 => Some(Self::Governance),
                 0x03 => Some(Self::IdentityRegistry),
+                0x04 => Some(Self::Randomness),
                 _ => None,
             }
         } else {
@@ -0,0 +1,203 @@
+use crate::CryptoError;
+use bech32::primitives::decode::CheckedHrpstring;
+use bech32::{Bech32m, Hrp};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Number of bytes of the public key hash kept in an address.
+pub const ADDRESS_LENGTH: usize = 20;
+
+/// The network an address belongs to. Each network uses its own bech32m HRP,
+/// so a testnet address can never be pasted into a mainnet transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Devnet,
+}
+
+impl Network {
+    pub fn hrp(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "vajra",
+            Network::Testnet => "tvajra",
+            Network::Devnet => "dvajra",
+        }
+    }
+
+    pub fn from_hrp(hrp: &str) -> Option<Self> {
+        match hrp {
+            "vajra" => Some(Network::Mainnet),
+            "tvajra" => Some(Network::Testnet),
+            "dvajra" => Some(Network::Devnet),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Network::Mainnet => write!(f, "mainnet"),
+            Network::Testnet => write!(f, "testnet"),
+            Network::Devnet => write!(f, "devnet"),
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = CryptoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mainnet" => Ok(Network::Mainnet),
+            "testnet" => Ok(Network::Testnet),
+            "devnet" => Ok(Network::Devnet),
+            other => Err(CryptoError::InvalidAddress(format!(
+                "unknown network '{}'",
+                other
+            ))),
+        }
+    }
+}
+
+/// An account address: the first 20 bytes of the Blake3 hash of a public key,
+/// encoded as bech32m with a per-network HRP (e.g. `vajra1...`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Address {
+    network: Network,
+    hash: [u8; ADDRESS_LENGTH],
+}
+
+impl Address {
+    pub fn new(network: Network, hash: [u8; ADDRESS_LENGTH]) -> Self {
+        Self { network, hash }
+    }
+
+    pub fn from_public_key(public_key: &[u8], network: Network) -> Self {
+        let digest = blake3::hash(public_key);
+        let mut hash = [0u8; ADDRESS_LENGTH];
+        hash.copy_from_slice(&digest.as_bytes()[..ADDRESS_LENGTH]);
+        Self { network, hash }
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    pub fn as_bytes(&self) -> &[u8; ADDRESS_LENGTH] {
+        &self.hash
+    }
+
+    /// Parses a bech32m address, validating the checksum, HRP and payload length.
+    pub fn parse(s: &str) -> Result<Self, CryptoError> {
+        let checked = CheckedHrpstring::new::<Bech32m>(s)
+            .map_err(|e| CryptoError::InvalidAddress(e.to_string()))?;
+
+        let hrp = checked.hrp();
+        let network = Network::from_hrp(hrp.as_str()).ok_or_else(|| {
+            CryptoError::InvalidAddress(format!("unknown prefix '{}'", hrp.as_str()))
+        })?;
+
+        let bytes: Vec<u8> = checked.byte_iter().collect();
+        let hash: [u8; ADDRESS_LENGTH] = bytes.as_slice().try_into().map_err(|_| {
+            CryptoError::InvalidAddress(format!(
+                "expected {} bytes, got {}",
+                ADDRESS_LENGTH,
+                bytes.len()
+            ))
+        })?;
+
+        Ok(Self { network, hash })
+    }
+
+    /// Like `parse`, but also rejects addresses that belong to another network.
+    pub fn parse_for_network(s: &str, network: Network) -> Result<Self, CryptoError> {
+        let address = Self::parse(s)?;
+        if address.network != network {
+            return Err(CryptoError::InvalidAddress(format!(
+                "address is for {}, expected {}",
+                address.network, network
+            )));
+        }
+        Ok(address)
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hrp = Hrp::parse_unchecked(self.network.hrp());
+        bech32::encode_lower_to_fmt::<Bech32m, _>(f, hrp, &self.hash).map_err(|_| fmt::Error)
+    }
+}
+
+impl FromStr for Address {
+    type Err = CryptoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl TryFrom<String> for Address {
+    type Error = CryptoError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::parse(&s)
+    }
+}
+
+impl From<Address> for String {
+    fn from(address: Address) -> Self {
+        address.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyPair;
+
+    #[test]
+    fn test_address_roundtrip() {
+        let keypair = KeyPair::generate();
+        let address = Address::from_public_key(&keypair.public_key, Network::Mainnet);
+
+        let encoded = address.to_string();
+        assert!(encoded.starts_with("vajra1"));
+        assert_eq!(Address::parse(&encoded).unwrap(), address);
+    }
+
+    #[test]
+    fn test_hrp_differs_per_network() {
+        let pk = [7u8; 32];
+        let mainnet = Address::from_public_key(&pk, Network::Mainnet);
+        let testnet = Address::from_public_key(&pk, Network::Testnet);
+
+        assert!(testnet.to_string().starts_with("tvajra1"));
+        assert_eq!(mainnet.as_bytes(), testnet.as_bytes());
+        assert!(Address::parse_for_network(&testnet.to_string(), Network::Mainnet).is_err());
+    }
+
+    #[test]
+    fn test_typo_rejected() {
+        let address = Address::from_public_key(&[1u8; 32], Network::Mainnet).to_string();
+
+        // Flip one data character to another valid bech32 character
+        let mut chars: Vec<char> = address.chars().collect();
+        let idx = chars.len() - 10;
+        chars[idx] = if chars[idx] == 'q' { 'p' } else { 'q' };
+        let typo: String = chars.into_iter().collect();
+
+        assert!(Address::parse(&typo).is_err());
+    }
+
+    #[test]
+    fn test_legacy_bech32_checksum_rejected() {
+        let hrp = Hrp::parse("vajra").unwrap();
+        let legacy = bech32::encode::<bech32::Bech32>(hrp, &[0u8; ADDRESS_LENGTH]).unwrap();
+        assert!(Address::parse(&legacy).is_err());
+    }
+}
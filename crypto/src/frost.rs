@@ -0,0 +1,251 @@
+//! FROST threshold Schnorr signatures over Ed25519 (RFC 9591).
+//!
+//! A committee of `n` participants runs a dealerless distributed key generation
+//! (DKG) to obtain one group public key and a private share each. Any `t` of them
+//! can then produce a signature in two rounds (commit, sign) which a coordinator
+//! aggregates. The aggregate is a plain 64-byte Ed25519 signature over the group
+//! key, so `KeyPair::verify` accepts it without knowing it came from a committee.
+
+use crate::CryptoError;
+use frost_ed25519 as frost;
+use rand::rngs::OsRng;
+use std::collections::BTreeMap;
+
+/// Participants are numbered `1..=n`; zero is not a valid identifier.
+pub type ParticipantId = u16;
+
+pub type DkgRound1Secret = frost::keys::dkg::round1::SecretPackage;
+pub type DkgRound1Package = frost::keys::dkg::round1::Package;
+pub type DkgRound2Secret = frost::keys::dkg::round2::SecretPackage;
+/// Round 2 packages are addressed to a single participant and carry secret
+/// material; they must be sent over an encrypted, authenticated channel.
+pub type DkgRound2Package = frost::keys::dkg::round2::Package;
+
+pub type SigningNonces = frost::round1::SigningNonces;
+pub type SigningCommitments = frost::round1::SigningCommitments;
+pub type SigningPackage = frost::SigningPackage;
+pub type SignatureShare = frost::round2::SignatureShare;
+
+/// A participant's long-lived output of the DKG.
+#[derive(Debug, Clone)]
+pub struct KeyShare {
+    pub id: ParticipantId,
+    key_package: frost::keys::KeyPackage,
+    group: GroupKey,
+}
+
+impl KeyShare {
+    pub fn group_key(&self) -> &GroupKey {
+        &self.group
+    }
+}
+
+/// The committee's public data: the group verifying key plus every
+/// participant's verifying share (needed to aggregate and to blame bad shares).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupKey {
+    package: frost::keys::PublicKeyPackage,
+}
+
+impl GroupKey {
+    /// The 32-byte Ed25519 public key that aggregated signatures verify against.
+    pub fn public_key(&self) -> Result<Vec<u8>, CryptoError> {
+        self.package
+            .verifying_key()
+            .serialize()
+            .map_err(|_| CryptoError::SerializationError)
+    }
+}
+
+fn identifier(id: ParticipantId) -> Result<frost::Identifier, CryptoError> {
+    frost::Identifier::try_from(id).map_err(|e| CryptoError::Frost(e.to_string()))
+}
+
+fn by_identifier<T: Clone>(
+    map: &BTreeMap<ParticipantId, T>,
+) -> Result<BTreeMap<frost::Identifier, T>, CryptoError> {
+    map.iter()
+        .map(|(id, value)| Ok((identifier(*id)?, value.clone())))
+        .collect()
+}
+
+/// DKG round 1: generate our secret polynomial and the package to broadcast to
+/// every other participant.
+pub fn dkg_part1(
+    id: ParticipantId,
+    max_signers: u16,
+    min_signers: u16,
+) -> Result<(DkgRound1Secret, DkgRound1Package), CryptoError> {
+    frost::keys::dkg::part1(identifier(id)?, max_signers, min_signers, OsRng)
+        .map_err(|e| CryptoError::Frost(e.to_string()))
+}
+
+/// DKG round 2: given everyone else's round 1 packages, produce one private
+/// package per recipient.
+pub fn dkg_part2(
+    secret: DkgRound1Secret,
+    round1_packages: &BTreeMap<ParticipantId, DkgRound1Package>,
+) -> Result<(DkgRound2Secret, BTreeMap<ParticipantId, DkgRound2Package>), CryptoError> {
+    let (secret, packages) = frost::keys::dkg::part2(secret, &by_identifier(round1_packages)?)
+        .map_err(|e| CryptoError::Frost(e.to_string()))?;
+
+    // Map the library identifiers back to the participant numbers we were given.
+    let mut outgoing = BTreeMap::new();
+    for id in round1_packages.keys() {
+        if let Some(package) = packages.get(&identifier(*id)?) {
+            outgoing.insert(*id, package.clone());
+        }
+    }
+    Ok((secret, outgoing))
+}
+
+/// DKG round 3: verify the packages addressed to us and derive our key share.
+pub fn dkg_part3(
+    id: ParticipantId,
+    secret: &DkgRound2Secret,
+    round1_packages: &BTreeMap<ParticipantId, DkgRound1Package>,
+    round2_packages: &BTreeMap<ParticipantId, DkgRound2Package>,
+) -> Result<KeyShare, CryptoError> {
+    let (key_package, public_package) = frost::keys::dkg::part3(
+        secret,
+        &by_identifier(round1_packages)?,
+        &by_identifier(round2_packages)?,
+    )
+    .map_err(|e| CryptoError::Frost(e.to_string()))?;
+
+    if *key_package.identifier() != identifier(id)? {
+        return Err(CryptoError::Frost(
+            "key package does not belong to this participant".to_string(),
+        ));
+    }
+
+    Ok(KeyShare {
+        id,
+        key_package,
+        group: GroupKey {
+            package: public_package,
+        },
+    })
+}
+
+/// Signing round 1: generate single-use nonces and the commitments to send to
+/// the coordinator. Nonces must never be reused across signing sessions.
+pub fn commit(share: &KeyShare) -> (SigningNonces, SigningCommitments) {
+    frost::round1::commit(share.key_package.signing_share(), &mut OsRng)
+}
+
+/// Built by the coordinator once it has commitments from at least `t` signers.
+pub fn signing_package(
+    commitments: &BTreeMap<ParticipantId, SigningCommitments>,
+    message: &[u8],
+) -> Result<SigningPackage, CryptoError> {
+    Ok(SigningPackage::new(by_identifier(commitments)?, message))
+}
+
+/// Signing round 2: produce our share of the signature.
+pub fn sign(
+    package: &SigningPackage,
+    nonces: &SigningNonces,
+    share: &KeyShare,
+) -> Result<SignatureShare, CryptoError> {
+    frost::round2::sign(package, nonces, &share.key_package)
+        .map_err(|e| CryptoError::Frost(e.to_string()))
+}
+
+/// Combines the signature shares into a standard 64-byte Ed25519 signature.
+pub fn aggregate(
+    package: &SigningPackage,
+    shares: &BTreeMap<ParticipantId, SignatureShare>,
+    group: &GroupKey,
+) -> Result<Vec<u8>, CryptoError> {
+    let signature = frost::aggregate(package, &by_identifier(shares)?, &group.package)
+        .map_err(|e| CryptoError::Frost(e.to_string()))?;
+    signature
+        .serialize()
+        .map_err(|_| CryptoError::SerializationError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyPair;
+
+    fn run_dkg(n: u16, t: u16) -> Vec<KeyShare> {
+        let mut round1_secrets = BTreeMap::new();
+        let mut round1_packages = BTreeMap::new();
+        for id in 1..=n {
+            let (secret, package) = dkg_part1(id, n, t).unwrap();
+            round1_secrets.insert(id, secret);
+            round1_packages.insert(id, package);
+        }
+
+        // Each participant sees everyone's round 1 package except its own
+        let others = |me: u16, all: &BTreeMap<u16, DkgRound1Package>| {
+            all.iter()
+                .filter(|(id, _)| **id != me)
+                .map(|(id, p)| (*id, p.clone()))
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        let mut round2_secrets = BTreeMap::new();
+        let mut inboxes: BTreeMap<u16, BTreeMap<u16, DkgRound2Package>> = BTreeMap::new();
+        for (id, secret) in round1_secrets {
+            let (secret, outgoing) = dkg_part2(secret, &others(id, &round1_packages)).unwrap();
+            round2_secrets.insert(id, secret);
+            for (recipient, package) in outgoing {
+                inboxes.entry(recipient).or_default().insert(id, package);
+            }
+        }
+
+        (1..=n)
+            .map(|id| {
+                dkg_part3(
+                    id,
+                    &round2_secrets[&id],
+                    &others(id, &round1_packages),
+                    &inboxes[&id],
+                )
+                .unwrap()
+            })
+            .collect()
+    }
+
+    fn threshold_sign(signers: &[&KeyShare], message: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        let mut nonces = BTreeMap::new();
+        let mut commitments = BTreeMap::new();
+        for share in signers {
+            let (n, c) = commit(share);
+            nonces.insert(share.id, n);
+            commitments.insert(share.id, c);
+        }
+
+        let package = signing_package(&commitments, message)?;
+        let mut shares = BTreeMap::new();
+        for share in signers {
+            shares.insert(share.id, sign(&package, &nonces[&share.id], share)?);
+        }
+        aggregate(&package, &shares, signers[0].group_key())
+    }
+
+    #[test]
+    fn test_dkg_and_threshold_signature_verifies_as_ed25519() {
+        let shares = run_dkg(3, 2);
+        let group_pk = shares[0].group_key().public_key().unwrap();
+        assert!(shares
+            .iter()
+            .all(|s| s.group_key().public_key().unwrap() == group_pk));
+
+        let message = b"bridge withdrawal #42";
+        let signature = threshold_sign(&[&shares[0], &shares[2]], message).unwrap();
+
+        assert_eq!(signature.len(), 64);
+        assert!(KeyPair::verify(&group_pk, message, &signature).unwrap());
+        assert!(KeyPair::verify(&group_pk, b"other message", &signature).is_err());
+    }
+
+    #[test]
+    fn test_below_threshold_fails() {
+        let shares = run_dkg(3, 2);
+        assert!(threshold_sign(&[&shares[1]], b"msg").is_err());
+    }
+}
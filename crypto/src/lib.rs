@@ -1,3 +1,8 @@
+pub mod address;
+pub mod frost;
+pub mod secp256k1;
+
+use address::{Address, Network};
 use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
 use rand::rngs::OsRng;
 use serde::{Deserialize, Serialize};
@@ -11,6 +16,10 @@ pub enum CryptoError {
     KeyGenerationFailed,
     #[error("Serialization error")]
     SerializationError,
+    #[error("Invalid address: {0}")]
+    InvalidAddress(String),
+    #[error("FROST error: {0}")]
+    Frost(String),
 }
 
 pub trait Hashable {
@@ -45,6 +54,16 @@ impl KeyPair {
         }
     }
 
+    /// Derives a key pair from a fixed 32-byte seed. Only for tests and
+    /// simulations that need reproducible keys.
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        let signing_key = SigningKey::from_bytes(&seed);
+        Self {
+            public_key: signing_key.verifying_key().to_bytes().to_vec(),
+            private_key: signing_key.to_bytes().to_vec(),
+        }
+    }
+
     pub fn sign(&self, message: &[u8]) -> Result<Vec<u8>, CryptoError> {
         let signing_key = SigningKey::from_bytes(self.private_key.as_slice().try_into().unwrap());
         let signature = signing_key.sign(message);
@@ -75,3 +94,43 @@ impl KeyPair {
             .map_err(|_| CryptoError::InvalidSignature)
     }
 }
+
+/// Selects the signature algorithm used to authorize a transaction.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum SignatureScheme {
+    #[default]
+    Ed25519,
+    /// Ethereum-compatible ECDSA; messages are Keccak-256 hashed before signing.
+    Secp256k1,
+}
+
+impl SignatureScheme {
+    pub fn verify(
+        &self,
+        public_key: &[u8],
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<bool, CryptoError> {
+        match self {
+            SignatureScheme::Ed25519 => KeyPair::verify(public_key, message, signature),
+            SignatureScheme::Secp256k1 => {
+                secp256k1::Secp256k1KeyPair::verify(public_key, message, signature)
+            }
+        }
+    }
+
+    /// Derives the account address owned by `public_key`. secp256k1 keys map to
+    /// their Ethereum address so the same key controls the same 20 bytes on both chains.
+    pub fn derive_address(
+        &self,
+        public_key: &[u8],
+        network: Network,
+    ) -> Result<Address, CryptoError> {
+        match self {
+            SignatureScheme::Ed25519 => Ok(Address::from_public_key(public_key, network)),
+            SignatureScheme::Secp256k1 => {
+                Ok(Address::new(network, secp256k1::eth_address(public_key)?))
+            }
+        }
+    }
+}
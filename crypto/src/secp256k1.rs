@@ -0,0 +1,186 @@
+//! secp256k1 ECDSA, as used by Ethereum and other EVM chains.
+//!
+//! Signatures are 65 bytes: `r || s || v` where `v` is the recovery id (0/1, or
+//! 27/28 as produced by most Ethereum wallets). Messages are hashed with
+//! Keccak-256 before signing, matching Ethereum's conventions.
+
+use crate::CryptoError;
+use k256::ecdsa::{RecoveryId, Signature, SigningKey, VerifyingKey};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+
+pub const SIGNATURE_LENGTH: usize = 65;
+pub const ETH_ADDRESS_LENGTH: usize = 20;
+
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    Keccak256::digest(data).into()
+}
+
+/// The digest signed by `personal_sign` / `eth_sign` in MetaMask-style wallets.
+pub fn eth_message_hash(message: &[u8]) -> [u8; 32] {
+    let prefix = format!("\x19Ethereum Signed Message:\n{}", message.len());
+    let mut hasher = Keccak256::new();
+    hasher.update(prefix.as_bytes());
+    hasher.update(message);
+    hasher.finalize().into()
+}
+
+/// Ethereum address: the last 20 bytes of Keccak-256 over the uncompressed
+/// public key (without the 0x04 prefix). Accepts compressed or uncompressed keys.
+pub fn eth_address(public_key: &[u8]) -> Result<[u8; ETH_ADDRESS_LENGTH], CryptoError> {
+    let key =
+        VerifyingKey::from_sec1_bytes(public_key).map_err(|_| CryptoError::SerializationError)?;
+    let uncompressed = key.to_encoded_point(false);
+    let hash = keccak256(&uncompressed.as_bytes()[1..]);
+
+    let mut address = [0u8; ETH_ADDRESS_LENGTH];
+    address.copy_from_slice(&hash[32 - ETH_ADDRESS_LENGTH..]);
+    Ok(address)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Secp256k1KeyPair {
+    /// SEC1 compressed public key (33 bytes)
+    pub public_key: Vec<u8>,
+    pub private_key: Vec<u8>,
+}
+
+impl Secp256k1KeyPair {
+    pub fn generate() -> Self {
+        let signing_key = SigningKey::random(&mut OsRng);
+        Self::from_signing_key(&signing_key)
+    }
+
+    pub fn from_private_key(private_key: &[u8]) -> Result<Self, CryptoError> {
+        let signing_key =
+            SigningKey::from_slice(private_key).map_err(|_| CryptoError::SerializationError)?;
+        Ok(Self::from_signing_key(&signing_key))
+    }
+
+    fn from_signing_key(signing_key: &SigningKey) -> Self {
+        Self {
+            public_key: signing_key
+                .verifying_key()
+                .to_encoded_point(true)
+                .as_bytes()
+                .to_vec(),
+            private_key: signing_key.to_bytes().to_vec(),
+        }
+    }
+
+    /// Signs `keccak256(message)`, returning a 65-byte recoverable signature.
+    pub fn sign(&self, message: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        self.sign_prehash(&keccak256(message))
+    }
+
+    pub fn sign_prehash(&self, prehash: &[u8; 32]) -> Result<Vec<u8>, CryptoError> {
+        let signing_key = SigningKey::from_slice(&self.private_key)
+            .map_err(|_| CryptoError::SerializationError)?;
+        let (signature, recovery_id) = signing_key
+            .sign_prehash_recoverable(prehash)
+            .map_err(|_| CryptoError::InvalidSignature)?;
+
+        let mut bytes = signature.to_bytes().to_vec();
+        bytes.push(recovery_id.to_byte());
+        Ok(bytes)
+    }
+
+    pub fn verify(
+        public_key: &[u8],
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<bool, CryptoError> {
+        Self::verify_prehash(public_key, &keccak256(message), signature)
+    }
+
+    pub fn verify_prehash(
+        public_key: &[u8],
+        prehash: &[u8; 32],
+        signature: &[u8],
+    ) -> Result<bool, CryptoError> {
+        let expected = VerifyingKey::from_sec1_bytes(public_key)
+            .map_err(|_| CryptoError::SerializationError)?;
+        let recovered = recover_key(prehash, signature)?;
+
+        if recovered == expected {
+            Ok(true)
+        } else {
+            Err(CryptoError::InvalidSignature)
+        }
+    }
+
+    /// Recovers the compressed public key that produced `signature` over
+    /// `keccak256(message)`.
+    pub fn recover(message: &[u8], signature: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        Self::recover_prehash(&keccak256(message), signature)
+    }
+
+    pub fn recover_prehash(prehash: &[u8; 32], signature: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        let key = recover_key(prehash, signature)?;
+        Ok(key.to_encoded_point(true).as_bytes().to_vec())
+    }
+}
+
+fn recover_key(prehash: &[u8; 32], signature: &[u8]) -> Result<VerifyingKey, CryptoError> {
+    if signature.len() != SIGNATURE_LENGTH {
+        return Err(CryptoError::SerializationError);
+    }
+
+    let sig =
+        Signature::from_slice(&signature[..64]).map_err(|_| CryptoError::SerializationError)?;
+    // Reject malleable high-s signatures, as Ethereum does since EIP-2
+    if sig.normalize_s().is_some() {
+        return Err(CryptoError::InvalidSignature);
+    }
+
+    let v = match signature[64] {
+        27 | 28 => signature[64] - 27,
+        v => v,
+    };
+    let recovery_id = RecoveryId::from_byte(v).ok_or(CryptoError::InvalidSignature)?;
+
+    VerifyingKey::recover_from_prehash(prehash, &sig, recovery_id)
+        .map_err(|_| CryptoError::InvalidSignature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_verify_recover() {
+        let keypair = Secp256k1KeyPair::generate();
+        let message = b"hello vajra";
+
+        let signature = keypair.sign(message).unwrap();
+        assert_eq!(signature.len(), SIGNATURE_LENGTH);
+        assert!(Secp256k1KeyPair::verify(&keypair.public_key, message, &signature).unwrap());
+        assert!(Secp256k1KeyPair::verify(&keypair.public_key, b"tampered", &signature).is_err());
+
+        let recovered = Secp256k1KeyPair::recover(message, &signature).unwrap();
+        assert_eq!(recovered, keypair.public_key);
+    }
+
+    #[test]
+    fn test_eth_v_values_accepted() {
+        let keypair = Secp256k1KeyPair::generate();
+        let mut signature = keypair.sign(b"msg").unwrap();
+        signature[64] += 27;
+        assert!(Secp256k1KeyPair::verify(&keypair.public_key, b"msg", &signature).unwrap());
+    }
+
+    #[test]
+    fn test_eth_address_known_vector() {
+        // Private key 0x...01 maps to the well-known address 0x7e5f...bdf
+        let mut private_key = [0u8; 32];
+        private_key[31] = 1;
+        let keypair = Secp256k1KeyPair::from_private_key(&private_key).unwrap();
+
+        let address = eth_address(&keypair.public_key).unwrap();
+        assert_eq!(
+            hex::encode(address),
+            "7e5f4552091a69125d5dfcb7b8c2659029395bdf"
+        );
+    }
+}
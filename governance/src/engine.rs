@@ -1,4 +1,5 @@
 use crate::proposal::{Proposal, ProposalState, Vote, VoteOption};
+use consensus::poa::{AuthorityChange, PoaEngine};
 use std::collections::HashMap;
 
 pub struct GovernanceEngine {
@@ -94,6 +95,27 @@ impl GovernanceEngine {
 
         Ok(proposal.state)
     }
+
+    /// Executes a passed proposal whose call data is an `AuthorityChange`
+    /// against the proof-of-authority engine.
+    pub fn execute_authority_change(
+        &mut self,
+        proposal_id: u64,
+        poa: &mut PoaEngine,
+    ) -> Result<(), String> {
+        let proposal = self
+            .proposals
+            .get_mut(&proposal_id)
+            .ok_or("Proposal not found")?;
+        if proposal.state != ProposalState::Passed {
+            return Err("Proposal has not passed".to_string());
+        }
+
+        let change = AuthorityChange::decode(&proposal.call_data).map_err(|e| e.to_string())?;
+        poa.apply_change(&change).map_err(|e| e.to_string())?;
+        proposal.state = ProposalState::Executed;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -120,4 +142,21 @@ mod tests {
         let state = engine.tally_votes(pid).unwrap();
         assert_eq!(state, ProposalState::Passed);
     }
+
+    #[test]
+    fn test_authority_change_proposal() {
+        let mut poa = PoaEngine::new(vec![vec![1]]);
+        let mut engine = GovernanceEngine::new(10);
+        let call_data = AuthorityChange::Add(vec![2]).encode();
+        let pid = engine.create_proposal("Add authority".to_string(), call_data, 1, 10);
+
+        assert!(engine.execute_authority_change(pid, &mut poa).is_err());
+        engine.cast_vote(pid, vec![1], VoteOption::Yes, 10).unwrap();
+        engine.tally_votes(pid).unwrap();
+        engine.execute_authority_change(pid, &mut poa).unwrap();
+
+        assert_eq!(poa.authorities(), &[vec![1], vec![2]]);
+        // Can't be executed twice
+        assert!(engine.execute_authority_change(pid, &mut poa).is_err());
+    }
 }
@@ -1,12 +1,17 @@
+use crypto::{CryptoError, KeyPair};
 use serde::{Deserialize, Serialize};
 
+/// Prefix of the signed bytes, so a credential signature can't be passed off
+/// as a signature over anything else.
+const SIGNING_DOMAIN: &[u8] = b"vajra/credential/1";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VerifiableCredential {
     pub id: String,
     pub issuer: String,  // DID of issuer
     pub subject: String, // DID of subject
     pub claims: String,  // JSON string of claims
-    pub proof: Vec<u8>,  // Signature
+    pub proof: Vec<u8>,  // Issuer's Ed25519 signature over `signing_bytes`
 }
 
 impl VerifiableCredential {
@@ -26,10 +31,33 @@ impl VerifiableCredential {
         }
     }
 
+    /// Builds a credential and signs it with the issuer's key.
+    pub fn issue(
+        id: String,
+        issuer: String,
+        subject: String,
+        claims: String,
+        issuer_key: &KeyPair,
+    ) -> Result<Self, CryptoError> {
+        let mut credential = Self::new(id, issuer, subject, claims, Vec::new());
+        credential.proof = issuer_key.sign(&credential.signing_bytes())?;
+        Ok(credential)
+    }
+
+    /// Bytes covered by the proof: every field but the proof, each length
+    /// prefixed so fields can't bleed into each other.
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let mut bytes = SIGNING_DOMAIN.to_vec();
+        for field in [&self.id, &self.issuer, &self.subject, &self.claims] {
+            bytes.extend_from_slice(&(field.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(field.as_bytes());
+        }
+        bytes
+    }
+
+    /// Checks the proof is the issuer's signature over this credential.
     pub fn verify(&self, issuer_public_key: &[u8]) -> bool {
-        // Mock verification: In real system, verify signature over (id+issuer+subject+claims) using pubkey.
-        // For prototype, just check if proof is not empty.
-        !self.proof.is_empty() && !issuer_public_key.is_empty()
+        KeyPair::verify(issuer_public_key, &self.signing_bytes(), &self.proof).unwrap_or(false)
     }
 }
 
@@ -39,14 +67,26 @@ mod tests {
 
     #[test]
     fn test_credential_verification() {
-        let vc = VerifiableCredential::new(
+        let issuer = KeyPair::generate();
+        let vc = VerifiableCredential::issue(
             "vc:1".to_string(),
             "did:vajra:issuer".to_string(),
             "did:vajra:subject".to_string(),
             "{\"role\": \"admin\"}".to_string(),
-            vec![1, 2, 3],
-        );
-        assert!(vc.verify(&[0x01]));
+            &issuer,
+        )
+        .unwrap();
+        assert!(vc.verify(&issuer.public_key));
+        assert!(!vc.verify(&KeyPair::generate().public_key));
         assert!(!vc.verify(&[]));
+
+        // Any non-empty proof used to pass
+        let mut forged = vc.clone();
+        forged.proof = vec![1, 2, 3];
+        assert!(!forged.verify(&issuer.public_key));
+
+        let mut altered = vc;
+        altered.subject = "did:vajra:someone-else".to_string();
+        assert!(!altered.verify(&issuer.public_key));
     }
 }
@@ -1,27 +1,55 @@
+use crate::evidence::Evidence;
 use crate::merkle::compute_merkle_root;
+use crate::randao::{self, RandaoMix};
 use crate::transaction::Transaction;
 use crypto::Blake3;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct BlockHeader {
+    /// Chain the block is for. It is signed, so the block can't be replayed
+    /// on another chain.
+    pub chain_id: String,
     pub parent_hash: String,
+    /// Unix time in milliseconds.
     pub timestamp: u64,
     pub slot: u64,
     pub state_root: String,
     pub transactions_root: String,
+    pub evidence_root: String,
+    /// Proposer's signature over its epoch, see `randao::reveal`.
+    pub randao_reveal: Vec<u8>,
+    /// Parent's mix with this block's reveal mixed in.
+    pub randao_mix: RandaoMix,
     pub validator_public_key: Vec<u8>,
     pub signature: Vec<u8>,
 }
 
+impl BlockHeader {
+    /// Bytes covered by the proposer's signature: the header with `signature` cleared.
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let mut unsigned = self.clone();
+        unsigned.signature = Vec::new();
+        bincode::serialize(&unsigned).unwrap()
+    }
+
+    pub fn hash(&self) -> String {
+        let serialized = bincode::serialize(self).unwrap();
+        Blake3::hash(&serialized)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Block {
     pub header: BlockHeader,
     pub transactions: Vec<Transaction>,
+    /// Misbehavior proofs the proposer included; each one slashes its offender.
+    pub evidence: Vec<Evidence>,
 }
 
 impl Block {
     pub fn new(
+        chain_id: impl Into<String>,
         parent_hash: String,
         timestamp: u64,
         slot: u64,
@@ -29,23 +57,18 @@ impl Block {
         transactions: Vec<Transaction>,
         validator_public_key: Vec<u8>,
     ) -> Self {
-        // Compute transactions root
-        let tx_hashes: Vec<String> = transactions
-            .iter()
-            .map(|tx| {
-                let serialized = bincode::serialize(tx).unwrap(); // Should handle error safely in prod
-                Blake3::hash(&serialized)
-            })
-            .collect();
-
-        let transactions_root = compute_merkle_root(&tx_hashes);
+        let transactions_root = Self::transactions_root(&transactions);
 
         let header = BlockHeader {
+            chain_id: chain_id.into(),
             parent_hash,
             timestamp,
             slot,
             state_root,
             transactions_root,
+            evidence_root: Self::evidence_root(&[]),
+            randao_reveal: Vec::new(),
+            randao_mix: [0u8; 32],
             validator_public_key,
             signature: Vec::new(), // To be signed
         };
@@ -53,11 +76,45 @@ impl Block {
         Block {
             header,
             transactions,
+            evidence: Vec::new(),
         }
     }
 
+    /// Attaches evidence and commits to it in the header. Call before signing.
+    pub fn with_evidence(mut self, evidence: Vec<Evidence>) -> Self {
+        self.header.evidence_root = Self::evidence_root(&evidence);
+        self.evidence = evidence;
+        self
+    }
+
+    /// Sets the proposer's reveal and folds it into the parent's mix. Call before signing.
+    pub fn with_randao(mut self, reveal: Vec<u8>, parent_mix: &RandaoMix) -> Self {
+        self.header.randao_mix = randao::mix_in(parent_mix, &reveal);
+        self.header.randao_reveal = reveal;
+        self
+    }
+
+    /// Whether `header.evidence_root` commits to exactly the attached evidence.
+    pub fn verify_evidence_root(&self) -> bool {
+        self.header.evidence_root == Self::evidence_root(&self.evidence)
+    }
+
+    /// Whether `header.transactions_root` commits to exactly the attached transactions.
+    pub fn verify_transactions_root(&self) -> bool {
+        self.header.transactions_root == Self::transactions_root(&self.transactions)
+    }
+
+    pub fn transactions_root(transactions: &[Transaction]) -> String {
+        let hashes: Vec<String> = transactions.iter().map(Transaction::hash).collect();
+        compute_merkle_root(&hashes)
+    }
+
+    pub fn evidence_root(evidence: &[Evidence]) -> String {
+        let hashes: Vec<String> = evidence.iter().map(Evidence::hash).collect();
+        compute_merkle_root(&hashes)
+    }
+
     pub fn hash(&self) -> String {
-        let serialized = bincode::serialize(&self.header).unwrap();
-        Blake3::hash(&serialized)
+        self.header.hash()
     }
 }
@@ -0,0 +1,221 @@
+use crate::block::BlockHeader;
+use crate::vote::Vote;
+use crypto::{Blake3, KeyPair};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum EvidenceError {
+    #[error("Messages were signed by different validators")]
+    DifferentSigners,
+    #[error("Messages are not for the same slot and round")]
+    DifferentHeights,
+    #[error("Messages are identical")]
+    NotConflicting,
+    #[error("Invalid signature in evidence")]
+    InvalidSignature,
+}
+
+/// Two different block headers signed by the same proposer for one slot.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DoubleSignEvidence {
+    pub first: BlockHeader,
+    pub second: BlockHeader,
+}
+
+impl DoubleSignEvidence {
+    /// Orders the pair by hash so the same offense always yields the same evidence.
+    pub fn new(a: BlockHeader, b: BlockHeader) -> Self {
+        let (first, second) = if a.hash() <= b.hash() { (a, b) } else { (b, a) };
+        Self { first, second }
+    }
+
+    pub fn verify(&self) -> Result<(), EvidenceError> {
+        if self.first.validator_public_key != self.second.validator_public_key {
+            return Err(EvidenceError::DifferentSigners);
+        }
+        if self.first.slot != self.second.slot {
+            return Err(EvidenceError::DifferentHeights);
+        }
+        // The same content signed twice is not a conflict
+        if self.first.signing_bytes() == self.second.signing_bytes() {
+            return Err(EvidenceError::NotConflicting);
+        }
+        for header in [&self.first, &self.second] {
+            KeyPair::verify(
+                &header.validator_public_key,
+                &header.signing_bytes(),
+                &header.signature,
+            )
+            .map_err(|_| EvidenceError::InvalidSignature)?;
+        }
+        Ok(())
+    }
+}
+
+/// Two votes of the same kind from one validator for different blocks in the
+/// same slot and round.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DoubleVoteEvidence {
+    pub first: Vote,
+    pub second: Vote,
+}
+
+impl DoubleVoteEvidence {
+    pub fn new(a: Vote, b: Vote) -> Self {
+        let (first, second) = if a.block_hash <= b.block_hash {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        Self { first, second }
+    }
+
+    pub fn verify(&self) -> Result<(), EvidenceError> {
+        if self.first.validator_public_key != self.second.validator_public_key {
+            return Err(EvidenceError::DifferentSigners);
+        }
+        let position = |v: &Vote| (v.chain_id.clone(), v.kind, v.slot, v.round);
+        if position(&self.first) != position(&self.second) {
+            return Err(EvidenceError::DifferentHeights);
+        }
+        if self.first.block_hash == self.second.block_hash {
+            return Err(EvidenceError::NotConflicting);
+        }
+        self.first
+            .verify()
+            .and(self.second.verify())
+            .map_err(|_| EvidenceError::InvalidSignature)
+    }
+}
+
+/// Self-contained proof of validator misbehavior, gossiped and included in blocks.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Evidence {
+    DoubleSign(Box<DoubleSignEvidence>),
+    DoubleVote(Box<DoubleVoteEvidence>),
+}
+
+impl Evidence {
+    pub fn verify(&self) -> Result<(), EvidenceError> {
+        match self {
+            Evidence::DoubleSign(e) => e.verify(),
+            Evidence::DoubleVote(e) => e.verify(),
+        }
+    }
+
+    pub fn offender(&self) -> &[u8] {
+        match self {
+            Evidence::DoubleSign(e) => &e.first.validator_public_key,
+            Evidence::DoubleVote(e) => &e.first.validator_public_key,
+        }
+    }
+
+    /// Slot the offense was committed in.
+    pub fn slot(&self) -> u64 {
+        match self {
+            Evidence::DoubleSign(e) => e.first.slot,
+            Evidence::DoubleVote(e) => e.first.slot,
+        }
+    }
+
+    pub fn hash(&self) -> String {
+        Blake3::hash(&bincode::serialize(self).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vote::VoteKind;
+
+    fn header(slot: u64, state_root: &str, keypair: &KeyPair) -> BlockHeader {
+        let mut header = BlockHeader {
+            chain_id: "devnet".to_string(),
+            parent_hash: "0".to_string(),
+            timestamp: 0,
+            slot,
+            state_root: state_root.to_string(),
+            transactions_root: "".to_string(),
+            evidence_root: "".to_string(),
+            randao_reveal: Vec::new(),
+            randao_mix: [0u8; 32],
+            validator_public_key: keypair.public_key.clone(),
+            signature: vec![],
+        };
+        header.signature = keypair.sign(&header.signing_bytes()).unwrap();
+        header
+    }
+
+    #[test]
+    fn test_double_sign_evidence() {
+        let keypair = KeyPair::generate();
+        let a = header(5, "a", &keypair);
+        let b = header(5, "b", &keypair);
+
+        let evidence = DoubleSignEvidence::new(a.clone(), b.clone());
+        assert!(evidence.verify().is_ok());
+        assert_eq!(evidence, DoubleSignEvidence::new(b, a.clone()));
+
+        assert_eq!(
+            DoubleSignEvidence::new(a.clone(), a.clone()).verify(),
+            Err(EvidenceError::NotConflicting)
+        );
+        assert_eq!(
+            DoubleSignEvidence::new(a.clone(), header(6, "b", &keypair)).verify(),
+            Err(EvidenceError::DifferentHeights)
+        );
+        let mut resigned = a.clone();
+        resigned.signature = vec![0; 64];
+        assert_eq!(
+            DoubleSignEvidence::new(a.clone(), resigned).verify(),
+            Err(EvidenceError::NotConflicting)
+        );
+        let mut forged = header(5, "c", &keypair);
+        forged.state_root = "tampered".to_string();
+        assert_eq!(
+            DoubleSignEvidence::new(a, forged).verify(),
+            Err(EvidenceError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn test_double_vote_evidence() {
+        let keypair = KeyPair::generate();
+        let a = Vote::new(
+            "devnet",
+            VoteKind::Precommit,
+            3,
+            0,
+            "a".to_string(),
+            &keypair,
+        )
+        .unwrap();
+        let b = Vote::new(
+            "devnet",
+            VoteKind::Precommit,
+            3,
+            0,
+            "b".to_string(),
+            &keypair,
+        )
+        .unwrap();
+        let next_round = Vote::new(
+            "devnet",
+            VoteKind::Precommit,
+            3,
+            1,
+            "b".to_string(),
+            &keypair,
+        )
+        .unwrap();
+
+        let evidence = Evidence::DoubleVote(Box::new(DoubleVoteEvidence::new(a.clone(), b)));
+        assert!(evidence.verify().is_ok());
+        assert_eq!(evidence.offender(), keypair.public_key.as_slice());
+        assert_eq!(
+            DoubleVoteEvidence::new(a, next_round).verify(),
+            Err(EvidenceError::DifferentHeights)
+        );
+    }
+}
@@ -1,3 +1,6 @@
 pub mod block;
+pub mod evidence;
 pub mod merkle;
+pub mod randao;
 pub mod transaction;
+pub mod vote;
@@ -41,7 +41,7 @@ mod tests {
     #[test]
     fn test_merkle_root_single() {
         let hash = Blake3::hash(b"test");
-        let root = compute_merkle_root(&[hash.clone()]);
+        let root = compute_merkle_root(std::slice::from_ref(&hash));
         assert_eq!(root, hash);
     }
 
@@ -0,0 +1,52 @@
+use crypto::{CryptoError, KeyPair};
+
+/// Accumulated randomness, carried in every block header.
+pub type RandaoMix = [u8; 32];
+
+const REVEAL_DOMAIN: &[u8] = b"vajra/randao";
+
+/// What a proposer signs to produce its reveal for `epoch`. The signature is
+/// deterministic, so a proposer can't grind the mix by re-signing.
+pub fn reveal_message(epoch: u64) -> Vec<u8> {
+    let mut message = REVEAL_DOMAIN.to_vec();
+    message.extend_from_slice(&epoch.to_be_bytes());
+    message
+}
+
+pub fn reveal(keypair: &KeyPair, epoch: u64) -> Result<Vec<u8>, CryptoError> {
+    keypair.sign(&reveal_message(epoch))
+}
+
+pub fn verify_reveal(public_key: &[u8], epoch: u64, reveal: &[u8]) -> bool {
+    KeyPair::verify(public_key, &reveal_message(epoch), reveal).unwrap_or(false)
+}
+
+/// XORs the hash of `reveal` into `mix`.
+pub fn mix_in(mix: &RandaoMix, reveal: &[u8]) -> RandaoMix {
+    let digest = blake3::hash(reveal);
+    let mut mixed = *mix;
+    for (byte, other) in mixed.iter_mut().zip(digest.as_bytes()) {
+        *byte ^= other;
+    }
+    mixed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reveal_is_bound_to_key_and_epoch() {
+        let keypair = KeyPair::generate();
+        let reveal = reveal(&keypair, 3).unwrap();
+
+        assert!(verify_reveal(&keypair.public_key, 3, &reveal));
+        assert!(!verify_reveal(&keypair.public_key, 4, &reveal));
+        assert!(!verify_reveal(&KeyPair::generate().public_key, 3, &reveal));
+
+        let mix = mix_in(&[0u8; 32], &reveal);
+        assert_ne!(mix, [0u8; 32]);
+        // Mixing the same reveal twice cancels out
+        assert_eq!(mix_in(&mix, &reveal), [0u8; 32]);
+    }
+}
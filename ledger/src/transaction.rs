@@ -1,3 +1,5 @@
+use crypto::address::Address;
+use crypto::{Blake3, CryptoError, KeyPair, SignatureScheme};
 use serde::{Deserialize, Serialize};
 
 /// Represents an input in the UTXO model.
@@ -13,20 +15,42 @@ pub struct UtxoInput {
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct UtxoOutput {
     pub amount: u64,
-    pub recipient_address: String,
+    pub recipient_address: Address,
 }
 
 /// Represents an account-based transaction (like Ethereum).
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct AccountTransaction {
+    /// Chain the transaction is meant for. It is signed, so the transaction
+    /// can't be replayed on another chain.
+    pub chain_id: String,
     pub nonce: u64,
-    pub to: Option<String>, // None for contract creation
+    pub to: Option<Address>, // None for contract creation
     pub amount: u64,
     pub data: Vec<u8>,
     pub gas_limit: u64,
     pub gas_price: u64,
     pub signature: Vec<u8>,
     pub sender_public_key: Vec<u8>,
+    pub signature_scheme: SignatureScheme,
+}
+
+impl AccountTransaction {
+    /// Bytes covered by the signature: the transaction with `signature` cleared.
+    pub fn signing_bytes(&self) -> Result<Vec<u8>, CryptoError> {
+        let mut unsigned = self.clone();
+        unsigned.signature = Vec::new();
+        bincode::serialize(&unsigned).map_err(|_| CryptoError::SerializationError)
+    }
+
+    /// Verifies `signature` with the verifier selected by `signature_scheme`.
+    pub fn verify_signature(&self) -> Result<bool, CryptoError> {
+        self.signature_scheme.verify(
+            &self.sender_public_key,
+            &self.signing_bytes()?,
+            &self.signature,
+        )
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -36,10 +60,46 @@ pub struct UtxoTransaction {
     pub timestamp: u64,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum StakingAction {
+    /// Return a jailed validator to the active set once its jail period is over.
+    Unjail,
+}
+
+/// A staking operation signed with the validator's consensus (Ed25519) key.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StakingTransaction {
+    /// Chain the transaction is meant for. It is signed, so the transaction
+    /// can't be replayed on another chain.
+    pub chain_id: String,
+    pub action: StakingAction,
+    pub validator_public_key: Vec<u8>,
+    pub nonce: u64,
+    pub signature: Vec<u8>,
+}
+
+impl StakingTransaction {
+    /// Bytes covered by the signature: the transaction with `signature` cleared.
+    pub fn signing_bytes(&self) -> Result<Vec<u8>, CryptoError> {
+        let mut unsigned = self.clone();
+        unsigned.signature = Vec::new();
+        bincode::serialize(&unsigned).map_err(|_| CryptoError::SerializationError)
+    }
+
+    pub fn verify_signature(&self) -> Result<bool, CryptoError> {
+        KeyPair::verify(
+            &self.validator_public_key,
+            &self.signing_bytes()?,
+            &self.signature,
+        )
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum TransactionData {
     Utxo(UtxoTransaction),
     Account(AccountTransaction),
+    Staking(StakingTransaction),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -48,5 +108,45 @@ pub struct Transaction {
 }
 
 impl Transaction {
-    // Helper functionality for serializing and hashing would go here
+    pub fn hash(&self) -> String {
+        Blake3::hash(&bincode::serialize(self).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::secp256k1::Secp256k1KeyPair;
+
+    fn unsigned_tx(sender_public_key: Vec<u8>, scheme: SignatureScheme) -> AccountTransaction {
+        AccountTransaction {
+            chain_id: "devnet".to_string(),
+            nonce: 0,
+            to: None,
+            amount: 10,
+            data: vec![],
+            gas_limit: 21_000,
+            gas_price: 1,
+            signature: vec![],
+            sender_public_key,
+            signature_scheme: scheme,
+        }
+    }
+
+    #[test]
+    fn test_scheme_selects_verifier() {
+        let ed = KeyPair::generate();
+        let mut tx = unsigned_tx(ed.public_key.clone(), SignatureScheme::Ed25519);
+        tx.signature = ed.sign(&tx.signing_bytes().unwrap()).unwrap();
+        assert!(tx.verify_signature().unwrap());
+
+        let secp = Secp256k1KeyPair::generate();
+        let mut tx = unsigned_tx(secp.public_key.clone(), SignatureScheme::Secp256k1);
+        tx.signature = secp.sign(&tx.signing_bytes().unwrap()).unwrap();
+        assert!(tx.verify_signature().unwrap());
+
+        // The same signature checked with the wrong scheme must fail
+        tx.signature_scheme = SignatureScheme::Ed25519;
+        assert!(tx.verify_signature().is_err());
+    }
 }
@@ -0,0 +1,68 @@
+use crypto::{CryptoError, KeyPair};
+use serde::{Deserialize, Serialize};
+
+/// Prefix of the signed bytes, so a vote signature can't be passed off as a
+/// signature over a block or transaction.
+const SIGNING_DOMAIN: &[u8] = b"vajra/vote/1";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum VoteKind {
+    Prevote,
+    Precommit,
+}
+
+/// A validator's signed finality vote for a block.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Vote {
+    /// Chain the vote is for. It is signed, so votes can't be replayed on
+    /// another chain.
+    pub chain_id: String,
+    pub kind: VoteKind,
+    pub slot: u64,
+    pub round: u32,
+    pub block_hash: String,
+    pub validator_public_key: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+impl Vote {
+    pub fn new(
+        chain_id: impl Into<String>,
+        kind: VoteKind,
+        slot: u64,
+        round: u32,
+        block_hash: String,
+        keypair: &KeyPair,
+    ) -> Result<Self, CryptoError> {
+        let mut vote = Self {
+            chain_id: chain_id.into(),
+            kind,
+            slot,
+            round,
+            block_hash,
+            validator_public_key: keypair.public_key.clone(),
+            signature: Vec::new(),
+        };
+        vote.signature = keypair.sign(&vote.signing_bytes())?;
+        Ok(vote)
+    }
+
+    /// Bytes covered by the validator's signature: a domain tag, then the
+    /// vote with `signature` cleared.
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let mut unsigned = self.clone();
+        unsigned.signature = Vec::new();
+        let mut bytes = SIGNING_DOMAIN.to_vec();
+        bytes.extend(bincode::serialize(&unsigned).unwrap());
+        bytes
+    }
+
+    pub fn verify(&self) -> Result<(), CryptoError> {
+        KeyPair::verify(
+            &self.validator_public_key,
+            &self.signing_bytes(),
+            &self.signature,
+        )
+        .map(|_| ())
+    }
+}
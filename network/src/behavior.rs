@@ -1,7 +1,13 @@
-use libp2p::{gossipsub, mdns, swarm::NetworkBehaviour};
+use crate::sync::SyncBehaviour;
+use libp2p::{allow_block_list, gossipsub, identify, kad, mdns, swarm::NetworkBehaviour};
 
 #[derive(NetworkBehaviour)]
 pub struct VajraBehaviour {
     pub gossipsub: gossipsub::Behaviour,
     pub mdns: mdns::tokio::Behaviour,
+    pub sync: SyncBehaviour,
+    pub kademlia: kad::Behaviour<kad::store::MemoryStore>,
+    pub identify: identify::Behaviour,
+    /// Banned peers; connections to them are refused.
+    pub blocked: allow_block_list::Behaviour<allow_block_list::BlockedPeers>,
 }
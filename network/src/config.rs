@@ -0,0 +1,213 @@
+use crate::error::NetworkError;
+use crate::topics::{GossipTopics, TopicTransition, GOSSIP_VERSION};
+use libp2p::multiaddr::Protocol;
+use libp2p::{Multiaddr, PeerId};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub const KADEMLIA_PROTOCOL: &str = "/vajra/kad/1";
+pub const IDENTIFY_PROTOCOL: &str = "/vajra/id/1";
+const DEFAULT_RANDOM_WALK_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+pub struct NetworkConfig {
+    /// Tags outgoing gossip; messages for any other chain are rejected.
+    pub chain_id: String,
+    /// Genesis hash of the chain; namespaces the gossip topics.
+    pub genesis: String,
+    pub gossip_version: u32,
+    /// Keeps an older gossip version's topics subscribed for a while after
+    /// an upgrade.
+    pub topic_transition: Option<TopicTransition>,
+    pub listen_addrs: Vec<Multiaddr>,
+    /// Publicly reachable addresses to announce to peers, e.g. when behind NAT
+    /// or a port forward. Listen addresses are announced too.
+    pub external_addrs: Vec<Multiaddr>,
+    /// Peers dialed and added to the DHT at startup. Each address has to end
+    /// in `/p2p/<peer id>`.
+    pub bootnodes: Vec<Multiaddr>,
+    /// Where bans are kept across restarts; in memory only if unset.
+    pub ban_file: Option<PathBuf>,
+    /// How often to look up a random peer id to discover more of the network.
+    pub random_walk_interval: Duration,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            chain_id: "mainnet".to_string(),
+            genesis: "mainnet".to_string(),
+            gossip_version: GOSSIP_VERSION,
+            topic_transition: None,
+            listen_addrs: vec!["/ip4/127.0.0.1/tcp/0".parse().expect("valid multiaddr")],
+            external_addrs: Vec::new(),
+            bootnodes: Vec::new(),
+            ban_file: None,
+            random_walk_interval: DEFAULT_RANDOM_WALK_INTERVAL,
+        }
+    }
+}
+
+impl NetworkConfig {
+    pub fn with_chain_id(mut self, chain_id: impl Into<String>) -> Self {
+        self.chain_id = chain_id.into();
+        self
+    }
+
+    pub fn with_genesis(mut self, genesis: impl Into<String>) -> Self {
+        self.genesis = genesis.into();
+        self
+    }
+
+    pub fn with_gossip_version(
+        mut self,
+        version: u32,
+        transition: Option<TopicTransition>,
+    ) -> Self {
+        self.gossip_version = version;
+        self.topic_transition = transition;
+        self
+    }
+
+    pub fn topics(&self) -> GossipTopics {
+        GossipTopics::new(self.genesis.clone(), self.gossip_version)
+            .with_transition(self.topic_transition)
+    }
+
+    pub fn with_ban_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.ban_file = Some(path.into());
+        self
+    }
+
+    /// Listens on `port` on every IPv4 and IPv6 interface.
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.listen_addrs = vec![
+            tcp_addr(Protocol::Ip4(Ipv4Addr::UNSPECIFIED), port),
+            tcp_addr(Protocol::Ip6(Ipv6Addr::UNSPECIFIED), port),
+        ];
+        self
+    }
+
+    /// Replaces the listen addresses, unless `addrs` is empty.
+    pub fn with_listen_addrs<I, T>(mut self, addrs: I) -> Result<Self, NetworkError>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        let addrs = parse_addrs(addrs)?;
+        if !addrs.is_empty() {
+            self.listen_addrs = addrs;
+        }
+        Ok(self)
+    }
+
+    pub fn with_external_addrs<I, T>(mut self, addrs: I) -> Result<Self, NetworkError>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        self.external_addrs.extend(parse_addrs(addrs)?);
+        Ok(self)
+    }
+
+    /// Parses and appends bootnode multiaddrs, rejecting any without a peer id.
+    pub fn with_bootnodes<I, T>(mut self, bootnodes: I) -> Result<Self, NetworkError>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        for bootnode in bootnodes {
+            let bootnode = bootnode.as_ref();
+            let addr: Multiaddr = bootnode
+                .parse()
+                .map_err(|_| NetworkError::InvalidBootnode(bootnode.to_string()))?;
+            if peer_id_of(&addr).is_none() {
+                return Err(NetworkError::InvalidBootnode(bootnode.to_string()));
+            }
+            self.bootnodes.push(addr);
+        }
+        Ok(self)
+    }
+}
+
+fn tcp_addr(ip: Protocol<'static>, port: u16) -> Multiaddr {
+    Multiaddr::empty().with(ip).with(Protocol::Tcp(port))
+}
+
+fn parse_addrs<I, T>(addrs: I) -> Result<Vec<Multiaddr>, NetworkError>
+where
+    I: IntoIterator<Item = T>,
+    T: AsRef<str>,
+{
+    addrs
+        .into_iter()
+        .map(|addr| {
+            let addr = addr.as_ref();
+            addr.parse()
+                .map_err(|_| NetworkError::InvalidAddress(addr.to_string()))
+        })
+        .collect()
+}
+
+/// The peer id in a trailing `/p2p/<id>` component.
+pub fn peer_id_of(addr: &Multiaddr) -> Option<PeerId> {
+    match addr.iter().last() {
+        Some(Protocol::P2p(peer_id)) => Some(peer_id),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bootnodes_need_peer_id() {
+        let peer = PeerId::random();
+        let config = NetworkConfig::default()
+            .with_bootnodes([format!("/ip4/10.0.0.1/tcp/8000/p2p/{peer}")])
+            .unwrap();
+        assert_eq!(peer_id_of(&config.bootnodes[0]), Some(peer));
+
+        assert!(matches!(
+            NetworkConfig::default().with_bootnodes(["/ip4/10.0.0.1/tcp/8000"]),
+            Err(NetworkError::InvalidBootnode(_))
+        ));
+        assert!(matches!(
+            NetworkConfig::default().with_bootnodes(["not an address"]),
+            Err(NetworkError::InvalidBootnode(_))
+        ));
+    }
+
+    #[test]
+    fn test_listen_and_external_addrs() {
+        let config = NetworkConfig::default().with_port(30333);
+        assert_eq!(
+            config.listen_addrs,
+            vec![
+                "/ip4/0.0.0.0/tcp/30333".parse::<Multiaddr>().unwrap(),
+                "/ip6/::/tcp/30333".parse().unwrap(),
+            ]
+        );
+
+        // An empty override keeps the port-derived addresses
+        let config = config
+            .with_listen_addrs(Vec::<String>::new())
+            .unwrap()
+            .with_external_addrs(["/ip4/203.0.113.7/tcp/30333"])
+            .unwrap();
+        assert_eq!(config.listen_addrs.len(), 2);
+        assert_eq!(config.external_addrs.len(), 1);
+
+        let config = config.with_listen_addrs(["/ip6/::1/tcp/4000"]).unwrap();
+        assert_eq!(
+            config.listen_addrs,
+            vec!["/ip6/::1/tcp/4000".parse().unwrap()]
+        );
+        assert!(matches!(
+            NetworkConfig::default().with_external_addrs(["203.0.113.7:30333"]),
+            Err(NetworkError::InvalidAddress(_))
+        ));
+    }
+}
@@ -22,4 +22,16 @@ pub enum NetworkError {
 
     #[error("Behavior initialization error: {0}")]
     BehaviorInit(String),
+
+    #[error("Invalid ban list: {0}")]
+    InvalidBanList(String),
+
+    #[error("Invalid node key: {0}")]
+    InvalidKey(String),
+
+    #[error("Invalid multiaddr: {0}")]
+    InvalidAddress(String),
+
+    #[error("Invalid bootnode address (expected a multiaddr ending in /p2p/<peer id>): {0}")]
+    InvalidBootnode(String),
 }
@@ -0,0 +1,83 @@
+use crate::error::NetworkError;
+use libp2p::identity::Keypair;
+use libp2p::PeerId;
+use std::fs;
+use std::path::Path;
+use tracing::info;
+
+/// File name of the node key inside the data directory.
+pub const NODE_KEY_FILE: &str = "node_key";
+
+/// Reads a protobuf-encoded libp2p keypair.
+pub fn load_keypair(path: &Path) -> Result<Keypair, NetworkError> {
+    let bytes = fs::read(path)?;
+    Keypair::from_protobuf_encoding(&bytes)
+        .map_err(|e| NetworkError::InvalidKey(format!("{}: {e}", path.display())))
+}
+
+/// Loads the key at `path`, or generates an Ed25519 key and saves it there so
+/// the peer id survives restarts.
+pub fn load_or_generate_keypair(path: &Path) -> Result<Keypair, NetworkError> {
+    if path.exists() {
+        return load_keypair(path);
+    }
+    let keypair = Keypair::generate_ed25519();
+    let bytes = keypair
+        .to_protobuf_encoding()
+        .map_err(|e| NetworkError::InvalidKey(e.to_string()))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    write_private(path, &bytes)?;
+    info!(
+        "Generated node key {} at {}",
+        PeerId::from(keypair.public()),
+        path.display()
+    );
+    Ok(keypair)
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(bytes)
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    fs::write(path, bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_key_persists() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("data").join(NODE_KEY_FILE);
+
+        let first = load_or_generate_keypair(&path).unwrap();
+        let second = load_or_generate_keypair(&path).unwrap();
+        assert_eq!(first.public(), second.public());
+        assert_eq!(load_keypair(&path).unwrap().public(), first.public());
+
+        fs::write(&path, b"garbage").unwrap();
+        assert!(matches!(
+            load_keypair(&path),
+            Err(NetworkError::InvalidKey(_))
+        ));
+        assert!(matches!(
+            load_keypair(&dir.path().join("missing")),
+            Err(NetworkError::Io(_))
+        ));
+    }
+}
@@ -1,4 +1,12 @@
 pub mod behavior;
+pub mod config;
 pub mod error;
+pub mod keys;
+pub mod message_id;
 pub mod messages;
+pub mod reputation;
+pub mod scoring;
 pub mod service;
+pub mod sync;
+pub mod topics;
+pub mod validation;
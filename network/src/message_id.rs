@@ -0,0 +1,166 @@
+use crate::messages::{GossipMessage, NetworkMessage};
+use crypto::Blake3;
+use libp2p::gossipsub::{self, MessageId};
+use std::collections::{HashSet, VecDeque};
+
+const BLOCK_DOMAIN: &[u8] = b"vajra/gossip/block";
+const TRANSACTION_DOMAIN: &[u8] = b"vajra/gossip/tx";
+const EVIDENCE_DOMAIN: &[u8] = b"vajra/gossip/evidence";
+const VOTE_DOMAIN: &[u8] = b"vajra/gossip/vote";
+/// For bytes that don't decode; they get rejected anyway.
+const RAW_DOMAIN: &[u8] = b"vajra/gossip/raw";
+
+/// How many accepted message ids `SeenCache` remembers by default.
+pub const DEFAULT_SEEN_CAPACITY: usize = 8192;
+
+/// Gossip message id: a domain-tagged hash over the whole encoded message,
+/// chain id and body included. Hashing only the block header would let anyone
+/// republish a signed header with other transactions first and have the real
+/// block dropped as a duplicate once the fake is rejected.
+pub fn message_id(data: &[u8]) -> MessageId {
+    let domain = match bincode::deserialize::<GossipMessage>(data) {
+        Ok(gossip) => match gossip.message {
+            NetworkMessage::Block(_) => BLOCK_DOMAIN,
+            NetworkMessage::Transaction(_) => TRANSACTION_DOMAIN,
+            NetworkMessage::Evidence(_) => EVIDENCE_DOMAIN,
+            NetworkMessage::Vote(_) => VOTE_DOMAIN,
+        },
+        Err(_) => RAW_DOMAIN,
+    };
+    let mut tagged = domain.to_vec();
+    tagged.push(0);
+    tagged.extend_from_slice(data);
+    MessageId::from(Blake3::hash(&tagged))
+}
+
+/// `message_id_fn` for the gossipsub config.
+pub fn gossip_message_id(message: &gossipsub::Message) -> MessageId {
+    message_id(&message.data)
+}
+
+/// Ids of messages already handed to the node. Gossipsub only suppresses
+/// duplicates for a short while; this keeps the last `capacity` ids so a
+/// block arriving late over another path isn't processed twice.
+#[derive(Debug)]
+pub struct SeenCache {
+    capacity: usize,
+    ids: HashSet<MessageId>,
+    order: VecDeque<MessageId>,
+}
+
+impl Default for SeenCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_SEEN_CAPACITY)
+    }
+}
+
+impl SeenCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            ids: HashSet::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    pub fn contains(&self, id: &MessageId) -> bool {
+        self.ids.contains(id)
+    }
+
+    /// Records `id`, evicting the oldest entry when full. `false` if it was
+    /// already seen.
+    pub fn insert(&mut self, id: MessageId) -> bool {
+        if !self.ids.insert(id.clone()) {
+            return false;
+        }
+        self.order.push_back(id);
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ledger::block::Block;
+
+    fn block(slot: u64) -> Block {
+        Block::new(
+            "devnet",
+            "0".to_string(),
+            0,
+            slot,
+            "root".to_string(),
+            vec![],
+            vec![],
+        )
+    }
+
+    fn encode(chain_id: &str, block: Block) -> Vec<u8> {
+        bincode::serialize(&GossipMessage {
+            chain_id: chain_id.to_string(),
+            message: NetworkMessage::Block(block),
+        })
+        .unwrap()
+    }
+
+    fn gossip(chain_id: &str, slot: u64) -> Vec<u8> {
+        encode(chain_id, block(slot))
+    }
+
+    #[test]
+    fn test_ids_follow_content() {
+        assert_eq!(message_id(&gossip("a", 1)), message_id(&gossip("a", 1)));
+        assert_ne!(message_id(&gossip("a", 1)), message_id(&gossip("a", 2)));
+    }
+
+    #[test]
+    fn test_tampered_copies_get_their_own_id() {
+        let real = block(1);
+        let real_id = message_id(&encode("a", real.clone()));
+
+        // Same signed header, different body
+        let mut tampered = real.clone();
+        tampered
+            .transactions
+            .push(ledger::transaction::Transaction {
+                data: ledger::transaction::TransactionData::Utxo(
+                    ledger::transaction::UtxoTransaction {
+                        inputs: vec![],
+                        outputs: vec![],
+                        timestamp: 0,
+                    },
+                ),
+            });
+        assert_eq!(tampered.hash(), real.hash());
+        assert_ne!(message_id(&encode("a", tampered)), real_id);
+
+        // Same block, wrong chain
+        assert_ne!(message_id(&encode("b", real)), real_id);
+    }
+
+    #[test]
+    fn test_seen_cache_evicts_oldest() {
+        let mut seen = SeenCache::new(2);
+        let ids: Vec<MessageId> = (1..=3).map(|slot| message_id(&gossip("a", slot))).collect();
+        assert!(seen.insert(ids[0].clone()));
+        assert!(!seen.insert(ids[0].clone()));
+        assert!(seen.insert(ids[1].clone()));
+        assert!(seen.insert(ids[2].clone()));
+        assert_eq!(seen.len(), 2);
+        assert!(!seen.contains(&ids[0]));
+        assert!(seen.contains(&ids[2]));
+    }
+}
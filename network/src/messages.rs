@@ -1,9 +1,21 @@
 use ledger::block::Block;
+use ledger::evidence::Evidence;
 use ledger::transaction::Transaction;
+use ledger::vote::Vote;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum NetworkMessage {
     Block(Block),
     Transaction(Transaction),
+    Evidence(Evidence),
+    Vote(Vote),
+}
+
+/// What actually goes over gossip: a message tagged with the chain it belongs
+/// to, so peers on another chain can't feed us their blocks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GossipMessage {
+    pub chain_id: String,
+    pub message: NetworkMessage,
 }
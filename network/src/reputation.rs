@@ -0,0 +1,451 @@
+use crate::error::NetworkError;
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Peers whose score drops to this are banned.
+pub const BAN_THRESHOLD: i32 = -100;
+pub const TEMPORARY_BAN: Duration = Duration::from_secs(60 * 60);
+/// Temporary bans a peer can collect before the next one is permanent.
+pub const MAX_TEMPORARY_BANS: u32 = 3;
+/// A temporary ban is forgotten after this long without another one, so a
+/// peer on a bad link isn't walked into a permanent ban over weeks.
+pub const BAN_MEMORY: Duration = Duration::from_secs(24 * 60 * 60);
+/// Score regained per `decay` call, towards zero.
+const RECOVERY_PER_DECAY: i32 = 10;
+/// Scores kept before neutral ones of disconnected peers are dropped.
+pub const MAX_TRACKED_PEERS: usize = 4096;
+
+/// Things a peer can do that cost it reputation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Offense {
+    /// Gossip that failed validation.
+    InvalidMessage,
+    /// Sync request that failed on the peer's side (bad protocol or response).
+    FailedSync,
+    /// Sync response with blocks that didn't import.
+    BadSyncResponse,
+    /// More inbound traffic than the rate limit allows.
+    RateLimited,
+    /// Block whose parent never turned up before it was evicted.
+    Orphan,
+}
+
+impl Offense {
+    pub fn penalty(self) -> i32 {
+        match self {
+            Offense::InvalidMessage => 20,
+            Offense::BadSyncResponse => 20,
+            Offense::RateLimited => 10,
+            Offense::FailedSync | Offense::Orphan => 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ban {
+    /// Unix time in milliseconds the ban ends; `None` is permanent.
+    pub until: Option<u64>,
+}
+
+impl Ban {
+    pub fn permanent() -> Self {
+        Self { until: None }
+    }
+
+    pub fn temporary(now: u64, duration: Duration) -> Self {
+        Self {
+            until: Some(now.saturating_add(duration.as_millis() as u64)),
+        }
+    }
+
+    pub fn is_active(&self, now: u64) -> bool {
+        self.until.is_none_or(|until| now < until)
+    }
+}
+
+/// Temporary bans a peer has collected. Kept with the bans, so a restart
+/// doesn't give a repeat offender a clean slate.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Strikes {
+    pub count: u32,
+    /// Unix millis of the last temporary ban, or of the last one forgotten.
+    pub last: u64,
+}
+
+/// Contents of the ban list file.
+#[derive(Default, Serialize, Deserialize)]
+struct StoredBans {
+    bans: Vec<(String, Ban)>,
+    strikes: Vec<(String, Strikes)>,
+}
+
+/// Banned peers and their strikes, written to disk on every change so both
+/// survive restarts.
+#[derive(Debug, Default)]
+pub struct BanList {
+    path: Option<PathBuf>,
+    bans: HashMap<PeerId, Ban>,
+    strikes: HashMap<PeerId, Strikes>,
+}
+
+impl BanList {
+    /// Loads the list at `path`; a missing file is an empty list.
+    pub fn load(path: &Path) -> Result<Self, NetworkError> {
+        let mut list = Self {
+            path: Some(path.to_path_buf()),
+            ..Self::default()
+        };
+        if path.exists() {
+            let stored: StoredBans = bincode::deserialize(&fs::read(path)?)
+                .map_err(|e| NetworkError::InvalidBanList(e.to_string()))?;
+            let parse = |peer: String| {
+                peer.parse::<PeerId>()
+                    .map_err(|_| NetworkError::InvalidBanList(peer.clone()))
+            };
+            for (peer, ban) in stored.bans {
+                list.bans.insert(parse(peer)?, ban);
+            }
+            for (peer, strikes) in stored.strikes {
+                list.strikes.insert(parse(peer)?, strikes);
+            }
+        }
+        Ok(list)
+    }
+
+    pub fn is_banned(&self, peer: &PeerId, now: u64) -> bool {
+        self.bans.get(peer).is_some_and(|ban| ban.is_active(now))
+    }
+
+    pub fn get(&self, peer: &PeerId) -> Option<Ban> {
+        self.bans.get(peer).copied()
+    }
+
+    pub fn strikes(&self, peer: &PeerId) -> Strikes {
+        self.strikes.get(peer).copied().unwrap_or_default()
+    }
+
+    /// Counts a temporary ban against `peer` at `now` and returns its
+    /// strikes. Saved along with the ban that follows.
+    fn strike(&mut self, peer: PeerId, now: u64) -> u32 {
+        let strikes = self.strikes.entry(peer).or_default();
+        strikes.count += 1;
+        strikes.last = now;
+        strikes.count
+    }
+
+    /// Forgets one strike of each peer that went `BAN_MEMORY` without one.
+    fn forget_strikes(&mut self, now: u64) -> Result<(), NetworkError> {
+        let memory = BAN_MEMORY.as_millis() as u64;
+        let mut changed = false;
+        for strikes in self.strikes.values_mut() {
+            if now >= strikes.last.saturating_add(memory) {
+                strikes.count = strikes.count.saturating_sub(1);
+                strikes.last = now;
+                changed = true;
+            }
+        }
+        if !changed {
+            return Ok(());
+        }
+        self.strikes.retain(|_, strikes| strikes.count > 0);
+        self.save()
+    }
+
+    /// Peers with a ban in force at `now`.
+    pub fn banned(&self, now: u64) -> Vec<PeerId> {
+        self.bans
+            .iter()
+            .filter(|(_, ban)| ban.is_active(now))
+            .map(|(peer, _)| *peer)
+            .collect()
+    }
+
+    pub fn ban(&mut self, peer: PeerId, ban: Ban) -> Result<(), NetworkError> {
+        self.bans.insert(peer, ban);
+        self.save()
+    }
+
+    pub fn unban(&mut self, peer: &PeerId) -> Result<bool, NetworkError> {
+        let removed = self.bans.remove(peer).is_some();
+        if removed {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    /// Drops temporary bans that ended by `now` and returns those peers.
+    pub fn expire(&mut self, now: u64) -> Result<Vec<PeerId>, NetworkError> {
+        let expired: Vec<PeerId> = self
+            .bans
+            .iter()
+            .filter(|(_, ban)| !ban.is_active(now))
+            .map(|(peer, _)| *peer)
+            .collect();
+        if !expired.is_empty() {
+            for peer in &expired {
+                self.bans.remove(peer);
+            }
+            self.save()?;
+        }
+        Ok(expired)
+    }
+
+    fn save(&self) -> Result<(), NetworkError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let stored = StoredBans {
+            bans: self
+                .bans
+                .iter()
+                .map(|(peer, ban)| (peer.to_base58(), *ban))
+                .collect(),
+            strikes: self
+                .strikes
+                .iter()
+                .map(|(peer, strikes)| (peer.to_base58(), *strikes))
+                .collect(),
+        };
+        let bytes =
+            bincode::serialize(&stored).map_err(|e| NetworkError::InvalidBanList(e.to_string()))?;
+        fs::write(path, bytes)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct PeerRecord {
+    score: i32,
+}
+
+/// Application-level peer scores. Offenses lower a peer's score; at
+/// `BAN_THRESHOLD` it is banned, temporarily at first and for good after
+/// `MAX_TEMPORARY_BANS`.
+#[derive(Debug, Default)]
+pub struct ReputationTracker {
+    peers: HashMap<PeerId, PeerRecord>,
+    connected: HashSet<PeerId>,
+    bans: BanList,
+}
+
+impl ReputationTracker {
+    pub fn new(bans: BanList) -> Self {
+        Self {
+            bans,
+            ..Self::default()
+        }
+    }
+
+    pub fn connected(&mut self, peer: PeerId) {
+        self.connected.insert(peer);
+    }
+
+    pub fn disconnected(&mut self, peer: &PeerId) {
+        self.connected.remove(peer);
+    }
+
+    pub fn score(&self, peer: &PeerId) -> i32 {
+        self.peers.get(peer).map_or(0, |record| record.score)
+    }
+
+    pub fn bans(&self) -> &BanList {
+        &self.bans
+    }
+
+    pub fn is_banned(&self, peer: &PeerId, now: u64) -> bool {
+        self.bans.is_banned(peer, now)
+    }
+
+    /// Penalizes `peer` and returns the ban if this pushed it over the edge.
+    pub fn report(
+        &mut self,
+        peer: PeerId,
+        offense: Offense,
+        now: u64,
+    ) -> Result<Option<Ban>, NetworkError> {
+        if self.peers.len() >= MAX_TRACKED_PEERS && !self.peers.contains_key(&peer) {
+            let connected = &self.connected;
+            self.peers
+                .retain(|peer, record| record.score < 0 || connected.contains(peer));
+        }
+        let record = self.peers.entry(peer).or_default();
+        record.score = record.score.saturating_sub(offense.penalty());
+        if record.score > BAN_THRESHOLD {
+            return Ok(None);
+        }
+
+        record.score = 0;
+        let ban = if self.bans.strike(peer, now) > MAX_TEMPORARY_BANS {
+            Ban::permanent()
+        } else {
+            Ban::temporary(now, TEMPORARY_BAN)
+        };
+        self.bans.ban(peer, ban)?;
+        Ok(Some(ban))
+    }
+
+    /// Bans `peer` directly, e.g. on operator request.
+    pub fn ban(&mut self, peer: PeerId, ban: Ban) -> Result<(), NetworkError> {
+        self.bans.ban(peer, ban)
+    }
+
+    /// Moves every score a step back towards zero, forgets old temporary bans
+    /// and lifts ended ones, returning the peers that are allowed back.
+    pub fn decay(&mut self, now: u64) -> Result<Vec<PeerId>, NetworkError> {
+        for record in self.peers.values_mut() {
+            record.score = (record.score + RECOVERY_PER_DECAY).min(0);
+        }
+        self.peers.retain(|_, record| record.score < 0);
+        self.bans.forget_strikes(now)?;
+        self.bans.expire(now)
+    }
+}
+
+/// Per-peer token buckets: each peer may burst up to `capacity` messages
+/// and then send `per_second` on average.
+#[derive(Debug)]
+pub struct RateLimiter {
+    capacity: f64,
+    per_second: f64,
+    buckets: HashMap<PeerId, (f64, Instant)>,
+}
+
+impl RateLimiter {
+    pub fn new(capacity: u32, per_second: u32) -> Self {
+        Self {
+            capacity: capacity as f64,
+            per_second: per_second as f64,
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Takes a token for `peer`; `false` if it is over the limit.
+    pub fn allow(&mut self, peer: PeerId, now: Instant) -> bool {
+        let (tokens, last) = self.buckets.entry(peer).or_insert((self.capacity, now));
+        let elapsed = now.saturating_duration_since(*last).as_secs_f64();
+        *tokens = (*tokens + elapsed * self.per_second).min(self.capacity);
+        *last = now;
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn remove(&mut self, peer: &PeerId) {
+        self.buckets.remove(peer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_offenses_escalate_to_permanent_ban() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("banned_peers");
+        let mut tracker = ReputationTracker::new(BanList::load(&path).unwrap());
+        let peer = PeerId::random();
+
+        // Four invalid messages cost 80, the fifth reaches the threshold
+        for _ in 0..4 {
+            assert_eq!(
+                tracker.report(peer, Offense::InvalidMessage, 0).unwrap(),
+                None
+            );
+        }
+        assert_eq!(tracker.score(&peer), -80);
+        let ban = tracker
+            .report(peer, Offense::InvalidMessage, 0)
+            .unwrap()
+            .unwrap();
+        assert_eq!(ban, Ban::temporary(0, TEMPORARY_BAN));
+        assert!(tracker.is_banned(&peer, 1));
+
+        // The ban is on disk and lifts once it has run out
+        let ends = TEMPORARY_BAN.as_millis() as u64;
+        assert!(BanList::load(&path).unwrap().is_banned(&peer, 1));
+        assert_eq!(tracker.decay(ends).unwrap(), vec![peer]);
+        assert!(!BanList::load(&path).unwrap().is_banned(&peer, ends));
+
+        // Strikes are on disk too, so a restart doesn't reset them
+        assert_eq!(BanList::load(&path).unwrap().strikes(&peer).count, 1);
+        let mut tracker = ReputationTracker::new(BanList::load(&path).unwrap());
+        let mut last = None;
+        for _ in 0..(MAX_TEMPORARY_BANS - 1) * 5 + 5 {
+            last = tracker.report(peer, Offense::InvalidMessage, ends).unwrap();
+        }
+        assert_eq!(last, Some(Ban::permanent()));
+        assert!(BanList::load(&path).unwrap().is_banned(&peer, u64::MAX));
+    }
+
+    #[test]
+    fn test_old_bans_are_forgotten() {
+        let mut tracker = ReputationTracker::default();
+        let peer = PeerId::random();
+        let day = BAN_MEMORY.as_millis() as u64;
+
+        // A ban a day never adds up to a permanent one
+        for n in 1..=MAX_TEMPORARY_BANS * 2 {
+            let now = n as u64 * day;
+            tracker.decay(now).unwrap();
+            let mut ban = None;
+            for _ in 0..5 {
+                ban = ban.or(tracker.report(peer, Offense::InvalidMessage, now).unwrap());
+            }
+            assert_eq!(ban, Some(Ban::temporary(now, TEMPORARY_BAN)));
+        }
+    }
+
+    #[test]
+    fn test_neutral_disconnected_peers_are_evicted() {
+        let mut tracker = ReputationTracker::default();
+        let connected = PeerId::random();
+        let offender = PeerId::random();
+        tracker.connected(connected);
+        tracker.report(offender, Offense::FailedSync, 0).unwrap();
+        // A ban resets the score, leaving a neutral entry behind
+        for _ in 0..5 {
+            tracker
+                .report(connected, Offense::InvalidMessage, 0)
+                .unwrap();
+        }
+        for _ in 0..MAX_TRACKED_PEERS {
+            let peer = PeerId::random();
+            for _ in 0..5 {
+                tracker.report(peer, Offense::InvalidMessage, 0).unwrap();
+            }
+        }
+
+        assert!(tracker.peers.len() <= MAX_TRACKED_PEERS);
+        assert!(tracker.peers.contains_key(&connected));
+        assert_eq!(tracker.score(&offender), -5);
+    }
+
+    #[test]
+    fn test_scores_recover_and_rate_limit_refills() {
+        let mut tracker = ReputationTracker::default();
+        let peer = PeerId::random();
+        tracker.report(peer, Offense::FailedSync, 0).unwrap();
+        tracker.report(peer, Offense::RateLimited, 0).unwrap();
+        assert_eq!(tracker.score(&peer), -15);
+        tracker.decay(0).unwrap();
+        tracker.decay(0).unwrap();
+        assert_eq!(tracker.score(&peer), 0);
+
+        let mut limiter = RateLimiter::new(2, 1);
+        let start = Instant::now();
+        assert!(limiter.allow(peer, start));
+        assert!(limiter.allow(peer, start));
+        assert!(!limiter.allow(peer, start));
+        assert!(limiter.allow(peer, start + Duration::from_secs(1)));
+        assert!(limiter.allow(PeerId::random(), start));
+    }
+}
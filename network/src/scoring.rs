@@ -0,0 +1,63 @@
+use crate::topics::GossipKind;
+use libp2p::gossipsub::{IdentTopic, PeerScoreParams, PeerScoreThresholds, TopicScoreParams};
+use std::time::Duration;
+
+/// Gossipsub peer scoring for our topics. Invalid messages are what we mostly
+/// care about. Mesh delivery penalties (P3) are off because blocks and
+/// evidence are too sparse for delivery rates to mean anything.
+pub fn peer_score_params(
+    topics: &[(GossipKind, IdentTopic)],
+) -> (PeerScoreParams, PeerScoreThresholds) {
+    let mut params = PeerScoreParams {
+        // A couple of bad peers behind one IP is fine, a sybil farm is not
+        ip_colocation_factor_threshold: 3.0,
+        decay_interval: Duration::from_secs(1),
+        ..PeerScoreParams::default()
+    };
+    for (kind, topic) in topics {
+        let topic_params = match kind {
+            GossipKind::Blocks => topic_params(1.0, -100.0, 100.0),
+            GossipKind::Transactions => topic_params(0.5, -10.0, 1000.0),
+            GossipKind::Evidence => topic_params(0.5, -100.0, 10.0),
+            GossipKind::Votes => topic_params(0.5, -100.0, 1000.0),
+        };
+        params.topics.insert(topic.hash(), topic_params);
+    }
+
+    let thresholds = PeerScoreThresholds {
+        gossip_threshold: -100.0,
+        publish_threshold: -200.0,
+        graylist_threshold: -400.0,
+        ..PeerScoreThresholds::default()
+    };
+    (params, thresholds)
+}
+
+fn topic_params(weight: f64, invalid_weight: f64, first_delivery_cap: f64) -> TopicScoreParams {
+    TopicScoreParams {
+        topic_weight: weight,
+        time_in_mesh_quantum: Duration::from_secs(1),
+        time_in_mesh_cap: 3600.0,
+        first_message_deliveries_cap: first_delivery_cap,
+        first_message_deliveries_decay: 0.9,
+        mesh_message_deliveries_weight: 0.0,
+        mesh_failure_penalty_weight: 0.0,
+        invalid_message_deliveries_weight: invalid_weight,
+        invalid_message_deliveries_decay: 0.99,
+        ..TopicScoreParams::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topics::GossipTopics;
+
+    #[test]
+    fn test_params_are_valid() {
+        let (params, thresholds) = peer_score_params(&GossipTopics::new("ab12", 1).current());
+        assert_eq!(params.topics.len(), 4);
+        params.validate().unwrap();
+        thresholds.validate().unwrap();
+    }
+}
@@ -1,34 +1,84 @@
-use crate::behavior::VajraBehaviour;
-use crate::messages::NetworkMessage;
+use crate::behavior::{VajraBehaviour, VajraBehaviourEvent};
+use crate::config::{self, NetworkConfig};
+use crate::message_id::{self, SeenCache};
+use crate::messages::{GossipMessage, NetworkMessage};
+use crate::reputation::{Ban, BanList, Offense, RateLimiter, ReputationTracker};
+use crate::scoring;
+use crate::sync::{self, SyncRequest, SyncResponse};
+use crate::topics::{GossipKind, GossipTopics};
+use crate::validation::{GossipValidator, MessageValidator, Validation};
+use libp2p::request_response::{self, ResponseChannel};
 use libp2p::{
-    futures::StreamExt, gossipsub, mdns, noise, swarm::SwarmEvent, tcp, yamux, Multiaddr, PeerId,
-    Swarm,
+    allow_block_list, futures::StreamExt, gossipsub, identify, kad, mdns, noise, swarm::SwarmEvent,
+    tcp, yamux, Multiaddr, PeerId, StreamProtocol, Swarm,
 };
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::mpsc;
-use tracing::{error, info};
+use tracing::{debug, error, info, warn};
+
+/// Inbound gossip per peer: burst, then messages per second.
+const GOSSIP_RATE_LIMIT: (u32, u32) = (200, 50);
+/// Inbound sync requests per peer: burst, then requests per second.
+const SYNC_RATE_LIMIT: (u32, u32) = (20, 5);
+/// How often scores recover and ended bans are lifted.
+const REPUTATION_DECAY_INTERVAL: Duration = Duration::from_secs(60);
 
 pub struct NetworkService {
     swarm: Swarm<VajraBehaviour>,
     command_receiver: mpsc::Receiver<NetworkCommand>,
     event_sender: mpsc::Sender<NetworkEvent>,
+    config: NetworkConfig,
+    topics: GossipTopics,
+    validator: Box<dyn MessageValidator>,
+    seen: SeenCache,
+    reputation: ReputationTracker,
+    gossip_limiter: RateLimiter,
+    sync_limiter: RateLimiter,
 }
 
 #[derive(Debug)]
 pub enum NetworkCommand {
     BroadcastBlock(ledger::block::Block),
     BroadcastTransaction(ledger::transaction::Transaction),
+    BroadcastEvidence(ledger::evidence::Evidence),
+    BroadcastVote(ledger::vote::Vote),
     Dial(PeerId, Multiaddr),
+    /// Penalizes a peer for something only the node can judge, like sync
+    /// blocks that fail to import.
+    ReportPeer(PeerId, Offense),
+    /// Bans a peer; `None` bans it for good.
+    BanPeer(PeerId, Option<Duration>),
+    /// Sends a sync request; the answer comes back as `SyncResponseReceived`
+    /// or `SyncFailed`.
+    RequestSync(PeerId, SyncRequest),
+    /// Answers a `SyncRequested` event. Block lists are trimmed to the
+    /// response limits.
+    RespondSync(ResponseChannel<SyncResponse>, SyncResponse),
 }
 
 #[derive(Debug)]
 pub enum NetworkEvent {
-    BlockReceived(ledger::block::Block),
+    BlockReceived {
+        /// Who forwarded it to us.
+        peer: PeerId,
+        block: Box<ledger::block::Block>,
+    },
     TransactionReceived(ledger::transaction::Transaction),
+    EvidenceReceived(ledger::evidence::Evidence),
+    VoteReceived(ledger::vote::Vote),
     PeerConnected(PeerId),
+    PeerDisconnected(PeerId),
     NewListenAddr(Multiaddr),
+    SyncRequested {
+        peer: PeerId,
+        request: SyncRequest,
+        channel: ResponseChannel<SyncResponse>,
+    },
+    SyncResponseReceived {
+        peer: PeerId,
+        response: SyncResponse,
+    },
+    SyncFailed(PeerId),
 }
 
 use crate::error::NetworkError;
@@ -43,35 +93,86 @@ impl NetworkService {
             mpsc::Receiver<NetworkEvent>,
         ),
         NetworkError,
+    > {
+        Self::with_config(local_key, NetworkConfig::default()).await
+    }
+
+    pub async fn with_config(
+        local_key: libp2p::identity::Keypair,
+        config: NetworkConfig,
+    ) -> Result<
+        (
+            Self,
+            mpsc::Sender<NetworkCommand>,
+            mpsc::Receiver<NetworkEvent>,
+        ),
+        NetworkError,
     > {
         let local_peer_id = PeerId::from(local_key.public());
         info!("Local peer id: {:?}", local_peer_id);
 
         // Gossipsub configuration
-        let message_id_fn = |message: &gossipsub::Message| {
-            let mut s = DefaultHasher::new();
-            message.data.hash(&mut s);
-            gossipsub::MessageId::from(s.finish().to_string())
-        };
-
         let gossipsub_config = gossipsub::ConfigBuilder::default()
             .heartbeat_interval(Duration::from_secs(1))
             .validation_mode(gossipsub::ValidationMode::Strict)
-            .message_id_fn(message_id_fn)
+            // Nothing is forwarded until `report_message_validation_result`
+            .validate_messages()
+            .message_id_fn(message_id::gossip_message_id)
             .build()
             .map_err(|msg| NetworkError::BehaviorInit(format!("{:?}", msg)))?;
 
-        let gossipsub = gossipsub::Behaviour::new(
+        let mut gossipsub = gossipsub::Behaviour::new(
             gossipsub::MessageAuthenticity::Signed(local_key.clone()),
             gossipsub_config,
         )
         .map_err(|msg| NetworkError::BehaviorInit(format!("{:?}", msg)))?;
+        let topics = config.topics();
+        let mut scored_topics = topics.current();
+        scored_topics.extend(topics.legacy(unix_millis()));
+        let (score_params, score_thresholds) = scoring::peer_score_params(&scored_topics);
+        gossipsub
+            .with_peer_score(score_params, score_thresholds)
+            .map_err(NetworkError::BehaviorInit)?;
+
+        // Bans from earlier runs still apply
+        let bans = match &config.ban_file {
+            Some(path) => BanList::load(path)?,
+            None => BanList::default(),
+        };
+        let mut blocked = allow_block_list::Behaviour::default();
+        for peer in bans.banned(unix_millis()) {
+            blocked.block_peer(peer);
+            gossipsub.blacklist_peer(&peer);
+        }
 
         // mDNS configuration
         let mdns = mdns::tokio::Behaviour::new(mdns::Config::default(), local_peer_id)
             .map_err(|e| NetworkError::BehaviorInit(e.to_string()))?;
 
-        let behaviour = VajraBehaviour { gossipsub, mdns };
+        // Kademlia for discovery beyond the LAN; always in server mode so
+        // peers can route through us
+        let mut kad_config = kad::Config::default();
+        kad_config.set_protocol_names(vec![StreamProtocol::new(config::KADEMLIA_PROTOCOL)]);
+        let mut kademlia = kad::Behaviour::with_config(
+            local_peer_id,
+            kad::store::MemoryStore::new(local_peer_id),
+            kad_config,
+        );
+        kademlia.set_mode(Some(kad::Mode::Server));
+
+        let identify = identify::Behaviour::new(identify::Config::new(
+            config::IDENTIFY_PROTOCOL.to_string(),
+            local_key.public(),
+        ));
+
+        let behaviour = VajraBehaviour {
+            gossipsub,
+            mdns,
+            sync: sync::new_behaviour(),
+            kademlia,
+            identify,
+            blocked,
+        };
 
         let swarm = libp2p::SwarmBuilder::with_existing_identity(local_key)
             .with_tokio()
@@ -94,83 +195,153 @@ impl NetworkService {
                 swarm,
                 command_receiver,
                 event_sender,
+                validator: Box::new(GossipValidator::new(config.chain_id.clone())),
+                config,
+                topics,
+                seen: SeenCache::default(),
+                reputation: ReputationTracker::new(bans),
+                gossip_limiter: RateLimiter::new(GOSSIP_RATE_LIMIT.0, GOSSIP_RATE_LIMIT.1),
+                sync_limiter: RateLimiter::new(SYNC_RATE_LIMIT.0, SYNC_RATE_LIMIT.1),
             },
             command_sender,
             event_receiver,
         ))
     }
 
+    /// Replaces the default stateless gossip checks.
+    pub fn with_validator(mut self, validator: impl MessageValidator + 'static) -> Self {
+        self.validator = Box::new(validator);
+        self
+    }
+
     pub async fn run(mut self) {
-        // Subscribe to topics
-        let block_topic = gossipsub::IdentTopic::new("blocks");
-        let tx_topic = gossipsub::IdentTopic::new("transactions");
+        let mut subscriptions = self.topics.current();
+        subscriptions.extend(self.topics.legacy(unix_millis()));
+        for (_, topic) in subscriptions {
+            if let Err(e) = self.swarm.behaviour_mut().gossipsub.subscribe(&topic) {
+                error!("Failed to subscribe to {topic}: {e:?}");
+            }
+        }
+        // Sleeps forever when there is no transition window to close
+        let transition_end = tokio::time::sleep(match self.topics.transition() {
+            Some(t) => Duration::from_millis(t.until.saturating_sub(unix_millis())),
+            None => Duration::MAX,
+        });
+        tokio::pin!(transition_end);
 
-        // Handle results safely (in prod, we'd retry or log better)
-        let _ = self.swarm.behaviour_mut().gossipsub.subscribe(&block_topic);
-        let _ = self.swarm.behaviour_mut().gossipsub.subscribe(&tx_topic);
+        for addr in self.config.listen_addrs.clone() {
+            if let Err(e) = self.swarm.listen_on(addr.clone()) {
+                error!("Failed to listen on {addr}: {e}");
+            }
+        }
+        for addr in self.config.external_addrs.clone() {
+            info!("Announcing external address {addr}");
+            self.swarm.add_external_address(addr);
+        }
 
-        // Listen on all interfaces
-        let _ = self
-            .swarm
-            .listen_on("/ip4/127.0.0.1/tcp/0".parse().unwrap()); // Changed to 127.0.0.1 for better local test reliability
+        self.connect_bootnodes();
+        let mut random_walk = tokio::time::interval(self.config.random_walk_interval);
+        let mut reputation_decay = tokio::time::interval(REPUTATION_DECAY_INTERVAL);
 
         loop {
             tokio::select! {
+                _ = random_walk.tick() => {
+                    // Looking up a random id refreshes buckets all over the keyspace
+                    self.swarm.behaviour_mut().kademlia.get_closest_peers(PeerId::random());
+                },
+                _ = reputation_decay.tick() => self.decay_reputation(),
+                _ = &mut transition_end, if self.topics.transition().is_some() => {
+                    for (_, topic) in self.topics.end_transition() {
+                        info!("Leaving old gossip topic {topic}");
+                        let _ = self.swarm.behaviour_mut().gossipsub.unsubscribe(&topic);
+                    }
+                },
                 event = self.swarm.select_next_some() => match event {
-                    SwarmEvent::Behaviour(crate::behavior::VajraBehaviourEvent::Mdns(mdns::Event::Discovered(list))) => {
-                        for (peer_id, _multiaddr) in list {
+                    SwarmEvent::Behaviour(VajraBehaviourEvent::Mdns(mdns::Event::Discovered(list))) => {
+                        for (peer_id, multiaddr) in list {
                             info!("mDNS discovered a new peer: {peer_id}");
-                            self.swarm.behaviour_mut().gossipsub.add_explicit_peer(&peer_id);
-                            let _ = self.event_sender.send(NetworkEvent::PeerConnected(peer_id)).await;
+                            // Gossipsub picks the peer up once connected; making it explicit
+                            // would exempt it from peer scoring
+                            self.swarm.behaviour_mut().kademlia.add_address(&peer_id, multiaddr.clone());
+                            if !self.swarm.is_connected(&peer_id) {
+                                let _ = self.swarm.dial(multiaddr);
+                            }
+                        }
+                    },
+                    SwarmEvent::Behaviour(VajraBehaviourEvent::Gossipsub(gossipsub::Event::Message { propagation_source, message_id, message })) => {
+                        self.handle_gossip(propagation_source, message_id, &message).await;
+                    },
+                    SwarmEvent::Behaviour(VajraBehaviourEvent::Sync(event)) => self.handle_sync_event(event).await,
+                    SwarmEvent::Behaviour(VajraBehaviourEvent::Identify(identify::Event::Received { peer_id, info })) => {
+                        for addr in info.listen_addrs {
+                            self.swarm.behaviour_mut().kademlia.add_address(&peer_id, addr);
                         }
                     },
-                    SwarmEvent::Behaviour(crate::behavior::VajraBehaviourEvent::Gossipsub(gossipsub::Event::Message { propagation_source, message_id: _, message })) => {
-                         if let Ok(msg) = bincode::deserialize::<NetworkMessage>(&message.data) {
-                             info!("Received message from {propagation_source}: {:?}", msg);
-                             match msg {
-                                 NetworkMessage::Block(b) => {
-                                     let _ = self.event_sender.send(NetworkEvent::BlockReceived(b)).await;
-                                 }
-                                 NetworkMessage::Transaction(t) => {
-                                     let _ = self.event_sender.send(NetworkEvent::TransactionReceived(t)).await;
-                                 }
-                             }
-                         }
+                    SwarmEvent::Behaviour(VajraBehaviourEvent::Kademlia(kad::Event::OutboundQueryProgressed {
+                        result: kad::QueryResult::GetClosestPeers(Ok(kad::GetClosestPeersOk { peers, .. })),
+                        ..
+                    })) => {
+                        for peer in peers {
+                            if !self.swarm.is_connected(&peer) && peer != *self.swarm.local_peer_id() {
+                                debug!("DHT walk found {peer}");
+                                let _ = self.swarm.dial(peer);
+                            }
+                        }
                     },
                     SwarmEvent::NewListenAddr { address, .. } => {
                         info!("Listening on {address}");
                         let _ = self.event_sender.send(NetworkEvent::NewListenAddr(address)).await;
                     },
+                    SwarmEvent::ListenerError { error, .. } => {
+                        error!("Listener error: {error}");
+                    },
                     SwarmEvent::OutgoingConnectionError { peer_id: Some(_peer_id), error: _error, .. } => {
                         // error!("Failed to dial {}: {}", _peer_id, _error);
                     },
                     SwarmEvent::ConnectionEstablished { peer_id, .. } => {
                          info!("Connected to {peer_id}");
-                         self.swarm.behaviour_mut().gossipsub.add_explicit_peer(&peer_id);
+                         self.reputation.connected(peer_id);
                          let _ = self.event_sender.send(NetworkEvent::PeerConnected(peer_id)).await;
                     },
+                    SwarmEvent::ConnectionClosed { peer_id, num_established: 0, .. } => {
+                         self.gossip_limiter.remove(&peer_id);
+                         self.sync_limiter.remove(&peer_id);
+                         self.reputation.disconnected(&peer_id);
+                         let _ = self.event_sender.send(NetworkEvent::PeerDisconnected(peer_id)).await;
+                    },
                     _ => {}
                 },
                 command = self.command_receiver.recv() => match command {
-                    Some(NetworkCommand::BroadcastBlock(block)) => {
-                        if let Ok(data) = bincode::serialize(&NetworkMessage::Block(block)) {
-                            let topic = gossipsub::IdentTopic::new("blocks");
-                            if let Err(e) = self.swarm.behaviour_mut().gossipsub.publish(topic, data) {
-                                error!("Publish error: {e:?}");
-                            }
+                    Some(NetworkCommand::BroadcastBlock(block)) => self.publish(NetworkMessage::Block(block)),
+                    Some(NetworkCommand::BroadcastTransaction(tx)) => self.publish(NetworkMessage::Transaction(tx)),
+                    Some(NetworkCommand::BroadcastEvidence(evidence)) => self.publish(NetworkMessage::Evidence(evidence)),
+                    Some(NetworkCommand::BroadcastVote(vote)) => self.publish(NetworkMessage::Vote(vote)),
+                    Some(NetworkCommand::Dial(_peer_id, addr)) => {
+                        if let Err(e) = self.swarm.dial(addr.clone()) {
+                             error!("Dial error: {e:?}");
                         }
                     },
-                    Some(NetworkCommand::BroadcastTransaction(tx)) => {
-                        if let Ok(data) = bincode::serialize(&NetworkMessage::Transaction(tx)) {
-                            let topic = gossipsub::IdentTopic::new("transactions");
-                            if let Err(e) = self.swarm.behaviour_mut().gossipsub.publish(topic, data) {
-                                error!("Publish error: {e:?}");
-                            }
+                    Some(NetworkCommand::ReportPeer(peer, offense)) => self.report(peer, offense),
+                    Some(NetworkCommand::BanPeer(peer, duration)) => {
+                        let ban = match duration {
+                            Some(duration) => Ban::temporary(unix_millis(), duration),
+                            None => Ban::permanent(),
+                        };
+                        if let Err(e) = self.reputation.ban(peer, ban) {
+                            error!("Failed to store ban of {peer}: {e}");
                         }
+                        self.block(peer);
                     },
-                    Some(NetworkCommand::Dial(_peer_id, addr)) => {
-                        if let Err(e) = self.swarm.dial(addr.clone()) {
-                             error!("Dial error: {e:?}");
+                    Some(NetworkCommand::RequestSync(peer, request)) => {
+                        self.swarm.behaviour_mut().sync.send_request(&peer, request);
+                    },
+                    Some(NetworkCommand::RespondSync(channel, response)) => {
+                        let response = match response {
+                            SyncResponse::Blocks(blocks) => SyncResponse::Blocks(sync::limit_response(blocks)),
+                            status => status,
+                        };
+                        if self.swarm.behaviour_mut().sync.send_response(channel, response).is_err() {
+                            debug!("Sync requester went away before the response was sent");
                         }
                     },
                     None => break,
@@ -178,4 +349,186 @@ impl NetworkService {
             }
         }
     }
+
+    fn publish(&mut self, message: NetworkMessage) {
+        let topic = self.topics.publish_topic(GossipKind::of(&message));
+        let message = GossipMessage {
+            chain_id: self.config.chain_id.clone(),
+            message,
+        };
+        if let Ok(data) = bincode::serialize(&message) {
+            self.seen.insert(message_id::message_id(&data));
+            if let Err(e) = self.swarm.behaviour_mut().gossipsub.publish(topic, data) {
+                error!("Publish error: {e:?}");
+            }
+        }
+    }
+
+    /// Runs the validator on an inbound message, tells gossipsub whether to
+    /// forward it and hands accepted messages to the node.
+    async fn handle_gossip(
+        &mut self,
+        source: PeerId,
+        message_id: gossipsub::MessageId,
+        message: &gossipsub::Message,
+    ) {
+        let decoded = bincode::deserialize::<GossipMessage>(&message.data);
+        let validation = if self.seen.contains(&message_id) {
+            // Already handled; a late copy over another path
+            Validation::Ignore
+        } else if !self.gossip_limiter.allow(source, Instant::now()) {
+            self.report(source, Offense::RateLimited);
+            Validation::Ignore
+        } else {
+            match &decoded {
+                Ok(message) => self.validator.validate(message),
+                // Likely an older wire format from a node that hasn't upgraded
+                Err(_) if self.topics.is_legacy(&message.topic) => Validation::Ignore,
+                Err(_) => Validation::Reject("undecodable message".to_string()),
+            }
+        };
+        if let Err(e) = self
+            .swarm
+            .behaviour_mut()
+            .gossipsub
+            .report_message_validation_result(&message_id, &source, validation.acceptance())
+        {
+            debug!("Failed to forward {message_id}: {e:?}");
+        }
+
+        match (validation, decoded) {
+            (Validation::Accept, Ok(message)) => {
+                self.seen.insert(message_id);
+                debug!("Accepted gossip from {source}: {:?}", message.message);
+                let event = match message.message {
+                    NetworkMessage::Block(block) => NetworkEvent::BlockReceived {
+                        peer: source,
+                        block: Box::new(block),
+                    },
+                    NetworkMessage::Transaction(t) => NetworkEvent::TransactionReceived(t),
+                    NetworkMessage::Evidence(e) => NetworkEvent::EvidenceReceived(e),
+                    NetworkMessage::Vote(v) => NetworkEvent::VoteReceived(v),
+                };
+                let _ = self.event_sender.send(event).await;
+            }
+            (Validation::Reject(reason), _) => {
+                debug!("Rejected gossip from {source}: {reason}");
+                self.report(source, Offense::InvalidMessage);
+            }
+            _ => {}
+        }
+    }
+
+    /// Penalizes `peer` and blocks it if that got it banned.
+    fn report(&mut self, peer: PeerId, offense: Offense) {
+        match self.reputation.report(peer, offense, unix_millis()) {
+            Ok(Some(ban)) => {
+                warn!("Banning {peer} (until {:?}) after {offense:?}", ban.until);
+                self.block(peer);
+            }
+            Ok(None) => {}
+            Err(e) => error!("Failed to store ban of {peer}: {e}"),
+        }
+    }
+
+    /// Closes and refuses connections to `peer` and drops it from gossip.
+    fn block(&mut self, peer: PeerId) {
+        let behaviour = self.swarm.behaviour_mut();
+        behaviour.blocked.block_peer(peer);
+        behaviour.gossipsub.blacklist_peer(&peer);
+        behaviour.kademlia.remove_peer(&peer);
+    }
+
+    fn decay_reputation(&mut self) {
+        match self.reputation.decay(unix_millis()) {
+            Ok(unbanned) => {
+                for peer in unbanned {
+                    info!("Ban of {peer} ended");
+                    let behaviour = self.swarm.behaviour_mut();
+                    behaviour.blocked.unblock_peer(peer);
+                    behaviour.gossipsub.remove_blacklisted_peer(&peer);
+                }
+            }
+            Err(e) => error!("Failed to update ban list: {e}"),
+        }
+    }
+
+    /// Seeds the routing table with the configured bootnodes, dials them and
+    /// starts a DHT bootstrap.
+    fn connect_bootnodes(&mut self) {
+        for addr in self.config.bootnodes.clone() {
+            let Some(peer_id) = config::peer_id_of(&addr) else {
+                continue;
+            };
+            self.swarm
+                .behaviour_mut()
+                .kademlia
+                .add_address(&peer_id, addr.clone());
+            if let Err(e) = self.swarm.dial(addr) {
+                error!("Failed to dial bootnode {peer_id}: {e}");
+            }
+        }
+        if !self.config.bootnodes.is_empty() {
+            if let Err(e) = self.swarm.behaviour_mut().kademlia.bootstrap() {
+                debug!("DHT bootstrap skipped: {e}");
+            }
+        }
+    }
+
+    async fn handle_sync_event(
+        &mut self,
+        event: request_response::Event<SyncRequest, SyncResponse>,
+    ) {
+        match event {
+            request_response::Event::Message { peer, message } => match message {
+                request_response::Message::Request {
+                    request, channel, ..
+                } => {
+                    if !self.sync_limiter.allow(peer, Instant::now()) {
+                        debug!("Dropping sync request from {peer}: rate limited");
+                        self.report(peer, Offense::RateLimited);
+                        return;
+                    }
+                    if !request.is_within_limits() {
+                        // Dropping the channel closes the stream without an answer
+                        debug!("Ignoring oversized sync request from {peer}");
+                        return;
+                    }
+                    let _ = self
+                        .event_sender
+                        .send(NetworkEvent::SyncRequested {
+                            peer,
+                            request,
+                            channel,
+                        })
+                        .await;
+                }
+                request_response::Message::Response { response, .. } => {
+                    let _ = self
+                        .event_sender
+                        .send(NetworkEvent::SyncResponseReceived { peer, response })
+                        .await;
+                }
+            },
+            request_response::Event::OutboundFailure { peer, error, .. } => {
+                debug!("Sync request to {peer} failed: {error}");
+                // Dial failures, timeouts and dropped connections may well be our end
+                if matches!(
+                    error,
+                    request_response::OutboundFailure::UnsupportedProtocols
+                        | request_response::OutboundFailure::Io(_)
+                ) {
+                    self.report(peer, Offense::FailedSync);
+                }
+                let _ = self.event_sender.send(NetworkEvent::SyncFailed(peer)).await;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
 }
@@ -0,0 +1,102 @@
+use ledger::block::Block;
+use libp2p::request_response::{self, ProtocolSupport};
+use libp2p::StreamProtocol;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+pub const SYNC_PROTOCOL: &str = "/vajra/sync/1";
+/// Most blocks a peer will ask for or serve in one response.
+pub const MAX_BLOCKS_PER_RESPONSE: u64 = 64;
+/// Responses are cut off once the serialized blocks pass this size.
+pub const MAX_RESPONSE_BYTES: usize = 4 * 1024 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Heights count blocks on the canonical chain; the first block after
+/// genesis is height 1.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyncRequest {
+    /// Ask for the peer's head.
+    Status,
+    BlocksByRange {
+        start_height: u64,
+        count: u64,
+    },
+    BlocksByHash(Vec<String>),
+}
+
+impl SyncRequest {
+    pub fn is_within_limits(&self) -> bool {
+        match self {
+            SyncRequest::Status => true,
+            SyncRequest::BlocksByRange { count, .. } => *count <= MAX_BLOCKS_PER_RESPONSE,
+            SyncRequest::BlocksByHash(hashes) => hashes.len() as u64 <= MAX_BLOCKS_PER_RESPONSE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyncResponse {
+    Status {
+        head_height: u64,
+        head_hash: String,
+    },
+    /// In ascending height order for range requests. May be shorter than asked
+    /// for if the peer doesn't have the blocks or hit the size limit.
+    Blocks(Vec<Block>),
+}
+
+pub type SyncBehaviour = request_response::cbor::Behaviour<SyncRequest, SyncResponse>;
+
+pub fn new_behaviour() -> SyncBehaviour {
+    SyncBehaviour::new(
+        [(StreamProtocol::new(SYNC_PROTOCOL), ProtocolSupport::Full)],
+        request_response::Config::default().with_request_timeout(REQUEST_TIMEOUT),
+    )
+}
+
+/// Truncates `blocks` to the per-response count and byte limits.
+pub fn limit_response(mut blocks: Vec<Block>) -> Vec<Block> {
+    blocks.truncate(MAX_BLOCKS_PER_RESPONSE as usize);
+    let mut bytes = 0;
+    let keep = blocks
+        .iter()
+        .take_while(|block| {
+            bytes += bincode::serialized_size(block).unwrap_or(u64::MAX) as usize;
+            bytes <= MAX_RESPONSE_BYTES
+        })
+        .count();
+    blocks.truncate(keep);
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(slot: u64) -> Block {
+        Block::new(
+            "devnet",
+            "0".to_string(),
+            0,
+            slot,
+            "root".to_string(),
+            vec![],
+            vec![],
+        )
+    }
+
+    #[test]
+    fn test_limits() {
+        assert!(SyncRequest::BlocksByRange {
+            start_height: 1,
+            count: MAX_BLOCKS_PER_RESPONSE
+        }
+        .is_within_limits());
+        assert!(!SyncRequest::BlocksByHash(vec![String::new(); 65]).is_within_limits());
+
+        let blocks: Vec<Block> = (0..100).map(block).collect();
+        let limited = limit_response(blocks);
+        assert_eq!(limited.len() as u64, MAX_BLOCKS_PER_RESPONSE);
+        assert_eq!(limited[0].header.slot, 0);
+    }
+}
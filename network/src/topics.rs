@@ -0,0 +1,170 @@
+use crate::messages::NetworkMessage;
+use libp2p::gossipsub::{IdentTopic, TopicHash};
+use serde::{Deserialize, Serialize};
+
+/// Gossip protocol version in topic names. Bump it when the wire format of
+/// gossip messages changes.
+pub const GOSSIP_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GossipKind {
+    Blocks,
+    Transactions,
+    Evidence,
+    Votes,
+}
+
+impl GossipKind {
+    pub const ALL: [GossipKind; 4] = [
+        GossipKind::Blocks,
+        GossipKind::Transactions,
+        GossipKind::Evidence,
+        GossipKind::Votes,
+    ];
+
+    pub fn of(message: &NetworkMessage) -> Self {
+        match message {
+            NetworkMessage::Block(_) => GossipKind::Blocks,
+            NetworkMessage::Transaction(_) => GossipKind::Transactions,
+            NetworkMessage::Evidence(_) => GossipKind::Evidence,
+            NetworkMessage::Vote(_) => GossipKind::Votes,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GossipKind::Blocks => "blocks",
+            GossipKind::Transactions => "transactions",
+            GossipKind::Evidence => "evidence",
+            GossipKind::Votes => "votes",
+        }
+    }
+}
+
+/// `/vajra/<genesis>/<kind>/<version>`.
+pub fn topic(genesis: &str, kind: GossipKind, version: u32) -> IdentTopic {
+    IdentTopic::new(format!("/vajra/{genesis}/{}/{version}", kind.name()))
+}
+
+/// An older gossip version to keep listening to after an upgrade, so blocks
+/// from nodes that haven't upgraded yet still arrive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TopicTransition {
+    pub previous_version: u32,
+    /// Unix time in milliseconds after which the old topics are dropped.
+    pub until: u64,
+}
+
+/// The topics a node publishes on and listens to.
+#[derive(Debug, Clone)]
+pub struct GossipTopics {
+    genesis: String,
+    version: u32,
+    transition: Option<TopicTransition>,
+}
+
+impl GossipTopics {
+    pub fn new(genesis: impl Into<String>, version: u32) -> Self {
+        Self {
+            genesis: genesis.into(),
+            version,
+            transition: None,
+        }
+    }
+
+    pub fn with_transition(mut self, transition: Option<TopicTransition>) -> Self {
+        self.transition = transition.filter(|t| t.previous_version != self.version);
+        self
+    }
+
+    pub fn transition(&self) -> Option<TopicTransition> {
+        self.transition
+    }
+
+    /// Where messages of `kind` are published. Always the current version.
+    pub fn publish_topic(&self, kind: GossipKind) -> IdentTopic {
+        topic(&self.genesis, kind, self.version)
+    }
+
+    pub fn current(&self) -> Vec<(GossipKind, IdentTopic)> {
+        self.versioned(self.version)
+    }
+
+    /// The previous version's topics while its window is open at `now`.
+    pub fn legacy(&self, now: u64) -> Vec<(GossipKind, IdentTopic)> {
+        match self.transition {
+            Some(t) if now < t.until => self.versioned(t.previous_version),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Whether `hash` is one of the previous version's topics. Their messages
+    /// may be in a wire format this node no longer decodes.
+    pub fn is_legacy(&self, hash: &TopicHash) -> bool {
+        self.transition.is_some_and(|t| {
+            self.versioned(t.previous_version)
+                .iter()
+                .any(|(_, topic)| topic.hash() == *hash)
+        })
+    }
+
+    /// Closes the transition window and returns the topics to leave.
+    pub fn end_transition(&mut self) -> Vec<(GossipKind, IdentTopic)> {
+        match self.transition.take() {
+            Some(t) => self.versioned(t.previous_version),
+            None => Vec::new(),
+        }
+    }
+
+    fn versioned(&self, version: u32) -> Vec<(GossipKind, IdentTopic)> {
+        GossipKind::ALL
+            .into_iter()
+            .map(|kind| (kind, topic(&self.genesis, kind, version)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topics_are_namespaced_and_versioned() {
+        let topics = GossipTopics::new("ab12", 2).with_transition(Some(TopicTransition {
+            previous_version: 1,
+            until: 1_000,
+        }));
+        assert_eq!(
+            topics.publish_topic(GossipKind::Blocks).to_string(),
+            "/vajra/ab12/blocks/2"
+        );
+        assert_ne!(
+            topic("ab12", GossipKind::Blocks, 1).hash(),
+            topic("cd34", GossipKind::Blocks, 1).hash()
+        );
+
+        let legacy: Vec<String> = topics
+            .legacy(999)
+            .into_iter()
+            .map(|(_, topic)| topic.to_string())
+            .collect();
+        assert_eq!(
+            legacy,
+            [
+                "/vajra/ab12/blocks/1",
+                "/vajra/ab12/transactions/1",
+                "/vajra/ab12/evidence/1",
+                "/vajra/ab12/votes/1"
+            ]
+        );
+        assert!(topics.legacy(1_000).is_empty());
+        assert!(topics.is_legacy(&topic("ab12", GossipKind::Evidence, 1).hash()));
+        assert!(!topics.is_legacy(&topics.publish_topic(GossipKind::Evidence).hash()));
+
+        let mut topics = topics;
+        assert_eq!(topics.end_transition().len(), 4);
+        assert!(!topics.is_legacy(&topic("ab12", GossipKind::Evidence, 1).hash()));
+        assert!(topics.transition().is_none());
+        assert!(topics.end_transition().is_empty());
+    }
+}
@@ -0,0 +1,242 @@
+use crate::messages::{GossipMessage, NetworkMessage};
+use crypto::KeyPair;
+use ledger::block::Block;
+use ledger::transaction::{Transaction, TransactionData};
+use ledger::vote::Vote;
+use libp2p::gossipsub::MessageAcceptance;
+
+/// Outcome of checking an inbound gossip message before it is forwarded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Validation {
+    Accept,
+    /// Don't forward, but the sender did nothing wrong (e.g. stale data).
+    Ignore,
+    /// Don't forward and count it against the sender.
+    Reject(String),
+}
+
+impl Validation {
+    pub fn acceptance(&self) -> MessageAcceptance {
+        match self {
+            Validation::Accept => MessageAcceptance::Accept,
+            Validation::Ignore => MessageAcceptance::Ignore,
+            Validation::Reject(_) => MessageAcceptance::Reject,
+        }
+    }
+}
+
+/// Hook run on every decoded gossip message. Only accepted messages are
+/// forwarded to peers and handed to the node.
+pub trait MessageValidator: Send + Sync {
+    fn validate(&self, message: &GossipMessage) -> Validation;
+}
+
+/// Stateless checks any node can do without chain state: chain id,
+/// signatures and the commitments inside blocks. The envelope's chain id is
+/// only a cheap pre-filter; what counts is the chain id under each signature.
+pub struct GossipValidator {
+    chain_id: String,
+}
+
+impl GossipValidator {
+    pub fn new(chain_id: impl Into<String>) -> Self {
+        Self {
+            chain_id: chain_id.into(),
+        }
+    }
+}
+
+impl GossipValidator {
+    fn validate_vote(&self, vote: &Vote) -> Result<(), String> {
+        if vote.chain_id != self.chain_id {
+            return Err(format!("vote for chain {}", vote.chain_id));
+        }
+        vote.verify().map_err(|_| "bad vote signature".to_string())
+    }
+
+    fn validate_block(&self, block: &Block) -> Result<(), String> {
+        let header = &block.header;
+        if header.chain_id != self.chain_id {
+            return Err(format!("block for chain {}", header.chain_id));
+        }
+        KeyPair::verify(
+            &header.validator_public_key,
+            &header.signing_bytes(),
+            &header.signature,
+        )
+        .map_err(|_| "bad proposer signature".to_string())?;
+        if !block.verify_transactions_root() {
+            return Err("transactions root mismatch".to_string());
+        }
+        if !block.verify_evidence_root() {
+            return Err("evidence root mismatch".to_string());
+        }
+        block
+            .transactions
+            .iter()
+            .try_for_each(|tx| self.validate_transaction(tx))
+    }
+
+    fn validate_transaction(&self, tx: &Transaction) -> Result<(), String> {
+        let (chain_id, signed) = match &tx.data {
+            TransactionData::Account(tx) => (&tx.chain_id, tx.verify_signature()),
+            TransactionData::Staking(tx) => (&tx.chain_id, tx.verify_signature()),
+            TransactionData::Utxo(tx) => {
+                return if tx.inputs.is_empty() || tx.outputs.is_empty() {
+                    Err("utxo transaction without inputs or outputs".to_string())
+                } else {
+                    Ok(())
+                };
+            }
+        };
+        if *chain_id != self.chain_id {
+            return Err(format!("transaction for chain {chain_id}"));
+        }
+        match signed {
+            Ok(true) => Ok(()),
+            _ => Err("bad transaction signature".to_string()),
+        }
+    }
+}
+
+impl MessageValidator for GossipValidator {
+    fn validate(&self, message: &GossipMessage) -> Validation {
+        if message.chain_id != self.chain_id {
+            return Validation::Reject(format!("wrong chain id {}", message.chain_id));
+        }
+        let result = match &message.message {
+            NetworkMessage::Block(block) => self.validate_block(block),
+            NetworkMessage::Transaction(tx) => self.validate_transaction(tx),
+            NetworkMessage::Evidence(evidence) => evidence
+                .verify()
+                .map_err(|e| format!("invalid evidence: {e}")),
+            NetworkMessage::Vote(vote) => self.validate_vote(vote),
+        };
+        match result {
+            Ok(()) => Validation::Accept,
+            Err(reason) => Validation::Reject(reason),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::SignatureScheme;
+    use ledger::transaction::AccountTransaction;
+    use ledger::vote::VoteKind;
+
+    fn signed_block(chain_id: &str, proposer: &KeyPair, transactions: Vec<Transaction>) -> Block {
+        let mut block = Block::new(
+            chain_id,
+            "0".to_string(),
+            0,
+            1,
+            "root".to_string(),
+            transactions,
+            proposer.public_key.clone(),
+        );
+        block.header.signature = proposer.sign(&block.header.signing_bytes()).unwrap();
+        block
+    }
+
+    fn gossip(message: NetworkMessage) -> GossipMessage {
+        GossipMessage {
+            chain_id: "devnet".to_string(),
+            message,
+        }
+    }
+
+    fn transfer(chain_id: &str, sender: &KeyPair) -> Transaction {
+        let mut tx = AccountTransaction {
+            chain_id: chain_id.to_string(),
+            nonce: 0,
+            to: None,
+            amount: 1,
+            data: vec![],
+            gas_limit: 21_000,
+            gas_price: 1,
+            signature: vec![],
+            sender_public_key: sender.public_key.clone(),
+            signature_scheme: SignatureScheme::Ed25519,
+        };
+        tx.signature = sender.sign(&tx.signing_bytes().unwrap()).unwrap();
+        Transaction {
+            data: TransactionData::Account(tx),
+        }
+    }
+
+    #[test]
+    fn test_accepts_signed_and_rejects_tampered() {
+        let validator = GossipValidator::new("devnet");
+        let proposer = KeyPair::generate();
+        let tx = transfer("devnet", &KeyPair::generate());
+
+        let block = signed_block("devnet", &proposer, vec![tx.clone()]);
+        assert_eq!(
+            validator.validate(&gossip(NetworkMessage::Block(block.clone()))),
+            Validation::Accept
+        );
+        assert_eq!(
+            validator.validate(&gossip(NetworkMessage::Transaction(tx.clone()))),
+            Validation::Accept
+        );
+
+        let mut tampered = block.clone();
+        tampered.header.slot = 2;
+        assert!(matches!(
+            validator.validate(&gossip(NetworkMessage::Block(tampered))),
+            Validation::Reject(_)
+        ));
+
+        // Dropping a transaction breaks the root the proposer signed
+        let mut stripped = block.clone();
+        stripped.transactions.clear();
+        assert!(matches!(
+            validator.validate(&gossip(NetworkMessage::Block(stripped))),
+            Validation::Reject(_)
+        ));
+
+        let mut forged = tx;
+        if let TransactionData::Account(inner) = &mut forged.data {
+            inner.amount = 1_000;
+        }
+        assert!(matches!(
+            validator.validate(&gossip(NetworkMessage::Transaction(forged))),
+            Validation::Reject(_)
+        ));
+
+        let other_chain = GossipMessage {
+            chain_id: "mainnet".to_string(),
+            message: NetworkMessage::Block(block),
+        };
+        assert!(matches!(
+            validator.validate(&other_chain),
+            Validation::Reject(_)
+        ));
+        // Signed for another chain, whatever the envelope says
+        let foreign_tx = transfer("mainnet", &KeyPair::generate());
+        for message in [
+            NetworkMessage::Block(signed_block("mainnet", &proposer, vec![])),
+            NetworkMessage::Block(signed_block("devnet", &proposer, vec![foreign_tx.clone()])),
+            NetworkMessage::Transaction(foreign_tx),
+        ] {
+            assert!(matches!(
+                validator.validate(&gossip(message)),
+                Validation::Reject(_)
+            ));
+        }
+        let vote = |chain_id| {
+            Vote::new(chain_id, VoteKind::Prevote, 1, 0, "abc".into(), &proposer).unwrap()
+        };
+        assert_eq!(
+            validator.validate(&gossip(NetworkMessage::Vote(vote("devnet")))),
+            Validation::Accept
+        );
+        // The vote itself is signed for another chain
+        assert!(matches!(
+            validator.validate(&gossip(NetworkMessage::Vote(vote("mainnet")))),
+            Validation::Reject(_)
+        ));
+    }
+}
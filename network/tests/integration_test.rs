@@ -1,7 +1,9 @@
-use ledger::block::{Block, BlockHeader};
+use crypto::KeyPair;
+use ledger::block::Block;
 use libp2p::identity::Keypair;
 use libp2p::{Multiaddr, PeerId};
 use network::service::{NetworkCommand, NetworkEvent, NetworkService};
+use network::sync::{SyncRequest, SyncResponse};
 use std::time::Duration;
 use tokio::time::{sleep, timeout};
 
@@ -55,39 +57,50 @@ async fn test_p2p_gossip() {
     let connect_timeout = Duration::from_secs(5);
     let wait_for_connect = async {
         loop {
-            if let Some(event) = events1.recv().await {
-                if let NetworkEvent::PeerConnected(pid) = event {
-                    if pid == peer_id2 {
-                        println!("Node 1 connected to Node 2");
-                        break;
-                    }
+            if let Some(NetworkEvent::PeerConnected(pid)) = events1.recv().await {
+                if pid == peer_id2 {
+                    println!("Node 1 connected to Node 2");
+                    break;
                 }
             }
         }
     };
 
-    if let Err(_) = timeout(connect_timeout, wait_for_connect).await {
+    if timeout(connect_timeout, wait_for_connect).await.is_err() {
         panic!("Timed out waiting for connection");
     }
 
     // Give Gossipsub a moment to exchange subscriptions
     sleep(Duration::from_secs(2)).await;
 
-    // Node 1 broadcasts a block
-    let dummy_block = Block {
-        header: BlockHeader {
-            parent_hash: "000".to_string(),
-            timestamp: 123456,
-            slot: 1,
-            state_root: "root".to_string(),
-            transactions_root: "tx_root".to_string(),
-            validator_public_key: vec![],
-            signature: vec![],
-        },
-        transactions: vec![],
-    };
-
-    println!("Node 1 broadcasting block...");
+    // Node 1 broadcasts a forged block and then a properly signed one
+    let proposer = KeyPair::generate();
+    let mut forged = Block::new(
+        "mainnet",
+        "000".to_string(),
+        654321,
+        1,
+        "root".to_string(),
+        vec![],
+        proposer.public_key.clone(),
+    );
+    forged.header.signature = vec![0; 64];
+    let mut dummy_block = Block::new(
+        "mainnet",
+        "000".to_string(),
+        123456,
+        1,
+        "root".to_string(),
+        vec![],
+        proposer.public_key.clone(),
+    );
+    dummy_block.header.signature = proposer.sign(&dummy_block.header.signing_bytes()).unwrap();
+
+    println!("Node 1 broadcasting blocks...");
+    sender1
+        .send(NetworkCommand::BroadcastBlock(forged))
+        .await
+        .unwrap();
     sender1
         .send(NetworkCommand::BroadcastBlock(dummy_block.clone()))
         .await
@@ -98,17 +111,96 @@ async fn test_p2p_gossip() {
     let receive_timeout = Duration::from_secs(5);
     let wait_for_block = async {
         loop {
-            if let Some(event) = events2.recv().await {
-                if let NetworkEvent::BlockReceived(block) = event {
-                    assert_eq!(block.header.timestamp, 123456);
-                    println!("Node 2 received block!");
-                    break;
-                }
+            if let Some(NetworkEvent::BlockReceived { block, .. }) = events2.recv().await {
+                // The forged block is rejected, never delivered
+                assert_eq!(block.header.timestamp, 123456);
+                println!("Node 2 received block!");
+                break;
             }
         }
     };
 
-    if let Err(_) = timeout(receive_timeout, wait_for_block).await {
+    if timeout(receive_timeout, wait_for_block).await.is_err() {
         panic!("Timed out waiting for Node 2 to receive block");
     }
 }
+
+#[tokio::test]
+async fn test_sync_request_response() {
+    let key1 = Keypair::generate_ed25519();
+    let (node1, sender1, mut events1) = NetworkService::new(key1).await.unwrap();
+    tokio::spawn(node1.run());
+
+    let key2 = Keypair::generate_ed25519();
+    let peer_id2 = PeerId::from(key2.public());
+    let (node2, sender2, mut events2) = NetworkService::new(key2).await.unwrap();
+    tokio::spawn(node2.run());
+
+    let addr2 = loop {
+        if let Some(NetworkEvent::NewListenAddr(addr)) = events2.recv().await {
+            break addr;
+        }
+    };
+    sender1
+        .send(NetworkCommand::Dial(peer_id2, addr2))
+        .await
+        .unwrap();
+    let connect = async {
+        loop {
+            if let Some(NetworkEvent::PeerConnected(pid)) = events1.recv().await {
+                if pid == peer_id2 {
+                    break;
+                }
+            }
+        }
+    };
+    timeout(Duration::from_secs(5), connect)
+        .await
+        .expect("Timed out waiting for connection");
+    sender1
+        .send(NetworkCommand::RequestSync(peer_id2, SyncRequest::Status))
+        .await
+        .unwrap();
+
+    // Node 2 answers the status request
+    let serve = async {
+        loop {
+            if let Some(NetworkEvent::SyncRequested {
+                request, channel, ..
+            }) = events2.recv().await
+            {
+                assert_eq!(request, SyncRequest::Status);
+                let response = SyncResponse::Status {
+                    head_height: 7,
+                    head_hash: "head".to_string(),
+                };
+                sender2
+                    .send(NetworkCommand::RespondSync(channel, response))
+                    .await
+                    .unwrap();
+                break;
+            }
+        }
+    };
+    timeout(Duration::from_secs(5), serve)
+        .await
+        .expect("Timed out waiting for sync request");
+
+    let receive = async {
+        loop {
+            if let Some(NetworkEvent::SyncResponseReceived { peer, response }) =
+                events1.recv().await
+            {
+                assert_eq!(peer, peer_id2);
+                assert!(matches!(
+                    response,
+                    SyncResponse::Status { head_height: 7, .. }
+                ));
+                break;
+            }
+        }
+    };
+    timeout(Duration::from_secs(5), receive)
+        .await
+        .expect("Timed out waiting for sync response");
+}
@@ -0,0 +1,254 @@
+use consensus::finality::Checkpoint;
+use security::liveness::{LivenessTracker, Uptime};
+use serde::Serialize;
+use std::sync::{Arc, RwLock};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tracing::debug;
+
+/// Longest request head we bother reading.
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+/// One validator's uptime as served by the API.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UptimeReport {
+    /// Hex-encoded public key.
+    pub validator: String,
+    pub proposals_expected: u64,
+    pub proposals_made: u64,
+    pub votes_expected: u64,
+    pub votes_cast: u64,
+    pub ratio: f64,
+}
+
+impl UptimeReport {
+    fn new(validator: &[u8], uptime: Uptime) -> Self {
+        Self {
+            validator: hex::encode(validator),
+            proposals_expected: uptime.proposals_expected,
+            proposals_made: uptime.proposals_made,
+            votes_expected: uptime.votes_expected,
+            votes_cast: uptime.votes_cast,
+            ratio: uptime.ratio(),
+        }
+    }
+}
+
+/// The latest finalized checkpoint as served by the API.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FinalityReport {
+    pub slot: u64,
+    pub block_hash: String,
+    /// Validators whose precommits finalized it.
+    pub signers: usize,
+}
+
+impl FinalityReport {
+    fn new(checkpoint: &Checkpoint) -> Self {
+        Self {
+            slot: checkpoint.slot,
+            block_hash: checkpoint.block_hash.clone(),
+            signers: checkpoint.certificate.votes.len(),
+        }
+    }
+}
+
+/// Cheap-to-clone read handle onto node state for API consumers. The chain
+/// writes through `liveness()`; everything else only reads. `serve` exposes
+/// it over HTTP:
+///
+/// - `GET /uptime` lists every tracked validator
+/// - `GET /uptime/<hex public key>` returns one, or 404 if untracked
+/// - `GET /finality` returns the latest finalized checkpoint, or 404 before
+///   the first one
+#[derive(Clone)]
+pub struct NodeApi {
+    liveness: Arc<RwLock<LivenessTracker>>,
+    finalized: watch::Receiver<Option<Checkpoint>>,
+}
+
+impl NodeApi {
+    /// `finalized` comes from `FinalityGadget::subscribe`.
+    pub fn new(liveness: LivenessTracker, finalized: watch::Receiver<Option<Checkpoint>>) -> Self {
+        Self {
+            liveness: Arc::new(RwLock::new(liveness)),
+            finalized,
+        }
+    }
+
+    pub fn liveness(&self) -> &Arc<RwLock<LivenessTracker>> {
+        &self.liveness
+    }
+
+    pub fn validator_uptime(&self, validator: &[u8]) -> Option<Uptime> {
+        self.liveness.read().unwrap().uptime(validator)
+    }
+
+    pub fn uptimes(&self) -> Vec<(Vec<u8>, Uptime)> {
+        self.liveness.read().unwrap().uptimes()
+    }
+
+    pub fn latest_finalized(&self) -> Option<Checkpoint> {
+        self.finalized.borrow().clone()
+    }
+
+    /// Answers HTTP requests until the listener fails.
+    pub async fn serve(self, listener: TcpListener) {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let api = self.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = api.handle(stream).await {
+                            debug!("API connection failed: {}", e);
+                        }
+                    });
+                }
+                Err(e) => debug!("API accept failed: {}", e),
+            }
+        }
+    }
+
+    async fn handle(&self, mut stream: TcpStream) -> std::io::Result<()> {
+        let mut request = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_REQUEST_BYTES {
+            let n = stream.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            request.extend_from_slice(&buf[..n]);
+        }
+        let request = String::from_utf8_lossy(&request);
+        let mut parts = request.split_whitespace();
+        let (status, body) = match (parts.next(), parts.next()) {
+            (Some("GET"), Some(path)) => self.get(path),
+            _ => (405, "\"method not allowed\"".to_string()),
+        };
+        let reason = match status {
+            200 => "OK",
+            404 => "Not Found",
+            _ => "Method Not Allowed",
+        };
+        let response = format!(
+            "HTTP/1.1 {status} {reason}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await
+    }
+
+    /// Routes a GET to its JSON status and body.
+    fn get(&self, path: &str) -> (u16, String) {
+        let not_found = || (404, "\"not found\"".to_string());
+        match path.trim_end_matches('/') {
+            "/uptime" => {
+                let reports: Vec<UptimeReport> = self
+                    .uptimes()
+                    .iter()
+                    .map(|(validator, uptime)| UptimeReport::new(validator, *uptime))
+                    .collect();
+                (200, serde_json::to_string(&reports).unwrap())
+            }
+            "/finality" => match self.latest_finalized() {
+                Some(checkpoint) => (
+                    200,
+                    serde_json::to_string(&FinalityReport::new(&checkpoint)).unwrap(),
+                ),
+                None => not_found(),
+            },
+            path => {
+                let Some(validator) = path
+                    .strip_prefix("/uptime/")
+                    .and_then(|key| hex::decode(key).ok())
+                else {
+                    return not_found();
+                };
+                match self.validator_uptime(&validator) {
+                    Some(uptime) => (
+                        200,
+                        serde_json::to_string(&UptimeReport::new(&validator, uptime)).unwrap(),
+                    ),
+                    None => not_found(),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use consensus::finality::{QuorumCertificate, VoteKind};
+
+    #[test]
+    fn test_uptime_visible_through_clones() {
+        let api = NodeApi::new(LivenessTracker::new(10, 5), watch::channel(None).1);
+        let reader = api.clone();
+
+        let mut tracker = api.liveness().write().unwrap();
+        tracker.record_proposal(&[1], 0, true);
+        tracker.record_proposal(&[1], 1, false);
+        drop(tracker);
+
+        let uptime = reader.validator_uptime(&[1]).unwrap();
+        assert_eq!(uptime.proposals_expected, 2);
+        assert_eq!(uptime.proposals_made, 1);
+        assert_eq!(reader.uptimes().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_serves_uptime_over_http() {
+        let (finalized, receiver) = watch::channel(None);
+        let api = NodeApi::new(LivenessTracker::new(10, 5), receiver);
+        api.liveness()
+            .write()
+            .unwrap()
+            .record_proposal(&[0xab], 0, false);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(api.serve(listener));
+
+        let get = |path: &'static str| async move {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream
+                .write_all(format!("GET {path} HTTP/1.1\r\nHost: node\r\n\r\n").as_bytes())
+                .await
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        };
+
+        let response = get("/uptime/ab").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        let body = response.split("\r\n\r\n").nth(1).unwrap();
+        let report: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(report["validator"], "ab");
+        assert_eq!(report["proposals_expected"], 1);
+        assert_eq!(report["ratio"], 0.0);
+
+        assert!(get("/uptime").await.contains("\"validator\":\"ab\""));
+        assert!(get("/uptime/cd").await.starts_with("HTTP/1.1 404"));
+        assert!(get("/blocks").await.starts_with("HTTP/1.1 404"));
+
+        assert!(get("/finality").await.starts_with("HTTP/1.1 404"));
+        let certificate = QuorumCertificate {
+            chain_id: "devnet".to_string(),
+            kind: VoteKind::Precommit,
+            slot: 7,
+            round: 0,
+            block_hash: "abc".to_string(),
+            votes: Vec::new(),
+        };
+        finalized.send_replace(Some(Checkpoint {
+            slot: 7,
+            block_hash: "abc".to_string(),
+            certificate,
+        }));
+        let response = get("/finality").await;
+        assert!(response.contains("\"slot\":7"));
+        assert!(response.contains("\"block_hash\":\"abc\""));
+    }
+}
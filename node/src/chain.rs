@@ -0,0 +1,1089 @@
+use crate::api::NodeApi;
+use crate::mempool::Mempool;
+use crate::producer::{BlockBuilder, BlockLimits, ChainHead};
+use crate::spec::NodeEngine;
+use crate::state::{ExecutionError, WorldState};
+use crate::store::BlockStore;
+use consensus::clock::SlotClock;
+use consensus::engine::{ConsensusEngine, ConsensusError};
+use consensus::finality::{BlockTree, FinalityGadget, QuorumCertificate};
+use consensus::randao::{verify_randao, RandaoBeacon};
+use consensus::staking::{StakeManager, StakingError};
+use crypto::KeyPair;
+use ledger::block::Block;
+use ledger::transaction::{StakingTransaction, TransactionData};
+use ledger::vote::{Vote, VoteKind};
+use network::service::NetworkCommand;
+use network::sync::{SyncRequest, SyncResponse};
+use security::evidence::EvidencePool;
+use security::liveness::LivenessTracker;
+use security::slashing::{Misbehavior, SlashOutcome, SlashingManager};
+use storage::db::{StateStore, StorageError};
+use thiserror::Error;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+#[derive(Error, Debug)]
+pub enum ImportError {
+    #[error("Block is already imported")]
+    Known,
+    #[error("Block or transaction is for chain {0}")]
+    WrongChain(String),
+    #[error("Parent {0} is unknown")]
+    UnknownParent(String),
+    #[error("Parent {0} is not the current head")]
+    NotOnHead(String),
+    #[error("Slot {slot} is not after the parent's slot {parent}")]
+    SlotNotAfterParent { slot: u64, parent: u64 },
+    #[error("Block transactions take {0} bytes, over the size limit")]
+    TooLarge(usize),
+    #[error("Evidence root does not match the attached evidence")]
+    EvidenceRoot,
+    #[error("Transactions root does not match the attached transactions")]
+    TransactionsRoot,
+    #[error("Consensus error: {0}")]
+    Consensus(#[from] ConsensusError),
+    #[error("Execution error: {0}")]
+    Execution(#[from] ExecutionError),
+    #[error("Staking error: {0}")]
+    Staking(#[from] StakingError),
+    #[error("Storage error: {0}")]
+    Storage(#[from] StorageError),
+}
+
+impl ImportError {
+    /// Whether the block itself is bad, as opposed to unknown, stale or a
+    /// local storage failure.
+    pub fn is_invalid_block(&self) -> bool {
+        matches!(
+            self,
+            ImportError::WrongChain(_)
+                | ImportError::SlotNotAfterParent { .. }
+                | ImportError::TooLarge(_)
+                | ImportError::EvidenceRoot
+                | ImportError::TransactionsRoot
+                | ImportError::Consensus(_)
+                | ImportError::Execution(_)
+                | ImportError::Staking(_)
+        )
+    }
+}
+
+/// The node's view of the canonical chain: stored blocks plus everything
+/// derived from them (account state, RANDAO, stake after slashing) and the
+/// finality votes on top.
+pub struct Chain<S> {
+    pub engine: NodeEngine,
+    /// Chain staking transactions have to be signed for.
+    chain_id: String,
+    pub state: WorldState,
+    pub mempool: Mempool,
+    pub evidence_pool: EvidencePool,
+    /// Gas and size limits, the same ones the block builder fills up to.
+    limits: BlockLimits,
+    clock: SlotClock,
+    slasher: SlashingManager,
+    beacon: RandaoBeacon,
+    finality: FinalityGadget,
+    /// Slot of the last head we prevoted for.
+    voted_slot: Option<u64>,
+    api: NodeApi,
+    blocks: BlockStore<S>,
+    head: ChainHead,
+    head_height: u64,
+    head_slot: Option<u64>,
+}
+
+impl<S: StateStore> Chain<S> {
+    /// Replays the stored canonical chain on top of the `genesis` state to
+    /// rebuild account state.
+    pub fn new(
+        engine: NodeEngine,
+        chain_id: impl Into<String>,
+        clock: SlotClock,
+        slasher: SlashingManager,
+        genesis: WorldState,
+        blocks: BlockStore<S>,
+    ) -> Result<Self, ImportError> {
+        let chain_id = chain_id.into();
+        let finality = FinalityGadget::new(chain_id.clone());
+        let api = NodeApi::new(LivenessTracker::default(), finality.subscribe());
+        let mempool = Mempool::new(chain_id.clone(), genesis.network());
+        let mut chain = Self {
+            engine,
+            chain_id,
+            mempool,
+            state: genesis,
+            evidence_pool: EvidencePool::new(),
+            limits: BlockLimits::default(),
+            clock,
+            slasher,
+            beacon: RandaoBeacon::new(),
+            finality,
+            voted_slot: None,
+            api,
+            blocks,
+            head: ChainHead::genesis(),
+            head_height: 0,
+            head_slot: None,
+        };
+
+        let (stored_height, _) = chain.blocks.head()?;
+        for block in chain.blocks.blocks_by_range(1, stored_height)? {
+            let epoch = chain.clock.epoch_of(block.header.slot);
+            chain.enter_epoch(epoch)?;
+            // Stake was stored at the start of its epoch. Earlier blocks are
+            // already in it; staking, slashes and jails from blocks since are
+            // applied again, in the same order as on import.
+            let since_stored = chain
+                .engine
+                .stake_manager()
+                .is_some_and(|stake| stake.current_epoch() == epoch);
+            let stake = if since_stored {
+                chain.execute_staking(&block, epoch)?
+            } else {
+                None
+            };
+            chain.state.set_epoch(epoch);
+            chain.state.apply_block(&block, chain.limits.max_gas)?;
+            chain.commit_stake(stake);
+            if since_stored {
+                chain.slash_included(&block);
+            } else {
+                chain.evidence_pool.mark_included(&block.evidence);
+            }
+            chain.record_liveness(&block, since_stored);
+            chain.advance_head(&block, chain.head_height + 1);
+        }
+        if chain.head_height > 0 {
+            info!("Restored chain at height {}", chain.head_height);
+        }
+        Ok(chain)
+    }
+
+    pub fn clock(&self) -> &SlotClock {
+        &self.clock
+    }
+
+    /// Slashes `validator` for `misbehavior`. Always `None` without stake.
+    pub fn punish(
+        &mut self,
+        validator: &[u8],
+        misbehavior: Misbehavior,
+        infraction_epoch: u64,
+    ) -> Option<SlashOutcome> {
+        let stake = self.engine.stake_manager_mut()?;
+        self.slasher
+            .apply(stake, validator, misbehavior, infraction_epoch)
+    }
+
+    /// Read handle for API consumers; liveness is kept up to date by imports.
+    pub fn api(&self) -> &NodeApi {
+        &self.api
+    }
+
+    pub fn beacon_mut(&mut self) -> &mut RandaoBeacon {
+        &mut self.beacon
+    }
+
+    pub fn head(&self) -> &ChainHead {
+        &self.head
+    }
+
+    pub fn head_height(&self) -> u64 {
+        self.head_height
+    }
+
+    /// Slot of the latest imported block, `None` before the first one.
+    pub fn head_slot(&self) -> Option<u64> {
+        self.head_slot
+    }
+
+    pub fn contains(&self, hash: &str) -> Result<bool, StorageError> {
+        self.blocks.contains(hash)
+    }
+
+    /// Verifies `block` on top of the head, executes it and makes it the new
+    /// head. Returns its height.
+    pub fn import(&mut self, block: &Block) -> Result<u64, ImportError> {
+        let hash = block.hash();
+        if self.blocks.contains(&hash)? {
+            return Err(ImportError::Known);
+        }
+        if block.header.chain_id != self.chain_id {
+            return Err(ImportError::WrongChain(block.header.chain_id.clone()));
+        }
+        let parent = &block.header.parent_hash;
+        if !self.blocks.contains(parent)? {
+            return Err(ImportError::UnknownParent(parent.clone()));
+        }
+        if *parent != self.head.hash {
+            return Err(ImportError::NotOnHead(parent.clone()));
+        }
+        if let Some(parent) = self.head_slot.filter(|parent| block.header.slot <= *parent) {
+            return Err(ImportError::SlotNotAfterParent {
+                slot: block.header.slot,
+                parent,
+            });
+        }
+        let size = BlockLimits::size_of(&block.transactions);
+        if size > self.limits.max_bytes {
+            return Err(ImportError::TooLarge(size));
+        }
+        if !block.verify_transactions_root() {
+            return Err(ImportError::TransactionsRoot);
+        }
+        // Staking transactions are checked against the chain when executed
+        for tx in &block.transactions {
+            if let TransactionData::Account(tx) = &tx.data {
+                if tx.chain_id != self.chain_id {
+                    return Err(ImportError::WrongChain(tx.chain_id.clone()));
+                }
+            }
+        }
+        if !block.verify_evidence_root() {
+            return Err(ImportError::EvidenceRoot);
+        }
+
+        let epoch = self.clock.epoch_of(block.header.slot);
+        self.clock.verify_block_time(&block.header)?;
+        // The block is checked against its own epoch's validator set
+        self.enter_epoch(epoch)?;
+        self.engine.verify_block_header(&block.header)?;
+        verify_randao(&block.header, &self.head.randao_mix, epoch)?;
+        let stake = self.execute_staking(block, epoch)?;
+        // Execution depends on the block's epoch (rent), never on our clock
+        self.state.set_epoch(epoch);
+        self.state.apply_block(block, self.limits.max_gas)?;
+        self.commit_stake(stake);
+
+        self.mempool.prune(&self.state);
+        self.on_block(block)
+    }
+
+    /// Builds and broadcasts a block if the local validator leads `slot`.
+    pub async fn propose(
+        &mut self,
+        builder: &BlockBuilder,
+        slot: u64,
+        commands: &mpsc::Sender<NetworkCommand>,
+    ) -> Option<Block> {
+        let epoch = self.clock.epoch_of(slot);
+        // The block would open the new epoch, so its proposer is picked from
+        // the new set. Without a block the transition is undone: only blocks
+        // move the chain into an epoch, never our clock.
+        let before = self.advance_epoch(epoch);
+        self.state.set_epoch(epoch);
+        let (staking, stake) = self.ready_staking(epoch);
+        let proposed = builder
+            .propose_if_leader(
+                &self.chain_id,
+                &self.engine,
+                slot,
+                self.clock.slot_start(slot),
+                &self.head,
+                &mut self.state,
+                &mut self.mempool,
+                staking,
+                &self.evidence_pool,
+                commands,
+            )
+            .await;
+        let Some(block) = proposed else {
+            if let (Some(before), Some(stake)) = (before, self.engine.stake_manager_mut()) {
+                *stake = before;
+            }
+            return None;
+        };
+        if before.is_some() {
+            if let Err(e) = self.commit_epoch(epoch) {
+                warn!("Failed to store stake for epoch {}: {}", epoch, e);
+            }
+        }
+        self.commit_stake(stake);
+        if let Err(e) = self.on_block(&block) {
+            warn!("Failed to store proposed block: {}", e);
+        }
+        Some(block)
+    }
+
+    pub fn finality(&self) -> &FinalityGadget {
+        &self.finality
+    }
+
+    /// Prevotes for a new head with `key`, if the key has voting power.
+    /// Returns the votes to gossip; a precommit follows right away if our
+    /// prevote completed the quorum.
+    pub fn vote_for_head(&mut self, key: &KeyPair) -> Vec<Vote> {
+        let mut votes = Vec::new();
+        let Some(slot) = self.head_slot.filter(|slot| self.voted_slot < Some(*slot)) else {
+            return votes;
+        };
+        self.voted_slot = Some(slot);
+        let hash = self.head.hash.clone();
+        self.cast_vote(key, VoteKind::Prevote, slot, hash, &mut votes);
+        votes
+    }
+
+    /// Counts a vote from the network. Once a block on our chain has a
+    /// prevote certificate, `key` precommits to it; those votes are returned
+    /// for gossip.
+    pub fn on_vote(&mut self, vote: Vote, key: &KeyPair) -> Result<Vec<Vote>, ConsensusError> {
+        let mut votes = Vec::new();
+        if let Some(certificate) = self.count_vote(vote)? {
+            self.precommit(certificate, key, &mut votes);
+        }
+        Ok(votes)
+    }
+
+    fn count_vote(&mut self, vote: Vote) -> Result<Option<QuorumCertificate>, ConsensusError> {
+        let validators = self.engine.validator_set();
+        let certificate = self.finality.add_vote(vote, &validators, &self.blocks)?;
+        if let Some(finalized) = certificate
+            .as_ref()
+            .filter(|c| c.kind == VoteKind::Precommit)
+        {
+            info!(
+                "Finalized block {} at slot {}",
+                finalized.block_hash, finalized.slot
+            );
+        }
+        Ok(certificate)
+    }
+
+    fn cast_vote(
+        &mut self,
+        key: &KeyPair,
+        kind: VoteKind,
+        slot: u64,
+        block_hash: String,
+        votes: &mut Vec<Vote>,
+    ) {
+        if !self.engine.validator_set().contains(&key.public_key) {
+            return;
+        }
+        let vote = match Vote::new(self.chain_id.clone(), kind, slot, 0, block_hash, key) {
+            Ok(vote) => vote,
+            Err(e) => {
+                warn!("Failed to sign {:?}: {}", kind, e);
+                return;
+            }
+        };
+        votes.push(vote.clone());
+        match self.count_vote(vote) {
+            Ok(Some(certificate)) => self.precommit(certificate, key, votes),
+            Ok(None) => {}
+            Err(e) => warn!("Own {:?} for slot {} not counted: {}", kind, slot, e),
+        }
+    }
+
+    /// Precommits to the block of a prevote certificate if it is on our chain.
+    fn precommit(&mut self, certificate: QuorumCertificate, key: &KeyPair, votes: &mut Vec<Vote>) {
+        let hash = certificate.block_hash;
+        if certificate.kind == VoteKind::Prevote && self.blocks.descends_from(&hash, &hash) {
+            self.cast_vote(key, VoteKind::Precommit, certificate.slot, hash, votes);
+        }
+    }
+
+    /// Answers a peer's sync request from the block store.
+    pub fn answer(&self, request: &SyncRequest) -> Result<SyncResponse, StorageError> {
+        Ok(match request {
+            SyncRequest::Status => SyncResponse::Status {
+                head_height: self.head_height,
+                head_hash: self.head.hash.clone(),
+            },
+            SyncRequest::BlocksByRange {
+                start_height,
+                count,
+            } => SyncResponse::Blocks(self.blocks.blocks_by_range(*start_height, *count)?),
+            SyncRequest::BlocksByHash(hashes) => {
+                SyncResponse::Blocks(self.blocks.blocks_by_hash(hashes)?)
+            }
+        })
+    }
+
+    /// Runs the stake epoch transition (queued deposits and exits, validator
+    /// set rotation) when the chain reaches a block from a later epoch, and
+    /// stores the new stake with its boundary snapshot.
+    fn enter_epoch(&mut self, epoch: u64) -> Result<(), StorageError> {
+        if self.advance_epoch(epoch).is_some() {
+            self.commit_epoch(epoch)?;
+        }
+        Ok(())
+    }
+
+    /// The epoch transition without storing it. Returns the stake from
+    /// before, or `None` if `epoch` isn't later than the stake's.
+    fn advance_epoch(&mut self, epoch: u64) -> Option<StakeManager> {
+        let stake = self.engine.stake_manager_mut()?;
+        if epoch <= stake.current_epoch() {
+            return None;
+        }
+        let before = stake.clone();
+        stake.process_epoch(epoch);
+        Some(before)
+    }
+
+    /// Stores the stake after a transition the chain has made.
+    fn commit_epoch(&self, epoch: u64) -> Result<(), StorageError> {
+        let Some(stake) = self.engine.stake_manager() else {
+            return Ok(());
+        };
+        let active = stake.validator_set().entries().len();
+        info!("Entered epoch {} with {} active validators", epoch, active);
+        stake.persist(self.blocks.store())
+    }
+
+    /// Applies the block's staking transactions to a copy of the stake, so a
+    /// block with a bad one leaves the real stake untouched. `None` if the
+    /// block has none.
+    fn execute_staking(
+        &self,
+        block: &Block,
+        epoch: u64,
+    ) -> Result<Option<StakeManager>, ImportError> {
+        let mut staking = block.transactions.iter().filter_map(|tx| match &tx.data {
+            TransactionData::Staking(tx) => Some(tx),
+            _ => None,
+        });
+        let Some(first) = staking.next() else {
+            return Ok(None);
+        };
+        let mut stake = self
+            .engine
+            .stake_manager()
+            .ok_or(StakingError::UnknownValidator)?
+            .clone();
+        for tx in std::iter::once(first).chain(staking) {
+            stake.apply_staking_transaction(tx, &self.chain_id, epoch)?;
+        }
+        Ok(Some(stake))
+    }
+
+    /// The pooled staking transactions that apply in order, and the stake
+    /// they leave behind.
+    fn ready_staking(&self, epoch: u64) -> (Vec<StakingTransaction>, Option<StakeManager>) {
+        let pending = self.mempool.staking();
+        let Some(stake) = self.engine.stake_manager().filter(|_| !pending.is_empty()) else {
+            return (Vec::new(), None);
+        };
+        let mut stake = stake.clone();
+        let mut ready = Vec::new();
+        for tx in pending {
+            match stake.apply_staking_transaction(tx, &self.chain_id, epoch) {
+                Ok(()) => ready.push(tx.clone()),
+                Err(e) => debug!("Skipping staking transaction: {}", e),
+            }
+        }
+        (ready, Some(stake))
+    }
+
+    fn commit_stake(&mut self, next: Option<StakeManager>) {
+        if let (Some(next), Some(stake)) = (next, self.engine.stake_manager_mut()) {
+            *stake = next;
+        }
+        if let Some(stake) = self.engine.stake_manager() {
+            self.mempool.prune_staking(stake);
+        }
+    }
+
+    fn on_block(&mut self, block: &Block) -> Result<u64, ImportError> {
+        let height = self.blocks.insert(block)?;
+        self.slash_included(block);
+        self.record_liveness(block, true);
+        self.advance_head(block, height);
+        Ok(height)
+    }
+
+    fn advance_head(&mut self, block: &Block, height: u64) {
+        self.beacon
+            .on_block(&block.header, self.clock.epoch_of(block.header.slot));
+        self.head = ChainHead::of(&block.header);
+        self.head_height = height;
+        self.head_slot = Some(block.header.slot);
+    }
+
+    /// Records which proposers delivered between the head and `block`, and
+    /// with `punish` jails those that missed too many slots. Only the chain
+    /// itself feeds this, so every node that imports it reaches the same
+    /// verdict, however late it saw the blocks.
+    fn record_liveness(&mut self, block: &Block, punish: bool) {
+        let slot = block.header.slot;
+        let window = self.api.liveness().read().unwrap().window();
+        // Empty slots since the head, or none before the first block
+        let first = self
+            .head_slot
+            .map_or(slot, |head| (head + 1).max(slot.saturating_sub(window)));
+        for expected in first..=slot {
+            let Some(proposer) = self.engine.proposer_for_slot(expected) else {
+                continue;
+            };
+            let proposed = expected == slot && proposer == block.header.validator_public_key;
+            let misbehavior = self
+                .api
+                .liveness()
+                .write()
+                .unwrap()
+                .record_proposal(&proposer, expected, proposed);
+            let Some(misbehavior) = misbehavior.filter(|_| punish) else {
+                continue;
+            };
+            if let Some(outcome) =
+                self.punish(&proposer, misbehavior, self.clock.epoch_of(expected))
+            {
+                info!(
+                    "Jailed offline validator until epoch {:?} ({} burned)",
+                    outcome.jailed_until, outcome.burned
+                );
+            }
+        }
+    }
+
+    /// Slashes every offender proven by evidence in an accepted block.
+    fn slash_included(&mut self, block: &Block) {
+        self.evidence_pool.mark_included(&block.evidence);
+        let Some(stake) = self.engine.stake_manager_mut() else {
+            return;
+        };
+        for evidence in &block.evidence {
+            let epoch = self.clock.epoch_of(evidence.slot());
+            if let Some(outcome) = self.slasher.apply_evidence(stake, evidence, epoch) {
+                info!(
+                    "Slashed double signer from slot {} ({} burned)",
+                    evidence.slot(),
+                    outcome.burned
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mempool::MempoolError;
+    use crate::spec::ChainSpec;
+    use consensus::poa::PoaEngine;
+    use consensus::pos::PosEngine;
+    use consensus::staking::StakeManager;
+    use crypto::address::Network;
+    use crypto::{KeyPair, SignatureScheme};
+    use ledger::evidence::{DoubleSignEvidence, Evidence};
+    use ledger::transaction::{AccountTransaction, StakingAction};
+    use std::time::Duration;
+    use storage::db::SledStore;
+    use tempfile::tempdir;
+
+    fn chain(store: SledStore, authority: &KeyPair) -> Chain<SledStore> {
+        chain_of(store, vec![authority.public_key.clone()])
+    }
+
+    fn chain_of(store: SledStore, authorities: Vec<Vec<u8>>) -> Chain<SledStore> {
+        Chain::new(
+            NodeEngine::Poa(PoaEngine::new(authorities)),
+            "devnet",
+            SlotClock::new(0, Duration::from_secs(2), 32),
+            SlashingManager::new(5, 1),
+            WorldState::new(Network::Devnet),
+            BlockStore::new(store),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_imports_and_restores() {
+        let authority = KeyPair::generate();
+        let builder = BlockBuilder::new(authority.clone());
+        let (commands, mut receiver) = mpsc::channel(8);
+        let producer_dir = tempdir().unwrap();
+        let follower_dir = tempdir().unwrap();
+
+        let mut producer = chain(SledStore::new(producer_dir.path()).unwrap(), &authority);
+        let first = producer.propose(&builder, 1, &commands).await.unwrap();
+        let second = producer.propose(&builder, 2, &commands).await.unwrap();
+        receiver.close();
+
+        let follower_store = SledStore::new(follower_dir.path()).unwrap();
+        let mut follower = chain(follower_store.clone(), &authority);
+        // The same block signed for another chain
+        let mut foreign = first.clone();
+        foreign.header.chain_id = "mainnet".to_string();
+        foreign.header.signature = authority.sign(&foreign.header.signing_bytes()).unwrap();
+        let err = follower.import(&foreign).unwrap_err();
+        assert!(matches!(err, ImportError::WrongChain(_)));
+        assert!(err.is_invalid_block());
+        assert!(matches!(
+            follower.import(&second),
+            Err(ImportError::UnknownParent(_))
+        ));
+        assert_eq!(follower.import(&first).unwrap(), 1);
+        assert_eq!(follower.import(&second).unwrap(), 2);
+        assert!(matches!(follower.import(&second), Err(ImportError::Known)));
+        assert_eq!(
+            follower.answer(&SyncRequest::Status).unwrap(),
+            SyncResponse::Status {
+                head_height: 2,
+                head_hash: second.hash()
+            }
+        );
+        drop(follower);
+
+        // Restarting on the same store replays the stored chain. Reopening the
+        // path could race sled releasing its file lock.
+        let restored = chain(follower_store, &authority);
+        assert_eq!(restored.head_height(), 2);
+        assert_eq!(restored.head(), &ChainHead::of(&second.header));
+        assert_eq!(restored.state.state_root(), producer.state.state_root());
+    }
+
+    #[tokio::test]
+    async fn test_dev_spec_produces_blocks() {
+        let validator = KeyPair::generate();
+        let spec = ChainSpec::dev(&validator.public_key);
+        let builder =
+            BlockBuilder::new(validator.clone()).with_slots_per_epoch(spec.slots_per_epoch);
+        let (commands, mut receiver) = mpsc::channel(8);
+        let producer_dir = tempdir().unwrap();
+        let follower_dir = tempdir().unwrap();
+        let dev_chain = |dir: &std::path::Path| {
+            Chain::new(
+                spec.build_engine(None).unwrap(),
+                spec.name.clone(),
+                SlotClock::new(
+                    spec.genesis_time,
+                    spec.slot_duration(),
+                    spec.slots_per_epoch,
+                ),
+                SlashingManager::new(5, 1),
+                WorldState::genesis(Network::Devnet, &spec.allocations).unwrap(),
+                BlockStore::new(SledStore::new(dir).unwrap()),
+            )
+            .unwrap()
+        };
+
+        let mut producer = dev_chain(producer_dir.path());
+        let block = producer.propose(&builder, 1, &commands).await.unwrap();
+        receiver.close();
+        let mut follower = dev_chain(follower_dir.path());
+        assert_eq!(follower.import(&block).unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_import_uses_block_epoch() {
+        let authority = KeyPair::generate();
+        let alice = KeyPair::generate();
+        let alice_address = SignatureScheme::Ed25519
+            .derive_address(&alice.public_key, Network::Devnet)
+            .unwrap();
+        let builder = BlockBuilder::new(authority.clone());
+        let (commands, mut receiver) = mpsc::channel(8);
+        let producer_dir = tempdir().unwrap();
+        let follower_dir = tempdir().unwrap();
+
+        let mut producer = chain(SledStore::new(producer_dir.path()).unwrap(), &authority);
+        let mut follower = chain(SledStore::new(follower_dir.path()).unwrap(), &authority);
+        producer.state.credit(alice_address, 1_000_000);
+        follower.state.credit(alice_address, 1_000_000);
+
+        // A transfer to a new account stamps it with the block's epoch
+        let mut tx = AccountTransaction {
+            chain_id: "devnet".to_string(),
+            nonce: 0,
+            to: Some(
+                SignatureScheme::Ed25519
+                    .derive_address(&KeyPair::generate().public_key, Network::Devnet)
+                    .unwrap(),
+            ),
+            amount: 100,
+            data: vec![],
+            gas_limit: 21_000,
+            gas_price: 1,
+            signature: vec![],
+            sender_public_key: alice.public_key.clone(),
+            signature_scheme: SignatureScheme::Ed25519,
+        };
+        tx.signature = alice.sign(&tx.signing_bytes().unwrap()).unwrap();
+        let mut foreign = tx.clone();
+        foreign.chain_id = "mainnet".to_string();
+        foreign.signature = alice.sign(&foreign.signing_bytes().unwrap()).unwrap();
+        assert_eq!(
+            producer.mempool.insert(foreign),
+            Err(MempoolError::WrongChain("mainnet".to_string()))
+        );
+        producer.mempool.insert(tx).unwrap();
+        let block = producer.propose(&builder, 70, &commands).await.unwrap();
+        receiver.close();
+        assert_eq!(block.transactions.len(), 1);
+
+        // A sync peer can't swap the body under the signed header
+        let mut stripped = block.clone();
+        stripped.transactions.clear();
+        let err = follower.import(&stripped).unwrap_err();
+        assert!(matches!(err, ImportError::TransactionsRoot));
+        assert!(err.is_invalid_block());
+
+        // A syncing follower whose clock is far past the block's epoch
+        follower.state.set_epoch(9);
+        assert_eq!(follower.import(&block).unwrap(), 1);
+        assert_eq!(follower.state.state_root(), producer.state.state_root());
+    }
+
+    #[tokio::test]
+    async fn test_import_checks_slot_order_and_limits() {
+        let authority = KeyPair::generate();
+        let alice = KeyPair::generate();
+        let alice_address = SignatureScheme::Ed25519
+            .derive_address(&alice.public_key, Network::Devnet)
+            .unwrap();
+        // The producer ignores the limits; importers don't
+        let builder = BlockBuilder::new(authority.clone()).with_limits(BlockLimits {
+            max_gas: u64::MAX,
+            max_bytes: usize::MAX,
+        });
+        let (commands, mut receiver) = mpsc::channel(8);
+        receiver.close();
+        let dirs: Vec<_> = (0..6).map(|_| tempdir().unwrap()).collect();
+        let mut chains = dirs.iter().map(|dir| {
+            let mut chain = chain(SledStore::new(dir.path()).unwrap(), &authority);
+            chain.state.credit(alice_address, 1_000_000_000);
+            chain
+        });
+
+        // Two blocks in one slot
+        let (mut producer, mut follower) = (chains.next().unwrap(), chains.next().unwrap());
+        let first = producer.propose(&builder, 2, &commands).await.unwrap();
+        let second = producer.propose(&builder, 2, &commands).await.unwrap();
+        follower.import(&first).unwrap();
+        let err = follower.import(&second).unwrap_err();
+        assert!(matches!(
+            err,
+            ImportError::SlotNotAfterParent { slot: 2, parent: 2 }
+        ));
+        assert!(err.is_invalid_block());
+
+        let transfer = |data: Vec<u8>, gas_limit: u64| {
+            let mut tx = AccountTransaction {
+                chain_id: "devnet".to_string(),
+                nonce: 0,
+                to: Some(alice_address),
+                amount: 1,
+                data,
+                gas_limit,
+                gas_price: 1,
+                signature: vec![],
+                sender_public_key: alice.public_key.clone(),
+                signature_scheme: SignatureScheme::Ed25519,
+            };
+            tx.signature = alice.sign(&tx.signing_bytes().unwrap()).unwrap();
+            tx
+        };
+
+        // A gas limit over the block's
+        let (mut producer, mut follower) = (chains.next().unwrap(), chains.next().unwrap());
+        let max_gas = BlockLimits::default().max_gas;
+        producer
+            .mempool
+            .insert(transfer(vec![], max_gas + 1))
+            .unwrap();
+        let greedy = producer.propose(&builder, 1, &commands).await.unwrap();
+        assert!(matches!(
+            follower.import(&greedy),
+            Err(ImportError::Execution(ExecutionError::BlockGasLimit))
+        ));
+
+        // More bytes than the block may carry
+        let (mut producer, mut follower) = (chains.next().unwrap(), chains.next().unwrap());
+        let data = vec![0; BlockLimits::default().max_bytes];
+        let gas = crate::state::intrinsic_gas(&transfer(data.clone(), 0));
+        producer.mempool.insert(transfer(data, gas)).unwrap();
+        let oversized = producer.propose(&builder, 1, &commands).await.unwrap();
+        assert_eq!(oversized.transactions.len(), 1);
+        assert!(matches!(
+            follower.import(&oversized),
+            Err(ImportError::TooLarge(_))
+        ));
+        assert_eq!(follower.head_height(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_liveness_follows_the_chain() {
+        let active = KeyPair::generate();
+        let offline = KeyPair::generate();
+        let authorities = vec![active.public_key.clone(), offline.public_key.clone()];
+        let builder = BlockBuilder::new(active.clone());
+        let (commands, mut receiver) = mpsc::channel(8);
+        let producer_dir = tempdir().unwrap();
+        let follower_dir = tempdir().unwrap();
+
+        let mut producer = chain_of(
+            SledStore::new(producer_dir.path()).unwrap(),
+            authorities.clone(),
+        );
+        let first = producer.propose(&builder, 2, &commands).await.unwrap();
+        let second = producer.propose(&builder, 6, &commands).await.unwrap();
+        receiver.close();
+
+        // Slots 3 and 5 belonged to the offline authority, 4 to the active one
+        let uptime = producer.api().validator_uptime(&active.public_key).unwrap();
+        assert_eq!((uptime.proposals_expected, uptime.proposals_made), (3, 2));
+        let uptime = producer
+            .api()
+            .validator_uptime(&offline.public_key)
+            .unwrap();
+        assert_eq!((uptime.proposals_expected, uptime.proposals_made), (2, 0));
+
+        // A follower importing much later reaches the same view
+        let mut follower = chain_of(SledStore::new(follower_dir.path()).unwrap(), authorities);
+        follower.import(&first).unwrap();
+        follower.import(&second).unwrap();
+        assert_eq!(follower.api().uptimes(), producer.api().uptimes());
+    }
+
+    #[tokio::test]
+    async fn test_votes_finalize_the_head() {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let dir = tempdir().unwrap();
+        let mut chain = chain_of(
+            SledStore::new(dir.path()).unwrap(),
+            vec![a.public_key.clone(), b.public_key.clone()],
+        );
+        let slot = (1..)
+            .find(|slot| chain.engine.proposer_for_slot(*slot) == Some(a.public_key.clone()))
+            .unwrap();
+        let (commands, mut receiver) = mpsc::channel(8);
+        let block = chain
+            .propose(&BlockBuilder::new(a.clone()), slot, &commands)
+            .await
+            .unwrap();
+        receiver.close();
+
+        // One of two authorities is no quorum yet
+        let prevotes = chain.vote_for_head(&a);
+        assert_eq!(prevotes.len(), 1);
+        assert!(chain.vote_for_head(&a).is_empty());
+
+        let vote = |kind, chain_id| Vote::new(chain_id, kind, slot, 0, block.hash(), &b).unwrap();
+        let precommits = chain
+            .on_vote(vote(VoteKind::Prevote, "devnet"), &a)
+            .unwrap();
+        assert_eq!(precommits.len(), 1);
+        assert_eq!(precommits[0].kind, VoteKind::Precommit);
+        assert!(chain.api().latest_finalized().is_none());
+
+        assert!(chain
+            .on_vote(vote(VoteKind::Precommit, "devnet"), &a)
+            .unwrap()
+            .is_empty());
+        let checkpoint = chain.api().latest_finalized().unwrap();
+        assert_eq!(
+            (checkpoint.slot, checkpoint.block_hash),
+            (slot, block.hash())
+        );
+
+        assert!(matches!(
+            chain.on_vote(vote(VoteKind::Prevote, "mainnet"), &a),
+            Err(ConsensusError::WrongChain(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_epoch_boundary_rotates_validators() {
+        let first = KeyPair::generate();
+        let joining = KeyPair::generate();
+        let mut stake = StakeManager::new();
+        stake.add_stake(first.public_key.clone(), 100);
+        stake.process_epoch(0);
+        // Queued now, active from epoch 1
+        stake.add_stake(joining.public_key.clone(), 100);
+
+        let dir = tempdir().unwrap();
+        let mut chain = Chain::new(
+            NodeEngine::Pos(Box::new(PosEngine::new(stake))),
+            "devnet",
+            SlotClock::new(0, Duration::from_secs(2), 32),
+            SlashingManager::new(5, 1),
+            WorldState::new(Network::Devnet),
+            BlockStore::new(SledStore::new(dir.path()).unwrap()),
+        )
+        .unwrap();
+        let (commands, mut receiver) = mpsc::channel(64);
+        let builder = BlockBuilder::new(first.clone());
+        assert!(chain.propose(&builder, 1, &commands).await.is_some());
+        assert_eq!(chain.engine.stake_manager_mut().unwrap().current_epoch(), 0);
+
+        // A tick into epoch 1 without a block of ours doesn't move the stake
+        let outsider = BlockBuilder::new(KeyPair::generate());
+        assert!(chain.propose(&outsider, 32, &commands).await.is_none());
+        assert_eq!(chain.engine.stake_manager().unwrap().current_epoch(), 0);
+        assert!(StakeManager::load(chain.blocks.store()).unwrap().is_none());
+
+        // Reaching epoch 1 brings the queued validator in
+        let mut proposed = None;
+        for slot in 32..64 {
+            proposed = chain.propose(&builder, slot, &commands).await;
+            if proposed.is_some() {
+                break;
+            }
+        }
+        receiver.close();
+        assert!(proposed.is_some());
+        let stake = chain.engine.stake_manager_mut().unwrap();
+        assert_eq!(stake.current_epoch(), 1);
+        assert_eq!(stake.validator_set().entries().len(), 2);
+        assert!(stake.get_voting_power(&joining.public_key) > 0);
+
+        // The boundary is on disk for the next start
+        let store = chain.blocks.store();
+        let stored = StakeManager::load(store).unwrap().unwrap();
+        assert_eq!(stored.current_epoch(), 1);
+        assert_eq!(
+            StakeManager::stake_at_epoch(store, &joining.public_key, 1).unwrap(),
+            100
+        );
+    }
+
+    #[tokio::test]
+    async fn test_staking_transactions_go_through_blocks() {
+        let proposer = KeyPair::generate();
+        let jailed = KeyPair::generate();
+        let mut stake = StakeManager::new();
+        stake.add_stake(proposer.public_key.clone(), 100);
+        stake.add_stake(jailed.public_key.clone(), 100);
+        stake.process_epoch(0);
+        stake.jail(&jailed.public_key, 0);
+        // Rebuild the set so the proposer leads every slot
+        stake.process_epoch(0);
+
+        let pos = |stake: &StakeManager, dir: &std::path::Path| {
+            Chain::new(
+                NodeEngine::Pos(Box::new(PosEngine::new(stake.clone()))),
+                "devnet",
+                SlotClock::new(0, Duration::from_secs(2), 32),
+                SlashingManager::new(5, 1),
+                WorldState::new(Network::Devnet),
+                BlockStore::new(SledStore::new(dir).unwrap()),
+            )
+            .unwrap()
+        };
+        let producer_dir = tempdir().unwrap();
+        let follower_dir = tempdir().unwrap();
+        let mut producer = pos(&stake, producer_dir.path());
+        let mut follower = pos(&stake, follower_dir.path());
+
+        let unjail = |chain_id: &str| {
+            let mut tx = StakingTransaction {
+                chain_id: chain_id.to_string(),
+                action: StakingAction::Unjail,
+                validator_public_key: jailed.public_key.clone(),
+                nonce: 0,
+                signature: vec![],
+            };
+            tx.signature = jailed.sign(&tx.signing_bytes().unwrap()).unwrap();
+            tx
+        };
+        // Signed for another chain, so never admitted
+        assert_eq!(
+            producer.mempool.insert_staking(unjail("mainnet")),
+            Err(MempoolError::WrongChain("mainnet".to_string()))
+        );
+        producer.mempool.insert_staking(unjail("devnet")).unwrap();
+
+        let (commands, mut receiver) = mpsc::channel(8);
+        let builder = BlockBuilder::new(proposer.clone());
+        let block = producer.propose(&builder, 1, &commands).await.unwrap();
+        receiver.close();
+        assert_eq!(
+            block.transactions[0].data,
+            TransactionData::Staking(unjail("devnet"))
+        );
+        assert_eq!(block.transactions.len(), 1);
+        assert!(producer.mempool.staking().is_empty());
+
+        follower.import(&block).unwrap();
+        for chain in [&producer, &follower] {
+            let stake = chain.engine.stake_manager().unwrap();
+            assert_eq!(stake.staking_nonce(&jailed.public_key), 1);
+            assert_eq!(
+                stake
+                    .get_validator(&jailed.public_key)
+                    .unwrap()
+                    .jailed_until,
+                None
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_restart_keeps_mid_epoch_slashes() {
+        let proposer = KeyPair::generate();
+        let offender = KeyPair::generate();
+        let mut stake = StakeManager::new();
+        stake.add_stake(proposer.public_key.clone(), 100);
+        stake.add_stake(offender.public_key.clone(), 100);
+        stake.process_epoch(0);
+        stake.jail(&offender.public_key, 0);
+        // Rebuild the set so the proposer leads every slot
+        stake.process_epoch(0);
+
+        let dir = tempdir().unwrap();
+        let store = SledStore::new(dir.path()).unwrap();
+        // What the node stored when it entered epoch 0
+        stake.persist(&store).unwrap();
+        let pos = |stake: StakeManager| {
+            Chain::new(
+                NodeEngine::Pos(Box::new(PosEngine::new(stake))),
+                "devnet",
+                SlotClock::new(0, Duration::from_secs(2), 32),
+                SlashingManager::new(5, 1),
+                WorldState::new(Network::Devnet),
+                BlockStore::new(store.clone()),
+            )
+            .unwrap()
+        };
+        let mut chain = pos(stake);
+
+        let header = |state_root: &str| {
+            let mut block = Block::new(
+                "devnet",
+                "0".to_string(),
+                0,
+                3,
+                state_root.to_string(),
+                vec![],
+                offender.public_key.clone(),
+            );
+            block.header.signature = offender.sign(&block.header.signing_bytes()).unwrap();
+            block.header
+        };
+        let evidence = DoubleSignEvidence::new(header("a"), header("b"));
+        assert!(chain
+            .evidence_pool
+            .add(Evidence::DoubleSign(Box::new(evidence))));
+        let (commands, mut receiver) = mpsc::channel(8);
+        let block = chain
+            .propose(&BlockBuilder::new(proposer.clone()), 5, &commands)
+            .await
+            .unwrap();
+        receiver.close();
+        assert_eq!(block.evidence.len(), 1);
+
+        let offender_of = |chain: &Chain<SledStore>| {
+            chain
+                .engine
+                .stake_manager()
+                .unwrap()
+                .get_validator(&offender.public_key)
+                .unwrap()
+                .clone()
+        };
+        let slashed = offender_of(&chain);
+        assert!(slashed.is_tombstoned);
+        assert!(slashed.stake < 100);
+        drop(chain);
+
+        // The slash came after the stored stake, so the restart replays it
+        let restored = pos(StakeManager::load(&store).unwrap().unwrap());
+        assert_eq!(restored.head_height(), 1);
+        assert_eq!(offender_of(&restored), slashed);
+    }
+}
@@ -0,0 +1,80 @@
+use crypto::KeyPair;
+use std::fs;
+use std::path::Path;
+use thiserror::Error;
+use tracing::info;
+
+/// File name of the validator signing key inside the data directory.
+pub const VALIDATOR_KEY_FILE: &str = "validator_key";
+
+#[derive(Error, Debug)]
+pub enum KeyError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid validator key in {0}")]
+    Invalid(String),
+}
+
+/// Reads a hex-encoded Ed25519 seed.
+pub fn load_validator_key(path: &Path) -> Result<KeyPair, KeyError> {
+    let invalid = || KeyError::Invalid(path.display().to_string());
+    let seed = hex::decode(fs::read_to_string(path)?.trim()).map_err(|_| invalid())?;
+    Ok(KeyPair::from_seed(seed.try_into().map_err(|_| invalid())?))
+}
+
+/// Loads the key at `path`, or generates one and saves it there so the node
+/// keeps signing as the same validator across restarts.
+pub fn load_or_generate_validator_key(path: &Path) -> Result<KeyPair, KeyError> {
+    if path.exists() {
+        return load_validator_key(path);
+    }
+    let key = KeyPair::generate();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    write_private(path, hex::encode(&key.private_key).as_bytes())?;
+    info!(
+        "Generated validator key {} at {}",
+        hex::encode(&key.public_key),
+        path.display()
+    );
+    Ok(key)
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(bytes)
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    fs::write(path, bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_key_survives_restart() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(VALIDATOR_KEY_FILE);
+        let key = load_or_generate_validator_key(&path).unwrap();
+        assert_eq!(load_or_generate_validator_key(&path).unwrap(), key);
+
+        fs::write(&path, "not hex").unwrap();
+        assert!(matches!(
+            load_validator_key(&path),
+            Err(KeyError::Invalid(_))
+        ));
+    }
+}
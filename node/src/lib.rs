@@ -0,0 +1,9 @@
+pub mod api;
+pub mod chain;
+pub mod keys;
+pub mod mempool;
+pub mod producer;
+pub mod spec;
+pub mod state;
+pub mod store;
+pub mod sync;
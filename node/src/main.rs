@@ -1,17 +1,55 @@
 use clap::Parser;
-use libp2p::identity;
-use network::service::NetworkService;
+use consensus::clock::SlotClock;
+use consensus::staking::StakeManager;
+use crypto::address::Network;
+use ledger::block::Block;
+use ledger::evidence::Evidence;
+use ledger::transaction::TransactionData;
+use ledger::vote::Vote;
+use libp2p::PeerId;
+use network::config::NetworkConfig;
+use network::keys;
+use network::reputation::Offense;
+use network::service::{NetworkCommand, NetworkEvent, NetworkService};
+use node::chain::{Chain, ImportError};
+use node::keys as validator_keys;
+use node::producer::BlockBuilder;
+use node::spec::ChainSpec;
+use node::state::WorldState;
+use node::store::BlockStore;
+use node::sync::{SyncManager, SyncRequests};
+use security::evidence::EquivocationDetector;
+use security::slashing::SlashingManager;
 use std::path::PathBuf;
-use storage::db::SledStore;
-use tracing::{error, info};
+use storage::db::{SledStore, StateStore};
+use tokio::sync::mpsc;
+use tokio_stream::StreamExt;
+use tracing::{debug, error, info};
+
+const DOUBLE_SIGN_PENALTY_PERCENT: u8 = 5;
+const DOWNTIME_PENALTY_PER_BLOCK: u64 = 1;
+/// How many sealed epoch mixes to keep around for readers.
+const RANDAO_HISTORY_EPOCHS: u64 = 256;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Port to listen on (P2P)
+    /// Port to listen on (P2P), on all IPv4 and IPv6 interfaces
     #[arg(short, long, default_value_t = 8000)]
     port: u16,
 
+    /// Listen multiaddr, overriding --port; may be repeated
+    #[arg(long = "listen-addr")]
+    listen_addrs: Vec<String>,
+
+    /// Public multiaddr to announce to peers (e.g. behind NAT); may be repeated
+    #[arg(long = "external-addr")]
+    external_addrs: Vec<String>,
+
+    /// Port for the local HTTP API (uptime queries), on 127.0.0.1
+    #[arg(long, default_value_t = 8001)]
+    api_port: u16,
+
     /// Path to store blockchain data
     #[arg(short, long, default_value = "./data")]
     db_path: PathBuf,
@@ -19,6 +57,32 @@ struct Args {
     /// Run in light mode (reduced memory usage)
     #[arg(long, default_value_t = false)]
     light: bool,
+
+    /// Network the node's addresses belong to (mainnet, testnet, devnet)
+    #[arg(long, default_value = "mainnet")]
+    network: Network,
+
+    /// Chain spec JSON (engine, genesis time, slot timing); defaults to a PoS
+    /// devnet with the local validator key as its only validator
+    #[arg(long)]
+    chain_spec: Option<PathBuf>,
+
+    /// Overrides the chain spec's genesis time, in Unix milliseconds
+    #[arg(long)]
+    genesis_time: Option<u64>,
+
+    /// Existing libp2p key file to use instead of <db-path>/node_key
+    #[arg(long)]
+    node_key: Option<PathBuf>,
+
+    /// Validator key file (hex Ed25519 seed) to use instead of
+    /// <db-path>/validator_key
+    #[arg(long)]
+    validator_key: Option<PathBuf>,
+
+    /// Extra bootnode multiaddr (ending in /p2p/<peer id>); may be repeated
+    #[arg(long = "bootnode")]
+    bootnodes: Vec<String>,
 }
 
 #[tokio::main]
@@ -40,15 +104,44 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         1024 * 1024 * 1024 // 1GB default
     };
 
-    let _storage = SledStore::new_with_cache(&args.db_path, cache_size).map_err(|e| {
+    let storage = SledStore::new_with_cache(&args.db_path, cache_size).map_err(|e| {
         error!("Failed to open database: {}", e);
         e
     })?;
     info!("Storage initialized");
 
+    let validator_key = match &args.validator_key {
+        Some(path) => validator_keys::load_validator_key(path)?,
+        None => validator_keys::load_or_generate_validator_key(
+            &args.db_path.join(validator_keys::VALIDATOR_KEY_FILE),
+        )?,
+    };
+    let mut spec = match &args.chain_spec {
+        Some(path) => ChainSpec::load(path)?,
+        None => ChainSpec::dev(&validator_key.public_key),
+    };
+    if let Some(genesis_time) = args.genesis_time {
+        spec.genesis_time = genesis_time;
+    }
+    let engine = spec.build_engine(StakeManager::load(&storage)?)?;
+    info!("Chain {} using {:?}", spec.name, spec.engine);
+
     // 2. Initialize Networking
-    let local_key = identity::Keypair::generate_ed25519();
-    let (service, _cmd_tx, _event_rx) = NetworkService::new(local_key).await?;
+    let local_key = match &args.node_key {
+        Some(path) => keys::load_keypair(path)?,
+        None => keys::load_or_generate_keypair(&args.db_path.join(keys::NODE_KEY_FILE))?,
+    };
+    let network_config = NetworkConfig::default()
+        .with_chain_id(spec.name.clone())
+        .with_genesis(spec.genesis_hash())
+        .with_gossip_version(spec.gossip_version, spec.gossip_transition)
+        .with_ban_file(args.db_path.join("banned_peers"))
+        .with_port(args.port)
+        .with_listen_addrs(&args.listen_addrs)?
+        .with_external_addrs(&args.external_addrs)?
+        .with_bootnodes(spec.bootnodes.iter().chain(&args.bootnodes))?;
+    let (service, cmd_tx, mut event_rx) =
+        NetworkService::with_config(local_key, network_config).await?;
 
     // Spawn Network Service
     tokio::spawn(async move {
@@ -56,10 +149,213 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     });
     info!("Network service started");
 
-    // 3. Keep Alive (Simulation of Consensus Loop)
-    // In real implementation, we'd start the Consensus Engine here.
+    // 3. Chain state, block production and sync
+    let clock = SlotClock::new(
+        spec.genesis_time,
+        spec.slot_duration(),
+        spec.slots_per_epoch,
+    );
+    let mut ticks = clock.ticks();
+    let slasher = SlashingManager::new(DOUBLE_SIGN_PENALTY_PERCENT, DOWNTIME_PENALTY_PER_BLOCK);
+    let mut chain = Chain::new(
+        engine,
+        spec.name.clone(),
+        clock,
+        slasher,
+        WorldState::genesis(args.network, &spec.allocations)?,
+        BlockStore::new(storage),
+    )?;
+    let voter = validator_key.clone();
+    let builder = BlockBuilder::new(validator_key).with_slots_per_epoch(spec.slots_per_epoch);
+    let mut detector = EquivocationDetector::new();
+    let mut sync = SyncManager::new(chain.head_height());
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", args.api_port)).await?;
+    info!("API listening on {}", listener.local_addr()?);
+    tokio::spawn(chain.api().clone().serve(listener));
+
     loop {
-        tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
-        info!("Node is running... (Heartbeat)");
+        tokio::select! {
+            Some(tick) = ticks.next() => {
+                if tick.new_epoch {
+                    for (validator, uptime) in chain.api().uptimes() {
+                        debug!(
+                            "Validator {:02x?} uptime {:.1}%",
+                            &validator[..validator.len().min(4)],
+                            uptime.ratio() * 100.0
+                        );
+                    }
+                    chain
+                        .beacon_mut()
+                        .prune_below(tick.epoch.saturating_sub(RANDAO_HISTORY_EPOCHS));
+                }
+                if let Some(block) = chain.propose(&builder, tick.slot, &cmd_tx).await {
+                    sync.on_imported(chain.head_height());
+                    debug!("Head is now {} at height {}", block.hash(), chain.head_height());
+                }
+            }
+            Some(event) = event_rx.recv() => match event {
+                NetworkEvent::TransactionReceived(tx) => {
+                    let inserted = match tx.data {
+                        TransactionData::Account(tx) => chain.mempool.insert(tx),
+                        TransactionData::Staking(tx) => chain.mempool.insert_staking(tx),
+                        TransactionData::Utxo(_) => continue,
+                    };
+                    if let Err(e) = inserted {
+                        debug!("Rejected transaction: {}", e);
+                    }
+                }
+                NetworkEvent::EvidenceReceived(evidence) => {
+                    let added = chain.evidence_pool.add(evidence);
+                    debug!("Received evidence (accepted: {})", added);
+                }
+                NetworkEvent::BlockReceived { peer, block } => {
+                    if let Some(evidence) = detector.observe_header(&block.header) {
+                        info!("Detected double sign at slot {}", block.header.slot);
+                        share_evidence(&mut chain, &cmd_tx, evidence).await;
+                    }
+                    let imported = import(&mut chain, &mut sync, *block, peer);
+                    report_peers(&cmd_tx, imported.orphaned, Offense::Orphan).await;
+                    send_sync_requests(&cmd_tx, imported.requests).await;
+                }
+                NetworkEvent::VoteReceived(vote) => {
+                    if let Some(evidence) = detector.observe_vote(&vote) {
+                        info!("Detected double vote at slot {}", vote.slot);
+                        share_evidence(&mut chain, &cmd_tx, evidence).await;
+                    }
+                    let finalized = chain.finality().latest_finalized().map(|c| c.slot);
+                    match chain.on_vote(vote, &voter) {
+                        Ok(votes) => send_votes(&cmd_tx, votes).await,
+                        Err(e) => debug!("Rejected vote: {}", e),
+                    }
+                    if let Some(slot) = chain
+                        .finality()
+                        .latest_finalized()
+                        .map(|c| c.slot)
+                        .filter(|slot| Some(*slot) != finalized)
+                    {
+                        detector.prune_below(slot);
+                    }
+                }
+                NetworkEvent::PeerConnected(peer) => {
+                    send_sync_requests(&cmd_tx, sync.add_peer(peer)).await;
+                }
+                NetworkEvent::PeerDisconnected(peer) => {
+                    send_sync_requests(&cmd_tx, sync.remove_peer(&peer)).await;
+                }
+                NetworkEvent::SyncRequested { peer, request, channel } => match chain.answer(&request) {
+                    Ok(response) => {
+                        let _ = cmd_tx.send(NetworkCommand::RespondSync(channel, response)).await;
+                    }
+                    Err(e) => error!("Failed to answer sync request from {peer}: {}", e),
+                },
+                NetworkEvent::SyncResponseReceived { peer, response } => {
+                    let (mut requests, ready) = sync.on_response(&peer, response);
+                    for block in ready {
+                        let imported = import(&mut chain, &mut sync, block, peer);
+                        report_peers(&cmd_tx, imported.orphaned, Offense::Orphan).await;
+                        requests.extend(imported.requests);
+                        if imported.invalid {
+                            let _ = cmd_tx.send(NetworkCommand::ReportPeer(peer, Offense::BadSyncResponse)).await;
+                        }
+                        if imported.failed {
+                            // The rest builds on the failed block; fetch it all again
+                            requests.extend(sync.on_import_failed(chain.head_height()));
+                            break;
+                        }
+                    }
+                    send_sync_requests(&cmd_tx, requests).await;
+                }
+                NetworkEvent::SyncFailed(peer) => {
+                    send_sync_requests(&cmd_tx, sync.on_failure(&peer)).await;
+                }
+                _ => {}
+            },
+        }
+        // Prevote once per new head, however it arrived
+        send_votes(&cmd_tx, chain.vote_for_head(&voter)).await;
+    }
+}
+
+/// What came of importing a block and the parked descendants it unlocked.
+#[derive(Default)]
+struct Imported {
+    requests: SyncRequests,
+    /// Some block didn't make it onto the chain.
+    failed: bool,
+    /// Some block was bad in itself.
+    invalid: bool,
+    /// Senders of parked blocks evicted before their parent showed up.
+    orphaned: Vec<PeerId>,
+}
+
+/// Imports `block` from `peer` and any parked descendants it unlocks. Blocks
+/// with an unknown parent are parked while sync fetches the missing ancestors.
+fn import<S: StateStore>(
+    chain: &mut Chain<S>,
+    sync: &mut SyncManager,
+    block: Block,
+    peer: PeerId,
+) -> Imported {
+    let mut imported = Imported::default();
+    let mut ready = vec![block];
+    while let Some(block) = ready.pop() {
+        match chain.import(&block) {
+            Ok(height) => {
+                sync.on_imported(height);
+                ready.extend(sync.take_children(&block.hash()));
+            }
+            Err(ImportError::UnknownParent(_)) => {
+                imported.failed = true;
+                let head_slot = chain.head_slot().unwrap_or(0);
+                let (requests, orphaned) = sync.fetch_ancestor(block, peer, head_slot);
+                imported.requests.extend(requests);
+                imported.orphaned.extend(orphaned);
+            }
+            Err(ImportError::Known) => {}
+            Err(e) => {
+                imported.failed = true;
+                imported.invalid |= e.is_invalid_block();
+                debug!("Rejected block {}: {}", block.hash(), e);
+            }
+        }
+    }
+    imported
+}
+
+async fn share_evidence<S: StateStore>(
+    chain: &mut Chain<S>,
+    commands: &mpsc::Sender<NetworkCommand>,
+    evidence: Evidence,
+) {
+    if chain.evidence_pool.add(evidence.clone()) {
+        let _ = commands
+            .send(NetworkCommand::BroadcastEvidence(evidence))
+            .await;
+    }
+}
+
+async fn report_peers(
+    commands: &mpsc::Sender<NetworkCommand>,
+    peers: Vec<PeerId>,
+    offense: Offense,
+) {
+    for peer in peers {
+        let _ = commands
+            .send(NetworkCommand::ReportPeer(peer, offense))
+            .await;
+    }
+}
+
+async fn send_votes(commands: &mpsc::Sender<NetworkCommand>, votes: Vec<Vote>) {
+    for vote in votes {
+        let _ = commands.send(NetworkCommand::BroadcastVote(vote)).await;
+    }
+}
+
+async fn send_sync_requests(commands: &mpsc::Sender<NetworkCommand>, requests: SyncRequests) {
+    for (peer, request) in requests {
+        let _ = commands
+            .send(NetworkCommand::RequestSync(peer, request))
+            .await;
     }
 }
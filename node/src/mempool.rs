@@ -0,0 +1,159 @@
+use crate::state::WorldState;
+use consensus::staking::StakeManager;
+use crypto::address::Network;
+use crypto::Blake3;
+use ledger::transaction::{AccountTransaction, StakingTransaction};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Default number of transactions kept before the cheapest are evicted.
+pub const DEFAULT_MEMPOOL_CAPACITY: usize = 10_000;
+/// Staking transactions pay no fee, so there is nothing to evict by.
+pub const MAX_STAKING_TRANSACTIONS: usize = 1_000;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum MempoolError {
+    #[error("Invalid transaction signature")]
+    InvalidSignature,
+    #[error("Transaction is for chain {0}")]
+    WrongChain(String),
+    #[error("Recipient is on another network")]
+    WrongNetwork,
+    #[error("Transaction already in the pool")]
+    Duplicate,
+    #[error("Pool is full and the fee is too low")]
+    FeeTooLow,
+    #[error("Pool is full")]
+    Full,
+}
+
+pub fn tx_hash(tx: &AccountTransaction) -> String {
+    Blake3::hash(&bincode::serialize(tx).unwrap())
+}
+
+/// Pending account transactions, handed to the block builder highest fee
+/// first, plus pending staking transactions.
+pub struct Mempool {
+    transactions: HashMap<String, AccountTransaction>,
+    staking: HashMap<String, StakingTransaction>,
+    capacity: usize,
+    chain_id: String,
+    network: Network,
+}
+
+impl Mempool {
+    pub fn new(chain_id: impl Into<String>, network: Network) -> Self {
+        Self::with_capacity(chain_id, network, DEFAULT_MEMPOOL_CAPACITY)
+    }
+
+    pub fn with_capacity(chain_id: impl Into<String>, network: Network, capacity: usize) -> Self {
+        Self {
+            transactions: HashMap::new(),
+            staking: HashMap::new(),
+            capacity: capacity.max(1),
+            chain_id: chain_id.into(),
+            network,
+        }
+    }
+
+    /// Adds a signed transaction. When full, the lowest-fee transaction is
+    /// evicted if the new one pays more.
+    pub fn insert(&mut self, tx: AccountTransaction) -> Result<String, MempoolError> {
+        if !tx.verify_signature().unwrap_or(false) {
+            return Err(MempoolError::InvalidSignature);
+        }
+        if tx.chain_id != self.chain_id {
+            return Err(MempoolError::WrongChain(tx.chain_id));
+        }
+        if tx.to.is_some_and(|to| to.network() != self.network) {
+            return Err(MempoolError::WrongNetwork);
+        }
+        let hash = tx_hash(&tx);
+        if self.transactions.contains_key(&hash) {
+            return Err(MempoolError::Duplicate);
+        }
+
+        if self.transactions.len() >= self.capacity {
+            let (cheapest, price) = self
+                .transactions
+                .iter()
+                .map(|(hash, tx)| (hash.clone(), tx.gas_price))
+                .min_by_key(|(_, price)| *price)
+                .unwrap();
+            if tx.gas_price <= price {
+                return Err(MempoolError::FeeTooLow);
+            }
+            self.transactions.remove(&cheapest);
+        }
+
+        self.transactions.insert(hash.clone(), tx);
+        Ok(hash)
+    }
+
+    /// Adds a signed staking transaction. Whether it applies is only known
+    /// against the stake at block time.
+    pub fn insert_staking(&mut self, tx: StakingTransaction) -> Result<String, MempoolError> {
+        if !tx.verify_signature().unwrap_or(false) {
+            return Err(MempoolError::InvalidSignature);
+        }
+        if tx.chain_id != self.chain_id {
+            return Err(MempoolError::WrongChain(tx.chain_id));
+        }
+        let hash = Blake3::hash(&bincode::serialize(&tx).unwrap());
+        if self.staking.contains_key(&hash) {
+            return Err(MempoolError::Duplicate);
+        }
+        if self.staking.len() >= MAX_STAKING_TRANSACTIONS {
+            return Err(MempoolError::Full);
+        }
+        self.staking.insert(hash.clone(), tx);
+        Ok(hash)
+    }
+
+    /// Pending staking transactions by validator, then nonce.
+    pub fn staking(&self) -> Vec<&StakingTransaction> {
+        let mut pending: Vec<&StakingTransaction> = self.staking.values().collect();
+        pending.sort_by(|a, b| {
+            a.validator_public_key
+                .cmp(&b.validator_public_key)
+                .then(a.nonce.cmp(&b.nonce))
+        });
+        pending
+    }
+
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+
+    /// Transactions ordered by gas price (highest first), then by nonce so a
+    /// sender's transactions come out in order. Ties fall back to the hash.
+    pub fn by_fee(&self) -> Vec<&AccountTransaction> {
+        let mut pending: Vec<(&String, &AccountTransaction)> = self.transactions.iter().collect();
+        pending.sort_by(|(ha, a), (hb, b)| {
+            b.gas_price
+                .cmp(&a.gas_price)
+                .then(a.nonce.cmp(&b.nonce))
+                .then(ha.cmp(hb))
+        });
+        pending.into_iter().map(|(_, tx)| tx).collect()
+    }
+
+    /// Drops staking transactions whose nonce has already been used.
+    pub fn prune_staking(&mut self, stake: &StakeManager) {
+        self.staking
+            .retain(|_, tx| tx.nonce >= stake.staking_nonce(&tx.validator_public_key));
+    }
+
+    /// Drops transactions whose nonce has already been used in `state`.
+    pub fn prune(&mut self, state: &WorldState) {
+        self.transactions.retain(|_, tx| {
+            state
+                .sender_of(tx)
+                .is_ok_and(|sender| tx.nonce >= state.nonce(&sender))
+        });
+    }
+}
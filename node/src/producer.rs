@@ -0,0 +1,429 @@
+use crate::mempool::Mempool;
+use crate::state::{ExecutionError, WorldState};
+use consensus::engine::ConsensusEngine;
+use crypto::address::Address;
+use crypto::{KeyPair, SignatureScheme};
+use ledger::block::{Block, BlockHeader};
+use ledger::evidence::Evidence;
+use ledger::randao::{self, RandaoMix};
+use ledger::transaction::{AccountTransaction, StakingTransaction, Transaction, TransactionData};
+use network::service::NetworkCommand;
+use security::evidence::{EvidencePool, MAX_EVIDENCE_PER_BLOCK};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+#[derive(Debug, Clone, Copy)]
+pub struct BlockLimits {
+    pub max_gas: u64,
+    /// Upper bound on the serialized size of the included transactions.
+    pub max_bytes: usize,
+}
+
+impl BlockLimits {
+    /// Serialized size of `transactions`, as counted against `max_bytes`.
+    pub fn size_of(transactions: &[Transaction]) -> usize {
+        transactions
+            .iter()
+            .map(|tx| bincode::serialized_size(tx).unwrap_or(u64::MAX) as usize)
+            .fold(0, usize::saturating_add)
+    }
+}
+
+impl Default for BlockLimits {
+    fn default() -> Self {
+        Self {
+            max_gas: 30_000_000,
+            max_bytes: 1024 * 1024,
+        }
+    }
+}
+
+/// The block a new block is built on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainHead {
+    pub hash: String,
+    pub randao_mix: RandaoMix,
+}
+
+impl ChainHead {
+    pub fn genesis() -> Self {
+        Self {
+            hash: "0".to_string(),
+            randao_mix: [0u8; 32],
+        }
+    }
+
+    pub fn of(header: &BlockHeader) -> Self {
+        Self {
+            hash: header.hash(),
+            randao_mix: header.randao_mix,
+        }
+    }
+}
+
+/// Assembles, executes and signs blocks with the local validator key.
+pub struct BlockBuilder {
+    keypair: KeyPair,
+    limits: BlockLimits,
+    slots_per_epoch: u64,
+}
+
+impl BlockBuilder {
+    pub fn new(keypair: KeyPair) -> Self {
+        Self {
+            keypair,
+            limits: BlockLimits::default(),
+            slots_per_epoch: 32,
+        }
+    }
+
+    pub fn with_limits(mut self, limits: BlockLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Epoch length used to pick which epoch the RANDAO reveal signs.
+    pub fn with_slots_per_epoch(mut self, slots_per_epoch: u64) -> Self {
+        self.slots_per_epoch = slots_per_epoch.max(1);
+        self
+    }
+
+    pub fn public_key(&self) -> &[u8] {
+        &self.keypair.public_key
+    }
+
+    fn fee_recipient(&self, state: &WorldState) -> Address {
+        SignatureScheme::Ed25519
+            .derive_address(&self.keypair.public_key, state.network())
+            .expect("validator key is a valid Ed25519 key")
+    }
+
+    /// Fills a block from `mempool` (highest fee first) until the gas or size
+    /// limit is hit, applying each transaction to `state`. Transactions that
+    /// fail to execute are left out; ones waiting on an earlier nonce from the
+    /// same sender get another chance once that nonce has been included.
+    /// `staking` transactions go first; the caller has already checked they
+    /// apply to the stake. `evidence` is attached as-is and committed to in
+    /// the header, and the proposer's RANDAO reveal is mixed into the
+    /// parent's mix. The header is signed for `chain_id`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn build(
+        &self,
+        chain_id: &str,
+        parent: &ChainHead,
+        slot: u64,
+        timestamp: u64,
+        state: &mut WorldState,
+        mempool: &Mempool,
+        staking: Vec<StakingTransaction>,
+        evidence: Vec<Evidence>,
+    ) -> Block {
+        let fee_recipient = self.fee_recipient(state);
+        let mut candidates: Vec<&AccountTransaction> = mempool.by_fee();
+        let mut included: Vec<Transaction> = staking
+            .into_iter()
+            .map(|tx| Transaction {
+                data: TransactionData::Staking(tx),
+            })
+            .collect();
+        let mut gas_used: u64 = 0;
+        let mut bytes = BlockLimits::size_of(&included);
+
+        loop {
+            let mut progressed = false;
+            let mut deferred = Vec::new();
+            for tx in candidates {
+                let candidate = Transaction {
+                    data: TransactionData::Account(tx.clone()),
+                };
+                let size = BlockLimits::size_of(std::slice::from_ref(&candidate));
+                // Gas limits are attacker-chosen, so don't let them overflow
+                if gas_used.saturating_add(tx.gas_limit) > self.limits.max_gas
+                    || bytes.saturating_add(size) > self.limits.max_bytes
+                {
+                    continue;
+                }
+                match state.apply_transaction(tx, &fee_recipient) {
+                    Ok(gas) => {
+                        gas_used += gas;
+                        bytes += size;
+                        included.push(candidate);
+                        progressed = true;
+                    }
+                    Err(ExecutionError::InvalidNonce { expected, actual }) if actual > expected => {
+                        deferred.push(tx);
+                    }
+                    Err(e) => debug!("Skipping transaction: {}", e),
+                }
+            }
+            if !progressed || deferred.is_empty() {
+                break;
+            }
+            candidates = deferred;
+        }
+
+        let reveal = randao::reveal(&self.keypair, slot / self.slots_per_epoch)
+            .expect("validator key can sign");
+        let mut block = Block::new(
+            chain_id,
+            parent.hash.clone(),
+            timestamp,
+            slot,
+            state.state_root(),
+            included,
+            self.keypair.public_key.clone(),
+        )
+        .with_evidence(evidence)
+        .with_randao(reveal, &parent.randao_mix);
+        block.header.signature = self
+            .keypair
+            .sign(&block.header.signing_bytes())
+            .expect("validator key can sign");
+        block
+    }
+
+    /// Builds and broadcasts a block if this node is the proposer for `slot`.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn propose_if_leader(
+        &self,
+        chain_id: &str,
+        engine: &dyn ConsensusEngine,
+        slot: u64,
+        timestamp: u64,
+        parent: &ChainHead,
+        state: &mut WorldState,
+        mempool: &mut Mempool,
+        staking: Vec<StakingTransaction>,
+        evidence: &EvidencePool,
+        commands: &mpsc::Sender<NetworkCommand>,
+    ) -> Option<Block> {
+        if engine.proposer_for_slot(slot).as_deref() != Some(self.public_key()) {
+            return None;
+        }
+
+        let block = self.build(
+            chain_id,
+            parent,
+            slot,
+            timestamp,
+            state,
+            mempool,
+            staking,
+            evidence.for_block(MAX_EVIDENCE_PER_BLOCK),
+        );
+        mempool.prune(state);
+        info!(
+            "Proposed block {} at slot {} with {} transactions",
+            block.hash(),
+            slot,
+            block.transactions.len()
+        );
+        if commands
+            .send(NetworkCommand::BroadcastBlock(block.clone()))
+            .await
+            .is_err()
+        {
+            warn!(
+                "Network service stopped; block {} not broadcast",
+                block.hash()
+            );
+        }
+        Some(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mempool::MempoolError;
+    use consensus::pos::PosEngine;
+    use consensus::staking::StakeManager;
+    use crypto::address::Network;
+
+    fn transfer(sender: &KeyPair, to: Address, nonce: u64, gas_price: u64) -> AccountTransaction {
+        let mut tx = AccountTransaction {
+            chain_id: "devnet".to_string(),
+            nonce,
+            to: Some(to),
+            amount: 100,
+            data: vec![],
+            gas_limit: 21_000,
+            gas_price,
+            signature: vec![],
+            sender_public_key: sender.public_key.clone(),
+            signature_scheme: SignatureScheme::Ed25519,
+        };
+        tx.signature = sender.sign(&tx.signing_bytes().unwrap()).unwrap();
+        tx
+    }
+
+    fn address(key: &KeyPair) -> Address {
+        SignatureScheme::Ed25519
+            .derive_address(&key.public_key, Network::Devnet)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_builds_highest_fee_first_within_gas_limit() {
+        let alice = KeyPair::generate();
+        let bob = KeyPair::generate();
+        let carol = address(&KeyPair::generate());
+        let mut state = WorldState::new(Network::Devnet);
+        state.credit(address(&alice), 1_000_000);
+        state.credit(address(&bob), 1_000_000);
+
+        let mut mempool = Mempool::new("devnet", Network::Devnet);
+        // Alice's second transaction pays more but must wait for her first
+        mempool.insert(transfer(&alice, carol, 1, 5)).unwrap();
+        mempool.insert(transfer(&alice, carol, 0, 1)).unwrap();
+        mempool.insert(transfer(&bob, carol, 0, 3)).unwrap();
+        // Unfunded sender is skipped
+        mempool
+            .insert(transfer(&KeyPair::generate(), carol, 0, 9))
+            .unwrap();
+
+        let builder = BlockBuilder::new(KeyPair::generate()).with_limits(BlockLimits {
+            max_gas: 63_000,
+            max_bytes: 1024 * 1024,
+        });
+        let mut follower = state.clone();
+        let block = builder.build(
+            "devnet",
+            &ChainHead::genesis(),
+            1,
+            0,
+            &mut state,
+            &mempool,
+            vec![],
+            vec![],
+        );
+
+        let senders: Vec<(Vec<u8>, u64)> = block
+            .transactions
+            .iter()
+            .map(|tx| match &tx.data {
+                TransactionData::Account(tx) => (tx.sender_public_key.clone(), tx.nonce),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(
+            senders,
+            vec![
+                (bob.public_key.clone(), 0),
+                (alice.public_key.clone(), 0),
+                (alice.public_key.clone(), 1),
+            ]
+        );
+        assert_eq!(state.account(&carol).unwrap().balance, 300);
+        assert_eq!(block.header.state_root, state.state_root());
+        follower.apply_block(&block, 63_000).unwrap();
+        assert_eq!(follower.state_root(), state.state_root());
+
+        mempool.prune(&state);
+        assert_eq!(mempool.len(), 1);
+    }
+
+    #[test]
+    fn test_skips_oversized_gas_limit() {
+        let alice = KeyPair::generate();
+        let bob = KeyPair::generate();
+        let carol = address(&KeyPair::generate());
+        let mut state = WorldState::new(Network::Devnet);
+        state.credit(address(&alice), 1_000_000);
+        state.credit(address(&bob), 1_000_000);
+
+        // Bob's transfer goes first, so the huge limit is added to used gas
+        let mut greedy = transfer(&alice, carol, 0, 1);
+        greedy.gas_limit = u64::MAX;
+        greedy.signature = alice.sign(&greedy.signing_bytes().unwrap()).unwrap();
+        let mut mempool = Mempool::new("devnet", Network::Devnet);
+        mempool.insert(transfer(&bob, carol, 0, 5)).unwrap();
+        mempool.insert(greedy).unwrap();
+
+        let builder = BlockBuilder::new(KeyPair::generate());
+        let block = builder.build(
+            "devnet",
+            &ChainHead::genesis(),
+            1,
+            0,
+            &mut state,
+            &mempool,
+            vec![],
+            vec![],
+        );
+        assert_eq!(block.transactions.len(), 1);
+    }
+
+    #[test]
+    fn test_rejects_recipients_on_another_network() {
+        let alice = KeyPair::generate();
+        let mainnet = SignatureScheme::Ed25519
+            .derive_address(&KeyPair::generate().public_key, Network::Mainnet)
+            .unwrap();
+        let mut state = WorldState::new(Network::Devnet);
+        state.credit(address(&alice), 1_000_000);
+        let tx = transfer(&alice, mainnet, 0, 1);
+
+        let mut mempool = Mempool::new("devnet", Network::Devnet);
+        assert_eq!(mempool.insert(tx.clone()), Err(MempoolError::WrongNetwork));
+        let root = state.state_root();
+        assert_eq!(
+            state.apply_transaction(&tx, &address(&KeyPair::generate())),
+            Err(ExecutionError::WrongNetwork(mainnet))
+        );
+        assert_eq!(state.state_root(), root);
+    }
+
+    #[tokio::test]
+    async fn test_leader_signs_and_broadcasts() {
+        let validator = KeyPair::generate();
+        let mut stake = StakeManager::new();
+        stake.add_stake(validator.public_key.clone(), 100);
+        stake.process_epoch(1);
+        let engine = PosEngine::new(stake);
+
+        let builder = BlockBuilder::new(validator);
+        let mut state = WorldState::new(Network::Devnet);
+        let mut mempool = Mempool::new("devnet", Network::Devnet);
+        let (commands, mut receiver) = mpsc::channel(1);
+
+        let block = builder
+            .propose_if_leader(
+                "devnet",
+                &engine,
+                4,
+                0,
+                &ChainHead::genesis(),
+                &mut state,
+                &mut mempool,
+                vec![],
+                &EvidencePool::new(),
+                &commands,
+            )
+            .await
+            .unwrap();
+        assert!(engine.verify_block_header(&block.header).is_ok());
+        assert!(consensus::randao::verify_randao(&block.header, &[0u8; 32], 0).is_ok());
+        match receiver.recv().await {
+            Some(NetworkCommand::BroadcastBlock(sent)) => assert_eq!(sent, block),
+            _ => panic!("expected a broadcast"),
+        }
+
+        let follower = BlockBuilder::new(KeyPair::generate());
+        assert!(follower
+            .propose_if_leader(
+                "devnet",
+                &engine,
+                5,
+                0,
+                &ChainHead::genesis(),
+                &mut state,
+                &mut mempool,
+                vec![],
+                &EvidencePool::new(),
+                &commands,
+            )
+            .await
+            .is_none());
+    }
+}
@@ -0,0 +1,382 @@
+use consensus::engine::{ConsensusEngine, ConsensusError};
+use consensus::poa::PoaEngine;
+use consensus::pos::PosEngine;
+use consensus::staking::{StakeManager, DEFAULT_CHURN_LIMIT};
+use consensus::validator::{ValidatorSet, ValidatorSetEntry};
+use crypto::address::Address;
+use crypto::Blake3;
+use ledger::block::BlockHeader;
+use network::topics::{TopicTransition, GOSSIP_VERSION};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SpecError {
+    #[error("Failed to read chain spec: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid chain spec: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("Invalid authority key: {0}")]
+    InvalidAuthority(String),
+    #[error("Proof-of-authority chain spec lists no authorities")]
+    NoAuthorities,
+    #[error("Invalid genesis validator key: {0}")]
+    InvalidValidator(String),
+    #[error("Proof-of-stake chain spec lists no genesis validators")]
+    NoValidators,
+}
+
+/// Stake a dev chain's only validator starts with.
+pub const DEV_VALIDATOR_STAKE: u64 = 1_000_000;
+
+/// Which consensus engine the chain runs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EngineSpec {
+    /// Validators bonded at genesis; later ones join through staking.
+    ProofOfStake {
+        #[serde(default)]
+        validators: Vec<GenesisValidator>,
+    },
+    /// Hex-encoded authority public keys, in proposing order.
+    ProofOfAuthority { authorities: Vec<String> },
+}
+
+/// A validator active from epoch 0.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenesisValidator {
+    /// Hex-encoded public key.
+    pub public_key: String,
+    pub stake: u64,
+}
+
+/// Balance credited to an address at genesis.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenesisAllocation {
+    pub address: Address,
+    pub amount: u64,
+}
+
+/// Chain parameters every node on a network has to agree on, read from a
+/// JSON file at startup.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainSpec {
+    pub name: String,
+    /// Unix time in milliseconds at which slot 0 starts.
+    pub genesis_time: u64,
+    pub slot_duration_ms: u64,
+    pub slots_per_epoch: u64,
+    pub engine: EngineSpec,
+    /// Starting balances.
+    #[serde(default)]
+    pub allocations: Vec<GenesisAllocation>,
+    /// Multiaddrs (ending in `/p2p/<peer id>`) new nodes dial to join.
+    #[serde(default)]
+    pub bootnodes: Vec<String>,
+    /// Version in gossip topic names; bumped by network upgrades.
+    #[serde(default = "default_gossip_version")]
+    pub gossip_version: u32,
+    /// Keeps listening on the previous version's topics until `until`.
+    #[serde(default)]
+    pub gossip_transition: Option<TopicTransition>,
+}
+
+fn default_gossip_version() -> u32 {
+    GOSSIP_VERSION
+}
+
+impl Default for ChainSpec {
+    fn default() -> Self {
+        Self {
+            name: "mainnet".to_string(),
+            genesis_time: 0,
+            slot_duration_ms: 2_000,
+            slots_per_epoch: 32,
+            engine: EngineSpec::ProofOfStake {
+                validators: Vec::new(),
+            },
+            allocations: Vec::new(),
+            bootnodes: Vec::new(),
+            gossip_version: GOSSIP_VERSION,
+            gossip_transition: None,
+        }
+    }
+}
+
+impl ChainSpec {
+    /// Reads and validates a spec file.
+    pub fn load(path: &Path) -> Result<Self, SpecError> {
+        let json = std::fs::read_to_string(path)?;
+        let spec: Self = serde_json::from_str(&json)?;
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    /// A chain of its own with `public_key` as the only validator, for
+    /// running a node without a spec file.
+    pub fn dev(public_key: &[u8]) -> Self {
+        Self {
+            name: "devnet".to_string(),
+            engine: EngineSpec::ProofOfStake {
+                validators: vec![GenesisValidator {
+                    public_key: hex::encode(public_key),
+                    stake: DEV_VALIDATOR_STAKE,
+                }],
+            },
+            ..Self::default()
+        }
+    }
+
+    /// Checks that the engine has someone to propose blocks.
+    pub fn validate(&self) -> Result<(), SpecError> {
+        self.build_engine(None).map(|_| ())
+    }
+
+    /// Identifies the chain: a hash over everything that fixes its genesis.
+    /// Networking-only settings like bootnodes don't count.
+    pub fn genesis_hash(&self) -> String {
+        let genesis = (
+            &self.name,
+            self.genesis_time,
+            self.slot_duration_ms,
+            self.slots_per_epoch,
+            &self.engine,
+            &self.allocations,
+        );
+        Blake3::hash(&bincode::serialize(&genesis).unwrap_or_default())
+    }
+
+    pub fn slot_duration(&self) -> Duration {
+        Duration::from_millis(self.slot_duration_ms)
+    }
+
+    /// Builds the configured engine. Proof of stake starts from `stored`
+    /// stake if there is any, and from the genesis validators otherwise.
+    pub fn build_engine(&self, stored: Option<StakeManager>) -> Result<NodeEngine, SpecError> {
+        match &self.engine {
+            EngineSpec::ProofOfStake { validators } => {
+                let genesis = genesis_stake(validators)?;
+                Ok(NodeEngine::Pos(Box::new(PosEngine::new(
+                    stored.unwrap_or(genesis),
+                ))))
+            }
+            EngineSpec::ProofOfAuthority { authorities } => {
+                let keys = authorities
+                    .iter()
+                    .map(|key| {
+                        hex::decode(key).map_err(|_| SpecError::InvalidAuthority(key.clone()))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                if keys.is_empty() {
+                    return Err(SpecError::NoAuthorities);
+                }
+                Ok(NodeEngine::Poa(PoaEngine::new(keys)))
+            }
+        }
+    }
+}
+
+/// Bonds the genesis validators and activates them all in epoch 0.
+fn genesis_stake(validators: &[GenesisValidator]) -> Result<StakeManager, SpecError> {
+    if validators.is_empty() {
+        return Err(SpecError::NoValidators);
+    }
+    let mut stake = StakeManager::new().with_churn_limit(validators.len());
+    for validator in validators {
+        let key = hex::decode(&validator.public_key)
+            .ok()
+            .filter(|_| validator.stake > 0)
+            .ok_or_else(|| SpecError::InvalidValidator(validator.public_key.clone()))?;
+        stake.add_stake(key, validator.stake);
+    }
+    stake.process_epoch(0);
+    Ok(stake.with_churn_limit(DEFAULT_CHURN_LIMIT))
+}
+
+/// The engine picked by the chain spec.
+pub enum NodeEngine {
+    Pos(Box<PosEngine>),
+    Poa(PoaEngine),
+}
+
+impl NodeEngine {
+    pub fn stake_manager(&self) -> Option<&StakeManager> {
+        match self {
+            NodeEngine::Pos(engine) => Some(engine.stake_manager()),
+            NodeEngine::Poa(_) => None,
+        }
+    }
+
+    /// Who votes on finality, and with how much power. Authorities all
+    /// count the same.
+    pub fn validator_set(&self) -> ValidatorSet {
+        match self {
+            NodeEngine::Pos(engine) => engine.stake_manager().validator_set().clone(),
+            NodeEngine::Poa(engine) => ValidatorSet::new(
+                0,
+                engine
+                    .authorities()
+                    .iter()
+                    .map(|key| ValidatorSetEntry {
+                        public_key: key.clone(),
+                        stake: 0,
+                        voting_power: 1,
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Stake state, if the engine has any. Slashing is a no-op without it.
+    pub fn stake_manager_mut(&mut self) -> Option<&mut StakeManager> {
+        match self {
+            NodeEngine::Pos(engine) => Some(engine.stake_manager_mut()),
+            NodeEngine::Poa(_) => None,
+        }
+    }
+}
+
+impl ConsensusEngine for NodeEngine {
+    fn verify_block_header(&self, header: &BlockHeader) -> Result<(), ConsensusError> {
+        match self {
+            NodeEngine::Pos(engine) => engine.verify_block_header(header),
+            NodeEngine::Poa(engine) => engine.verify_block_header(header),
+        }
+    }
+
+    fn proposer_for_slot(&self, slot: u64) -> Option<Vec<u8>> {
+        match self {
+            NodeEngine::Pos(engine) => engine.proposer_for_slot(slot),
+            NodeEngine::Poa(engine) => engine.proposer_for_slot(slot),
+        }
+    }
+
+    fn choose_fork<'a>(&self, forks: &'a [BlockHeader]) -> Option<&'a BlockHeader> {
+        match self {
+            NodeEngine::Pos(engine) => engine.choose_fork(forks),
+            NodeEngine::Poa(engine) => engine.choose_fork(forks),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{ExecutionError, WorldState};
+    use crypto::address::Network;
+
+    #[test]
+    fn test_poa_spec_builds_poa_engine() {
+        let spec: ChainSpec = serde_json::from_str(
+            r#"{
+                "name": "devnet",
+                "genesis_time": 1000,
+                "slot_duration_ms": 500,
+                "slots_per_epoch": 8,
+                "engine": { "type": "proof_of_authority", "authorities": ["0a0b", "0c0d"] }
+            }"#,
+        )
+        .unwrap();
+
+        let engine = spec.build_engine(None).unwrap();
+        assert!(matches!(engine, NodeEngine::Poa(_)));
+        assert_eq!(engine.proposer_for_slot(1), Some(vec![0x0c, 0x0d]));
+        assert_eq!(spec.slot_duration(), Duration::from_millis(500));
+        assert!(spec.bootnodes.is_empty());
+        assert_eq!(spec.gossip_version, GOSSIP_VERSION);
+
+        // Only genesis parameters change the hash
+        let mut other = spec.clone();
+        other.bootnodes.push("/ip4/10.0.0.1/tcp/8000".to_string());
+        assert_eq!(other.genesis_hash(), spec.genesis_hash());
+        other.genesis_time += 1;
+        assert_ne!(other.genesis_hash(), spec.genesis_hash());
+    }
+
+    #[test]
+    fn test_allocations_fund_genesis() {
+        let address = Address::from_public_key(b"alice", Network::Devnet);
+        let spec: ChainSpec = serde_json::from_str(&format!(
+            r#"{{
+                "name": "devnet",
+                "genesis_time": 0,
+                "slot_duration_ms": 500,
+                "slots_per_epoch": 8,
+                "engine": {{ "type": "proof_of_stake" }},
+                "allocations": [{{ "address": "{address}", "amount": 1000 }}]
+            }}"#
+        ))
+        .unwrap();
+        let state = WorldState::genesis(Network::Devnet, &spec.allocations).unwrap();
+        assert_eq!(state.account(&address).unwrap().balance, 1000);
+        assert_ne!(
+            spec.genesis_hash(),
+            ChainSpec {
+                allocations: vec![],
+                ..spec.clone()
+            }
+            .genesis_hash()
+        );
+
+        // Funds on another network could never be spent
+        assert!(matches!(
+            WorldState::genesis(Network::Mainnet, &spec.allocations),
+            Err(ExecutionError::WrongNetwork(_))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_bad_authorities() {
+        let mut spec = ChainSpec {
+            engine: EngineSpec::ProofOfAuthority {
+                authorities: vec!["zz".to_string()],
+            },
+            ..ChainSpec::default()
+        };
+        assert!(matches!(
+            spec.build_engine(None),
+            Err(SpecError::InvalidAuthority(_))
+        ));
+
+        spec.engine = EngineSpec::ProofOfAuthority {
+            authorities: vec![],
+        };
+        assert!(matches!(
+            spec.build_engine(None),
+            Err(SpecError::NoAuthorities)
+        ));
+    }
+
+    #[test]
+    fn test_pos_needs_genesis_validators() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("spec.json");
+        let mut spec = ChainSpec::default();
+        std::fs::write(&path, serde_json::to_string(&spec).unwrap()).unwrap();
+        assert!(matches!(
+            ChainSpec::load(&path),
+            Err(SpecError::NoValidators)
+        ));
+
+        for (public_key, stake) in [("zz", 1), ("0a0b", 0)] {
+            spec.engine = EngineSpec::ProofOfStake {
+                validators: vec![GenesisValidator {
+                    public_key: public_key.to_string(),
+                    stake,
+                }],
+            };
+            assert!(matches!(
+                spec.validate(),
+                Err(SpecError::InvalidValidator(_))
+            ));
+        }
+
+        let spec = ChainSpec::dev(&[1; 32]);
+        std::fs::write(&path, serde_json::to_string(&spec).unwrap()).unwrap();
+        assert_eq!(ChainSpec::load(&path).unwrap(), spec);
+        let engine = spec.build_engine(None).unwrap();
+        assert_eq!(engine.proposer_for_slot(1), Some(vec![1; 32]));
+    }
+}
@@ -0,0 +1,192 @@
+use crate::spec::GenesisAllocation;
+use crypto::address::{Address, Network};
+use crypto::Blake3;
+use crypto::SignatureScheme;
+use ledger::block::Block;
+use ledger::merkle::compute_merkle_root;
+use ledger::transaction::{AccountTransaction, TransactionData};
+use std::collections::HashMap;
+use storage::state_compliance::AccountState;
+use thiserror::Error;
+
+/// Gas charged for every transaction before any payload.
+pub const TX_BASE_GAS: u64 = 21_000;
+/// Gas charged per byte of `data`.
+pub const TX_DATA_GAS_PER_BYTE: u64 = 16;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ExecutionError {
+    #[error("Invalid transaction signature")]
+    InvalidSignature,
+    #[error("Invalid sender public key")]
+    InvalidSender,
+    #[error("Expected nonce {expected}, got {actual}")]
+    InvalidNonce { expected: u64, actual: u64 },
+    #[error("Gas limit below intrinsic gas of {0}")]
+    IntrinsicGas(u64),
+    #[error("Insufficient balance")]
+    InsufficientBalance,
+    #[error("Recipient {0} is on another network")]
+    WrongNetwork(Address),
+    #[error("Contract creation is not supported yet")]
+    ContractCreationUnsupported,
+    #[error("Transaction gas limit exceeds the gas left in the block")]
+    BlockGasLimit,
+    #[error("Block state root does not match execution")]
+    StateRootMismatch,
+}
+
+/// Gas a transaction uses before running any contract code.
+pub fn intrinsic_gas(tx: &AccountTransaction) -> u64 {
+    TX_BASE_GAS + tx.data.len() as u64 * TX_DATA_GAS_PER_BYTE
+}
+
+/// Account balances and nonces that blocks are executed against.
+#[derive(Debug, Clone)]
+pub struct WorldState {
+    network: Network,
+    accounts: HashMap<Address, AccountState>,
+    current_epoch: u64,
+}
+
+impl WorldState {
+    pub fn new(network: Network) -> Self {
+        Self {
+            network,
+            accounts: HashMap::new(),
+            current_epoch: 0,
+        }
+    }
+
+    /// State at genesis, with the chain spec's allocations credited.
+    pub fn genesis(
+        network: Network,
+        allocations: &[GenesisAllocation],
+    ) -> Result<Self, ExecutionError> {
+        let mut state = Self::new(network);
+        for allocation in allocations {
+            if allocation.address.network() != network {
+                return Err(ExecutionError::WrongNetwork(allocation.address));
+            }
+            state.credit(allocation.address, allocation.amount);
+        }
+        Ok(state)
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    pub fn set_epoch(&mut self, epoch: u64) {
+        self.current_epoch = epoch;
+    }
+
+    pub fn account(&self, address: &Address) -> Option<&AccountState> {
+        self.accounts.get(address)
+    }
+
+    pub fn nonce(&self, address: &Address) -> u64 {
+        self.accounts.get(address).map_or(0, |a| a.nonce)
+    }
+
+    pub fn credit(&mut self, address: Address, amount: u64) {
+        let epoch = self.current_epoch;
+        self.accounts
+            .entry(address)
+            .or_insert_with(|| AccountState::new(0, 0, epoch))
+            .balance += amount;
+    }
+
+    pub fn sender_of(&self, tx: &AccountTransaction) -> Result<Address, ExecutionError> {
+        tx.signature_scheme
+            .derive_address(&tx.sender_public_key, self.network)
+            .map_err(|_| ExecutionError::InvalidSender)
+    }
+
+    /// Executes a transfer, charging `gas_used * gas_price` to the sender and
+    /// paying it to `fee_recipient`. State is untouched on error. Returns the gas used.
+    pub fn apply_transaction(
+        &mut self,
+        tx: &AccountTransaction,
+        fee_recipient: &Address,
+    ) -> Result<u64, ExecutionError> {
+        if !tx.verify_signature().unwrap_or(false) {
+            return Err(ExecutionError::InvalidSignature);
+        }
+        let Some(to) = tx.to else {
+            return Err(ExecutionError::ContractCreationUnsupported);
+        };
+        if to.network() != self.network {
+            return Err(ExecutionError::WrongNetwork(to));
+        }
+        let gas_used = intrinsic_gas(tx);
+        if tx.gas_limit < gas_used {
+            return Err(ExecutionError::IntrinsicGas(gas_used));
+        }
+
+        let sender = self.sender_of(tx)?;
+        let expected = self.nonce(&sender);
+        if tx.nonce != expected {
+            return Err(ExecutionError::InvalidNonce {
+                expected,
+                actual: tx.nonce,
+            });
+        }
+        let fee = gas_used
+            .checked_mul(tx.gas_price)
+            .ok_or(ExecutionError::InsufficientBalance)?;
+        let total = fee
+            .checked_add(tx.amount)
+            .ok_or(ExecutionError::InsufficientBalance)?;
+        let account = self
+            .accounts
+            .get_mut(&sender)
+            .filter(|a| a.balance >= total)
+            .ok_or(ExecutionError::InsufficientBalance)?;
+
+        account.balance -= total;
+        account.nonce += 1;
+        self.credit(to, tx.amount);
+        self.credit(*fee_recipient, fee);
+        Ok(gas_used)
+    }
+
+    /// Re-executes a block received from its proposer. The state is only
+    /// updated if every transaction applies and the resulting root matches the header.
+    /// Like the block builder, each transaction's gas limit has to fit in
+    /// what is left of `max_gas`.
+    pub fn apply_block(&mut self, block: &Block, max_gas: u64) -> Result<(), ExecutionError> {
+        let fee_recipient = SignatureScheme::Ed25519
+            .derive_address(&block.header.validator_public_key, self.network)
+            .map_err(|_| ExecutionError::InvalidSender)?;
+        let mut next = self.clone();
+        let mut gas_used: u64 = 0;
+        for tx in &block.transactions {
+            if let TransactionData::Account(tx) = &tx.data {
+                if gas_used.saturating_add(tx.gas_limit) > max_gas {
+                    return Err(ExecutionError::BlockGasLimit);
+                }
+                gas_used += next.apply_transaction(tx, &fee_recipient)?;
+            }
+        }
+        if next.state_root() != block.header.state_root {
+            return Err(ExecutionError::StateRootMismatch);
+        }
+        *self = next;
+        Ok(())
+    }
+
+    /// Merkle root over (address, account) leaves sorted by address.
+    pub fn state_root(&self) -> String {
+        let mut accounts: Vec<(&Address, &AccountState)> = self.accounts.iter().collect();
+        accounts.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+        let leaves: Vec<String> = accounts
+            .into_iter()
+            .map(|(address, account)| {
+                let serialized = bincode::serialize(&(address, account)).unwrap();
+                Blake3::hash(&serialized)
+            })
+            .collect();
+        compute_merkle_root(&leaves)
+    }
+}
@@ -0,0 +1,196 @@
+use consensus::finality::BlockTree;
+use ledger::block::Block;
+use storage::db::{StateStore, StorageError};
+
+const HEAD_KEY: &[u8] = b"chain/head";
+const BLOCK_PREFIX: &[u8] = b"chain/block/";
+const HEIGHT_OF_PREFIX: &[u8] = b"chain/height_of/";
+const CANONICAL_PREFIX: &[u8] = b"chain/canonical/";
+
+/// Parent hash of the first block.
+pub const GENESIS_PARENT: &str = "0";
+
+fn key(prefix: &[u8], suffix: &[u8]) -> Vec<u8> {
+    let mut key = prefix.to_vec();
+    key.extend_from_slice(suffix);
+    key
+}
+
+/// Imported blocks by hash plus the canonical chain by height. Height 0 is
+/// genesis (which has no block); the first real block is height 1.
+pub struct BlockStore<S> {
+    store: S,
+}
+
+impl<S: StateStore> BlockStore<S> {
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    /// The underlying store, for state kept next to the blocks.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    pub fn get(&self, hash: &str) -> Result<Option<Block>, StorageError> {
+        match self.store.get(&key(BLOCK_PREFIX, hash.as_bytes()))? {
+            Some(bytes) => Ok(Some(bincode::deserialize(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Whether `hash` is genesis or a stored block.
+    pub fn contains(&self, hash: &str) -> Result<bool, StorageError> {
+        Ok(hash == GENESIS_PARENT || self.height_of(hash)?.is_some())
+    }
+
+    pub fn height_of(&self, hash: &str) -> Result<Option<u64>, StorageError> {
+        if hash == GENESIS_PARENT {
+            return Ok(Some(0));
+        }
+        match self.store.get(&key(HEIGHT_OF_PREFIX, hash.as_bytes()))? {
+            Some(bytes) => Ok(Some(bincode::deserialize(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn hash_at(&self, height: u64) -> Result<Option<String>, StorageError> {
+        match self
+            .store
+            .get(&key(CANONICAL_PREFIX, &height.to_be_bytes()))?
+        {
+            Some(bytes) => Ok(Some(String::from_utf8_lossy(&bytes).into_owned())),
+            None => Ok(None),
+        }
+    }
+
+    /// Height and hash of the canonical head; genesis if nothing is stored.
+    pub fn head(&self) -> Result<(u64, String), StorageError> {
+        match self.store.get(HEAD_KEY)? {
+            Some(bytes) => Ok(bincode::deserialize(&bytes)?),
+            None => Ok((0, GENESIS_PARENT.to_string())),
+        }
+    }
+
+    /// Stores `block` and makes it the canonical head. Its parent must already
+    /// be stored. Returns the new head height.
+    pub fn insert(&self, block: &Block) -> Result<u64, StorageError> {
+        let hash = block.hash();
+        let height = self
+            .height_of(&block.header.parent_hash)?
+            .map_or(1, |parent| parent + 1);
+
+        self.store.put(
+            &key(BLOCK_PREFIX, hash.as_bytes()),
+            &bincode::serialize(block)?,
+        )?;
+        self.store.put(
+            &key(HEIGHT_OF_PREFIX, hash.as_bytes()),
+            &bincode::serialize(&height)?,
+        )?;
+        self.store.put(
+            &key(CANONICAL_PREFIX, &height.to_be_bytes()),
+            hash.as_bytes(),
+        )?;
+        self.store
+            .put(HEAD_KEY, &bincode::serialize(&(height, hash))?)?;
+        Ok(height)
+    }
+
+    /// Canonical blocks from `start` on, at most `count` of them.
+    pub fn blocks_by_range(&self, start: u64, count: u64) -> Result<Vec<Block>, StorageError> {
+        let (head, _) = self.head()?;
+        let mut blocks = Vec::new();
+        for height in start.max(1)..start.saturating_add(count).min(head + 1) {
+            let Some(hash) = self.hash_at(height)? else {
+                break;
+            };
+            match self.get(&hash)? {
+                Some(block) => blocks.push(block),
+                None => break,
+            }
+        }
+        Ok(blocks)
+    }
+
+    /// The requested blocks we have, in request order.
+    pub fn blocks_by_hash(&self, hashes: &[String]) -> Result<Vec<Block>, StorageError> {
+        let mut blocks = Vec::new();
+        for hash in hashes {
+            if let Some(block) = self.get(hash)? {
+                blocks.push(block);
+            }
+        }
+        Ok(blocks)
+    }
+}
+
+impl<S: StateStore> BlockTree for BlockStore<S> {
+    /// Blocks only ever extend the canonical chain, so ancestry comes down
+    /// to comparing canonical heights.
+    fn descends_from(&self, descendant: &str, ancestor: &str) -> bool {
+        let canonical_height = |hash: &str| {
+            let height = self.height_of(hash).ok()??;
+            let canonical = height == 0 || self.hash_at(height).ok()?? == hash;
+            canonical.then_some(height)
+        };
+        matches!(
+            (canonical_height(descendant), canonical_height(ancestor)),
+            (Some(descendant), Some(ancestor)) if ancestor <= descendant
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use storage::db::SledStore;
+    use tempfile::tempdir;
+
+    fn chain(len: u64) -> Vec<Block> {
+        let mut parent = GENESIS_PARENT.to_string();
+        (1..=len)
+            .map(|slot| {
+                let block = Block::new(
+                    "devnet",
+                    parent.clone(),
+                    0,
+                    slot,
+                    "root".to_string(),
+                    vec![],
+                    vec![],
+                );
+                parent = block.hash();
+                block
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_canonical_index_and_queries() {
+        let dir = tempdir().unwrap();
+        let store = BlockStore::new(SledStore::new(dir.path()).unwrap());
+        let blocks = chain(5);
+        for (i, block) in blocks.iter().enumerate() {
+            assert_eq!(store.insert(block).unwrap(), i as u64 + 1);
+        }
+
+        assert_eq!(store.head().unwrap(), (5, blocks[4].hash()));
+        assert!(store.contains(&blocks[2].hash()).unwrap());
+        assert!(store.contains(GENESIS_PARENT).unwrap());
+
+        let range = store.blocks_by_range(2, 10).unwrap();
+        assert_eq!(range, blocks[1..].to_vec());
+        assert!(store.blocks_by_range(6, 3).unwrap().is_empty());
+
+        let by_hash = store
+            .blocks_by_hash(&[blocks[3].hash(), "missing".to_string()])
+            .unwrap();
+        assert_eq!(by_hash, vec![blocks[3].clone()]);
+
+        assert!(store.descends_from(&blocks[4].hash(), &blocks[1].hash()));
+        assert!(store.descends_from(&blocks[1].hash(), GENESIS_PARENT));
+        assert!(!store.descends_from(&blocks[1].hash(), &blocks[4].hash()));
+        assert!(!store.descends_from("missing", GENESIS_PARENT));
+    }
+}
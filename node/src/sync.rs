@@ -0,0 +1,573 @@
+use ledger::block::Block;
+use libp2p::PeerId;
+use network::sync::{SyncRequest, SyncResponse, MAX_BLOCKS_PER_RESPONSE};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+/// How far past the local head sync downloads before waiting for imports.
+/// Peers report their own head, so this also caps what a lying peer can make
+/// us queue.
+pub const SYNC_WINDOW: u64 = 1024;
+
+/// Most blocks parked waiting for their parent; the oldest are evicted.
+pub const MAX_ORPHANS: usize = 256;
+/// Blocks more than this many slots from the head aren't parked. Range sync
+/// gets to them if they are real.
+pub const MAX_ORPHAN_DISTANCE: u64 = 64;
+
+/// Requests to hand to the network service.
+pub type SyncRequests = Vec<(PeerId, SyncRequest)>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncState {
+    Idle,
+    /// Catching up to the highest head a peer reported.
+    Downloading {
+        target: u64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Chunk {
+    start: u64,
+    count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum InFlight {
+    Status,
+    Range(Chunk),
+    Ancestors(Vec<String>),
+}
+
+/// A parked block and the peer it came from.
+#[derive(Debug)]
+struct Orphan {
+    block: Block,
+    peer: PeerId,
+}
+
+#[derive(Debug, Default)]
+struct PeerSync {
+    head_height: u64,
+    in_flight: Option<InFlight>,
+}
+
+/// Catches the node up with its peers. Asks every new peer for its head,
+/// splits the missing height range into chunks and downloads them from all
+/// peers that have them at once (one request per peer at a time), then hands
+/// blocks back strictly in height order. Blocks whose parent is unknown are
+/// parked until their ancestors have been fetched by hash.
+///
+/// Purely a state machine: the caller sends the returned requests and feeds
+/// responses and failures back in.
+pub struct SyncManager {
+    peers: HashMap<PeerId, PeerSync>,
+    queued: VecDeque<Chunk>,
+    /// Highest height queued for download so far. Chunks above it are only
+    /// created once a peer is free to fetch them.
+    queued_up_to: u64,
+    /// Highest height to download, at most `SYNC_WINDOW` past the local head.
+    target: u64,
+    /// Next height to hand out for import.
+    next_import: u64,
+    downloaded: BTreeMap<u64, Vec<Block>>,
+    wanted: VecDeque<String>,
+    requested: HashSet<String>,
+    /// Blocks waiting for their parent, keyed by parent hash.
+    orphans: HashMap<String, Vec<Orphan>>,
+    /// (parent hash, hash) of every parked block, oldest first.
+    orphan_order: VecDeque<(String, String)>,
+}
+
+impl SyncManager {
+    pub fn new(local_height: u64) -> Self {
+        Self {
+            peers: HashMap::new(),
+            queued: VecDeque::new(),
+            queued_up_to: local_height,
+            target: local_height,
+            next_import: local_height + 1,
+            downloaded: BTreeMap::new(),
+            wanted: VecDeque::new(),
+            requested: HashSet::new(),
+            orphans: HashMap::new(),
+            orphan_order: VecDeque::new(),
+        }
+    }
+
+    pub fn state(&self) -> SyncState {
+        if self.next_import > self.target {
+            SyncState::Idle
+        } else {
+            SyncState::Downloading {
+                target: self.target,
+            }
+        }
+    }
+
+    pub fn add_peer(&mut self, peer: PeerId) -> SyncRequests {
+        if self.peers.contains_key(&peer) {
+            return Vec::new();
+        }
+        self.peers.insert(
+            peer,
+            PeerSync {
+                head_height: 0,
+                in_flight: Some(InFlight::Status),
+            },
+        );
+        vec![(peer, SyncRequest::Status)]
+    }
+
+    pub fn remove_peer(&mut self, peer: &PeerId) -> SyncRequests {
+        if let Some(state) = self.peers.remove(peer) {
+            self.requeue(state.in_flight);
+        }
+        self.schedule()
+    }
+
+    /// A request to `peer` timed out or failed. Its work goes to someone else
+    /// and the peer is asked for its head again before it gets more.
+    pub fn on_failure(&mut self, peer: &PeerId) -> SyncRequests {
+        let Some(state) = self.peers.get_mut(peer) else {
+            return Vec::new();
+        };
+        state.head_height = 0;
+        let in_flight = state.in_flight.replace(InFlight::Status);
+        self.requeue(in_flight);
+        let mut requests = vec![(*peer, SyncRequest::Status)];
+        requests.extend(self.schedule());
+        requests
+    }
+
+    /// Handles a response and returns follow-up requests plus blocks that are
+    /// ready to import, in order.
+    pub fn on_response(
+        &mut self,
+        peer: &PeerId,
+        response: SyncResponse,
+    ) -> (SyncRequests, Vec<Block>) {
+        let Some(state) = self.peers.get_mut(peer) else {
+            return (Vec::new(), Vec::new());
+        };
+        let mut ready = Vec::new();
+        match (state.in_flight.take(), response) {
+            (Some(InFlight::Status), SyncResponse::Status { head_height, .. }) => {
+                state.head_height = head_height;
+            }
+            (Some(InFlight::Range(chunk)), SyncResponse::Blocks(mut blocks)) => {
+                if blocks.is_empty() {
+                    // The peer doesn't have this range after all
+                    state.head_height = state.head_height.min(chunk.start - 1);
+                    self.queued.push_front(chunk);
+                } else {
+                    blocks.truncate(chunk.count as usize);
+                    let received = blocks.len() as u64;
+                    if received < chunk.count {
+                        self.queued.push_front(Chunk {
+                            start: chunk.start + received,
+                            count: chunk.count - received,
+                        });
+                    }
+                    self.downloaded.insert(chunk.start, blocks);
+                    ready = self.drain_downloaded();
+                }
+            }
+            (Some(InFlight::Ancestors(hashes)), SyncResponse::Blocks(blocks)) => {
+                for hash in &hashes {
+                    self.requested.remove(hash);
+                }
+                ready = blocks
+                    .into_iter()
+                    .filter(|block| hashes.contains(&block.hash()))
+                    .collect();
+            }
+            (in_flight, _) => self.requeue(in_flight),
+        }
+        self.extend_target();
+        (self.schedule(), ready)
+    }
+
+    /// The node imported up to `height` on its own (e.g. from gossip), so
+    /// there's no need to download anything below it.
+    pub fn on_imported(&mut self, height: u64) {
+        if height < self.next_import {
+            return;
+        }
+        self.next_import = height + 1;
+        self.queued_up_to = self.queued_up_to.max(height);
+        self.target = self.target.max(height);
+        self.queued
+            .retain(|chunk| chunk.start + chunk.count > height + 1);
+        self.downloaded = self.downloaded.split_off(&(height + 1));
+        self.extend_target();
+    }
+
+    /// Reports that blocks handed out for import only got the chain to
+    /// `local_height` (one failed and the rest were skipped). Those heights are
+    /// downloaded again, possibly from another peer.
+    pub fn on_import_failed(&mut self, local_height: u64) -> SyncRequests {
+        let mut start = local_height + 1;
+        let mut chunks = Vec::new();
+        while start < self.next_import {
+            let count = MAX_BLOCKS_PER_RESPONSE.min(self.next_import - start);
+            chunks.push(Chunk { start, count });
+            start += count;
+        }
+        for chunk in chunks.into_iter().rev() {
+            self.queued.push_front(chunk);
+        }
+        self.next_import = self.next_import.min(local_height + 1);
+        self.schedule()
+    }
+
+    /// Parks `block` from `peer` until its parent arrives and asks a peer for
+    /// the parent. Blocks too far from `head_slot` are dropped. Returns the
+    /// requests and the senders of blocks evicted to make room, which never
+    /// led anywhere.
+    pub fn fetch_ancestor(
+        &mut self,
+        block: Block,
+        peer: PeerId,
+        head_slot: u64,
+    ) -> (SyncRequests, Vec<PeerId>) {
+        if block.header.slot.abs_diff(head_slot) > MAX_ORPHAN_DISTANCE {
+            return (Vec::new(), Vec::new());
+        }
+        let hash = block.hash();
+        let parent = block.header.parent_hash.clone();
+        let children = self.orphans.entry(parent.clone()).or_default();
+        if !children.iter().any(|o| o.block.hash() == hash) {
+            children.push(Orphan { block, peer });
+            self.orphan_order.push_back((parent.clone(), hash));
+        }
+        let mut evicted = Vec::new();
+        while self.orphan_order.len() > MAX_ORPHANS {
+            evicted.extend(self.evict_oldest_orphan());
+        }
+        if self.orphans.contains_key(&parent)
+            && !self.requested.contains(&parent)
+            && !self.wanted.contains(&parent)
+        {
+            self.wanted.push_back(parent);
+        }
+        (self.schedule(), evicted)
+    }
+
+    /// Parked blocks that were waiting for `hash`.
+    pub fn take_children(&mut self, hash: &str) -> Vec<Block> {
+        let children = self.orphans.remove(hash).unwrap_or_default();
+        self.orphan_order.retain(|(parent, _)| parent != hash);
+        children.into_iter().map(|o| o.block).collect()
+    }
+
+    pub fn orphan_count(&self) -> usize {
+        self.orphan_order.len()
+    }
+
+    /// Drops the oldest parked block, and stops asking for its parent if
+    /// nothing else waits on it. Returns the peer that sent it.
+    fn evict_oldest_orphan(&mut self) -> Option<PeerId> {
+        let (parent, hash) = self.orphan_order.pop_front()?;
+        let children = self.orphans.get_mut(&parent)?;
+        let index = children.iter().position(|o| o.block.hash() == hash)?;
+        let orphan = children.remove(index);
+        if children.is_empty() {
+            self.orphans.remove(&parent);
+            self.wanted.retain(|wanted| *wanted != parent);
+        }
+        Some(orphan.peer)
+    }
+
+    /// Moves the target up to the best head any peer reported, but no
+    /// further than `SYNC_WINDOW` past what we have imported.
+    fn extend_target(&mut self) {
+        let best = self.peers.values().map(|p| p.head_height).max();
+        let limit = (self.next_import - 1).saturating_add(SYNC_WINDOW);
+        if let Some(best) = best {
+            self.target = self.target.max(best.min(limit));
+        }
+    }
+
+    /// A queued chunk `head_height` can serve, or else the next one above
+    /// `queued_up_to` if that is below both the target and the peer's head.
+    fn next_chunk(&mut self, head_height: u64) -> Option<Chunk> {
+        if let Some(index) = self
+            .queued
+            .iter()
+            .position(|chunk| chunk.start <= head_height)
+        {
+            return self.queued.remove(index);
+        }
+        let start = self.queued_up_to.checked_add(1)?;
+        if start > self.target || start > head_height {
+            return None;
+        }
+        let count = MAX_BLOCKS_PER_RESPONSE.min(self.target - start + 1);
+        self.queued_up_to += count;
+        Some(Chunk { start, count })
+    }
+
+    fn requeue(&mut self, in_flight: Option<InFlight>) {
+        match in_flight {
+            Some(InFlight::Range(chunk)) if chunk.start >= self.next_import => {
+                self.queued.push_front(chunk)
+            }
+            Some(InFlight::Ancestors(hashes)) => {
+                for hash in hashes {
+                    self.requested.remove(&hash);
+                    self.wanted.push_back(hash);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn drain_downloaded(&mut self) -> Vec<Block> {
+        let mut ready = Vec::new();
+        while let Some(blocks) = self.downloaded.remove(&self.next_import) {
+            self.next_import += blocks.len() as u64;
+            ready.extend(blocks);
+        }
+        ready
+    }
+
+    fn schedule(&mut self) -> SyncRequests {
+        let mut requests = Vec::new();
+        let mut idle: Vec<PeerId> = self
+            .peers
+            .iter()
+            .filter(|(_, p)| p.in_flight.is_none())
+            .map(|(id, _)| *id)
+            .collect();
+        idle.sort();
+
+        for peer in idle {
+            if !self.wanted.is_empty() {
+                let take = self.wanted.len().min(MAX_BLOCKS_PER_RESPONSE as usize);
+                let hashes: Vec<String> = self.wanted.drain(..take).collect();
+                self.requested.extend(hashes.iter().cloned());
+                self.peers.get_mut(&peer).unwrap().in_flight =
+                    Some(InFlight::Ancestors(hashes.clone()));
+                requests.push((peer, SyncRequest::BlocksByHash(hashes)));
+                continue;
+            }
+            let Some(chunk) = self.next_chunk(self.peers[&peer].head_height) else {
+                continue;
+            };
+            self.peers.get_mut(&peer).unwrap().in_flight = Some(InFlight::Range(chunk));
+            requests.push((
+                peer,
+                SyncRequest::BlocksByRange {
+                    start_height: chunk.start,
+                    count: chunk.count,
+                },
+            ));
+        }
+        requests
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain(len: u64) -> Vec<Block> {
+        let mut parent = "0".to_string();
+        (1..=len)
+            .map(|slot| {
+                let block = Block::new(
+                    "devnet",
+                    parent.clone(),
+                    0,
+                    slot,
+                    "root".to_string(),
+                    vec![],
+                    vec![],
+                );
+                parent = block.hash();
+                block
+            })
+            .collect()
+    }
+
+    fn range(request: &SyncRequest) -> (u64, u64) {
+        match request {
+            SyncRequest::BlocksByRange {
+                start_height,
+                count,
+            } => (*start_height, *count),
+            other => panic!("expected a range request, got {other:?}"),
+        }
+    }
+
+    fn status(height: u64) -> SyncResponse {
+        SyncResponse::Status {
+            head_height: height,
+            head_hash: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_downloads_in_parallel_and_imports_in_order() {
+        let blocks = chain(100);
+        let (a, b) = (PeerId::random(), PeerId::random());
+        let mut sync = SyncManager::new(0);
+
+        assert_eq!(sync.add_peer(a), vec![(a, SyncRequest::Status)]);
+        assert_eq!(sync.add_peer(b), vec![(b, SyncRequest::Status)]);
+        let (first, _) = sync.on_response(&a, status(100));
+        let (second, _) = sync.on_response(&b, status(100));
+        assert_eq!(sync.state(), SyncState::Downloading { target: 100 });
+
+        // Both chunks are in flight at once, one per peer
+        let requests: Vec<_> = first.into_iter().chain(second).collect();
+        assert_eq!(requests.len(), 2);
+        let mut assigned: HashMap<PeerId, (u64, u64)> =
+            requests.iter().map(|(p, r)| (*p, range(r))).collect();
+        let (late_peer, late) = assigned
+            .iter()
+            .find(|(_, (start, _))| *start == 65)
+            .map(|(p, r)| (*p, *r))
+            .unwrap();
+        let early_peer = if late_peer == a { b } else { a };
+        assert_eq!(assigned.remove(&early_peer), Some((1, 64)));
+
+        // The later chunk arriving first is held back
+        let slice = blocks[(late.0 - 1) as usize..].to_vec();
+        let (_, ready) = sync.on_response(&late_peer, SyncResponse::Blocks(slice));
+        assert!(ready.is_empty());
+
+        let (_, ready) = sync.on_response(&early_peer, SyncResponse::Blocks(blocks[..64].to_vec()));
+        assert_eq!(ready, blocks);
+        assert_eq!(sync.state(), SyncState::Idle);
+    }
+
+    #[test]
+    fn test_target_stays_within_the_window() {
+        let peer = PeerId::random();
+        let mut sync = SyncManager::new(10);
+        sync.add_peer(peer);
+
+        // A peer can claim any head; only the window gets queued
+        let (requests, _) = sync.on_response(&peer, status(u64::MAX));
+        assert_eq!(range(&requests[0].1), (11, 64));
+        assert_eq!(
+            sync.state(),
+            SyncState::Downloading {
+                target: 10 + SYNC_WINDOW
+            }
+        );
+        assert!(sync.queued.is_empty());
+
+        // Importing moves the window along
+        sync.on_imported(500);
+        assert_eq!(
+            sync.state(),
+            SyncState::Downloading {
+                target: 500 + SYNC_WINDOW
+            }
+        );
+    }
+
+    #[test]
+    fn test_failed_chunk_moves_to_another_peer() {
+        let (a, b) = (PeerId::random(), PeerId::random());
+        let mut sync = SyncManager::new(0);
+        sync.add_peer(a);
+        sync.add_peer(b);
+        let (requests, _) = sync.on_response(&a, status(10));
+        assert_eq!(range(&requests[0].1), (1, 10));
+
+        // b is still waiting on its status, so the retry goes out once it's idle
+        assert_eq!(sync.on_failure(&a), vec![(a, SyncRequest::Status)]);
+        let (requests, _) = sync.on_response(&b, status(10));
+        assert_eq!(requests.len(), 1);
+        assert_eq!(range(&requests[0].1), (1, 10));
+    }
+
+    #[test]
+    fn test_fetches_missing_ancestors() {
+        let blocks = chain(3);
+        let peer = PeerId::random();
+        let mut sync = SyncManager::new(1);
+        sync.add_peer(peer);
+        sync.on_response(&peer, status(1));
+
+        let (requests, evicted) = sync.fetch_ancestor(blocks[2].clone(), peer, 1);
+        assert!(evicted.is_empty());
+        assert_eq!(
+            requests,
+            vec![(peer, SyncRequest::BlocksByHash(vec![blocks[1].hash()]))]
+        );
+        let (_, ready) = sync.on_response(&peer, SyncResponse::Blocks(vec![blocks[1].clone()]));
+        assert_eq!(ready, vec![blocks[1].clone()]);
+        assert_eq!(
+            sync.take_children(&blocks[1].hash()),
+            vec![blocks[2].clone()]
+        );
+    }
+
+    #[test]
+    fn test_orphan_pool_is_bounded() {
+        let (honest, spammer) = (PeerId::random(), PeerId::random());
+        let mut sync = SyncManager::new(0);
+        let orphan = |parent: usize, slot: u64| {
+            Block::new(
+                "devnet",
+                parent.to_string(),
+                0,
+                slot,
+                "root".to_string(),
+                vec![],
+                vec![],
+            )
+        };
+
+        // Far from the head: not worth parking
+        let (_, evicted) = sync.fetch_ancestor(orphan(0, 1_000), honest, 10);
+        assert!(evicted.is_empty());
+        assert_eq!(sync.orphan_count(), 0);
+
+        sync.fetch_ancestor(orphan(0, 10), honest, 10);
+        for parent in 1..=MAX_ORPHANS {
+            let (_, evicted) = sync.fetch_ancestor(orphan(parent, 10), spammer, 10);
+            assert_eq!(
+                evicted,
+                if parent == MAX_ORPHANS {
+                    vec![honest]
+                } else {
+                    vec![]
+                }
+            );
+        }
+        assert_eq!(sync.orphan_count(), MAX_ORPHANS);
+        assert!(sync.take_children("0").is_empty());
+        assert_eq!(sync.wanted.len(), MAX_ORPHANS);
+        assert!(!sync.wanted.contains(&"0".to_string()));
+        assert_eq!(sync.take_children("1").len(), 1);
+        assert_eq!(sync.orphan_count(), MAX_ORPHANS - 1);
+    }
+
+    #[test]
+    fn test_failed_import_is_downloaded_again() {
+        let blocks = chain(10);
+        let (a, b) = (PeerId::random(), PeerId::random());
+        let mut sync = SyncManager::new(0);
+        sync.add_peer(a);
+        sync.on_response(&a, status(10));
+        let (_, ready) = sync.on_response(&a, SyncResponse::Blocks(blocks.clone()));
+        assert_eq!(ready.len(), 10);
+        assert_eq!(sync.state(), SyncState::Idle);
+
+        // Block 4 didn't import; 4..=10 have to come from somewhere again
+        sync.add_peer(b);
+        let requests = sync.on_import_failed(3);
+        assert_eq!(sync.state(), SyncState::Downloading { target: 10 });
+        assert_eq!(requests.len(), 1);
+        assert_eq!(range(&requests[0].1), (4, 7));
+        let (_, ready) =
+            sync.on_response(&requests[0].0, SyncResponse::Blocks(blocks[3..].to_vec()));
+        assert_eq!(ready, blocks[3..].to_vec());
+    }
+}
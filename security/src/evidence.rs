@@ -0,0 +1,218 @@
+use crypto::KeyPair;
+use ledger::block::BlockHeader;
+use ledger::evidence::{DoubleSignEvidence, DoubleVoteEvidence, Evidence};
+use ledger::vote::{Vote, VoteKind};
+use std::collections::{HashMap, HashSet};
+
+/// Default cap on how much evidence a proposer packs into one block.
+pub const MAX_EVIDENCE_PER_BLOCK: usize = 16;
+
+type VoteKey = (Vec<u8>, VoteKind, u64, u32);
+
+/// Watches signed headers and votes and produces evidence the first time a
+/// validator signs two different messages for the same slot (and round).
+#[derive(Default)]
+pub struct EquivocationDetector {
+    headers: HashMap<(Vec<u8>, u64), BlockHeader>,
+    votes: HashMap<VoteKey, Vote>,
+    reported: HashSet<String>,
+}
+
+impl EquivocationDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Headers with a bad signature are dropped before they are remembered,
+    /// so they can't take the place of the validator's real header.
+    pub fn observe_header(&mut self, header: &BlockHeader) -> Option<Evidence> {
+        let signed = KeyPair::verify(
+            &header.validator_public_key,
+            &header.signing_bytes(),
+            &header.signature,
+        );
+        if signed.is_err() {
+            return None;
+        }
+        let key = (header.validator_public_key.clone(), header.slot);
+        let first = self.headers.entry(key).or_insert_with(|| header.clone());
+        if first.signing_bytes() == header.signing_bytes() {
+            return None;
+        }
+        let evidence = Evidence::DoubleSign(Box::new(DoubleSignEvidence::new(
+            first.clone(),
+            header.clone(),
+        )));
+        self.report(evidence)
+    }
+
+    pub fn observe_vote(&mut self, vote: &Vote) -> Option<Evidence> {
+        if vote.verify().is_err() {
+            return None;
+        }
+        let key = (
+            vote.validator_public_key.clone(),
+            vote.kind,
+            vote.slot,
+            vote.round,
+        );
+        let first = self.votes.entry(key).or_insert_with(|| vote.clone());
+        if first.block_hash == vote.block_hash {
+            return None;
+        }
+        let evidence = Evidence::DoubleVote(Box::new(DoubleVoteEvidence::new(
+            first.clone(),
+            vote.clone(),
+        )));
+        self.report(evidence)
+    }
+
+    /// Only signed conflicts count, and each is reported once.
+    fn report(&mut self, evidence: Evidence) -> Option<Evidence> {
+        if evidence.verify().is_err() || !self.reported.insert(evidence.hash()) {
+            return None;
+        }
+        Some(evidence)
+    }
+
+    /// Forgets messages below `slot` (e.g. once it is finalized).
+    pub fn prune_below(&mut self, slot: u64) {
+        self.headers.retain(|(_, s), _| *s >= slot);
+        self.votes.retain(|(_, _, s, _), _| *s >= slot);
+    }
+}
+
+/// Verified evidence waiting to be included in a block.
+#[derive(Default)]
+pub struct EvidencePool {
+    pending: Vec<Evidence>,
+    /// Hashes of evidence already pending or included on chain.
+    known: HashSet<String>,
+}
+
+impl EvidencePool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns true if the evidence is valid and wasn't seen before, i.e.
+    /// worth gossiping on.
+    pub fn add(&mut self, evidence: Evidence) -> bool {
+        if evidence.verify().is_err() || !self.known.insert(evidence.hash()) {
+            return false;
+        }
+        self.pending.push(evidence);
+        true
+    }
+
+    pub fn pending(&self) -> &[Evidence] {
+        &self.pending
+    }
+
+    pub fn for_block(&self, max: usize) -> Vec<Evidence> {
+        self.pending.iter().take(max).cloned().collect()
+    }
+
+    /// Drops evidence that made it into a block.
+    pub fn mark_included(&mut self, included: &[Evidence]) {
+        let hashes: HashSet<String> = included.iter().map(Evidence::hash).collect();
+        self.known.extend(hashes.iter().cloned());
+        self.pending.retain(|e| !hashes.contains(&e.hash()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(slot: u64, state_root: &str, keypair: &KeyPair) -> BlockHeader {
+        let mut header = BlockHeader {
+            chain_id: "devnet".to_string(),
+            parent_hash: "0".to_string(),
+            timestamp: 0,
+            slot,
+            state_root: state_root.to_string(),
+            transactions_root: "".to_string(),
+            evidence_root: "".to_string(),
+            randao_reveal: Vec::new(),
+            randao_mix: [0u8; 32],
+            validator_public_key: keypair.public_key.clone(),
+            signature: vec![],
+        };
+        header.signature = keypair.sign(&header.signing_bytes()).unwrap();
+        header
+    }
+
+    #[test]
+    fn test_detects_conflicting_headers_once() {
+        let keypair = KeyPair::generate();
+        let mut detector = EquivocationDetector::new();
+
+        assert!(detector.observe_header(&header(1, "a", &keypair)).is_none());
+        assert!(detector.observe_header(&header(1, "a", &keypair)).is_none());
+        assert!(detector.observe_header(&header(2, "b", &keypair)).is_none());
+
+        let evidence = detector.observe_header(&header(1, "b", &keypair)).unwrap();
+        assert_eq!(evidence.offender(), keypair.public_key.as_slice());
+        assert!(detector.observe_header(&header(1, "b", &keypair)).is_none());
+
+        // Unsigned junk can't frame a validator
+        let mut forged = header(2, "c", &keypair);
+        forged.signature = vec![0; 64];
+        assert!(detector.observe_header(&forged).is_none());
+
+        // Nor can it go first and hide the real header's conflict
+        let mut forged = header(3, "junk", &keypair);
+        forged.signature = vec![0; 64];
+        assert!(detector.observe_header(&forged).is_none());
+        assert!(detector.observe_header(&header(3, "a", &keypair)).is_none());
+        assert!(detector.observe_header(&header(3, "b", &keypair)).is_some());
+    }
+
+    #[test]
+    fn test_detects_conflicting_votes() {
+        let keypair = KeyPair::generate();
+        let mut detector = EquivocationDetector::new();
+        let vote = |hash: &str| {
+            Vote::new(
+                "devnet",
+                VoteKind::Prevote,
+                4,
+                0,
+                hash.to_string(),
+                &keypair,
+            )
+            .unwrap()
+        };
+
+        let mut forged = vote("junk");
+        forged.signature = vec![0; 64];
+        assert!(detector.observe_vote(&forged).is_none());
+        assert!(detector.observe_vote(&vote("a")).is_none());
+        assert!(matches!(
+            detector.observe_vote(&vote("b")),
+            Some(Evidence::DoubleVote(_))
+        ));
+    }
+
+    #[test]
+    fn test_pool_deduplicates_and_drops_included() {
+        let keypair = KeyPair::generate();
+        let evidence = Evidence::DoubleSign(Box::new(DoubleSignEvidence::new(
+            header(1, "a", &keypair),
+            header(1, "b", &keypair),
+        )));
+        let mut pool = EvidencePool::new();
+
+        assert!(pool.add(evidence.clone()));
+        assert!(!pool.add(evidence.clone()));
+        assert_eq!(
+            pool.for_block(MAX_EVIDENCE_PER_BLOCK),
+            vec![evidence.clone()]
+        );
+
+        pool.mark_included(std::slice::from_ref(&evidence));
+        assert!(pool.pending().is_empty());
+        assert!(!pool.add(evidence));
+    }
+}
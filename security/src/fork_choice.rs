@@ -12,6 +12,12 @@ impl HeaviestChainRule {
     }
 }
 
+impl Default for HeaviestChainRule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // Simplified illustration: In a real system, we'd traverse the block tree.
 // Here we compare linear chains by summing the "weight" of blocks.
 // For now, let's assume block weight = 1 (Longest Chain) OR we'd look at signatures.
@@ -65,15 +71,20 @@ mod tests {
     fn mock_block() -> Block {
         Block {
             header: BlockHeader {
+                chain_id: "devnet".to_string(),
                 parent_hash: "0".to_string(),
                 timestamp: 0,
                 slot: 0,
                 state_root: "".to_string(),
                 transactions_root: "".to_string(),
+                evidence_root: "".to_string(),
+                randao_reveal: Vec::new(),
+                randao_mix: [0u8; 32],
                 validator_public_key: vec![],
                 signature: vec![],
             },
             transactions: vec![],
+            evidence: vec![],
         }
     }
 
@@ -1,2 +1,4 @@
+pub mod evidence;
 pub mod fork_choice;
+pub mod liveness;
 pub mod slashing;
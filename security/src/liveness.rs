@@ -0,0 +1,184 @@
+use crate::slashing::Misbehavior;
+use std::collections::{HashMap, VecDeque};
+
+/// Default number of slots the tracker looks back over.
+pub const DEFAULT_LIVENESS_WINDOW: u64 = 100;
+/// Default number of misses within the window that counts as downtime.
+pub const DEFAULT_MAX_MISSED: u64 = 50;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Uptime {
+    pub proposals_expected: u64,
+    pub proposals_made: u64,
+    pub votes_expected: u64,
+    pub votes_cast: u64,
+}
+
+impl Uptime {
+    pub fn missed(&self) -> u64 {
+        (self.proposals_expected - self.proposals_made) + (self.votes_expected - self.votes_cast)
+    }
+
+    /// Share of expected duties performed, 1.0 when nothing was expected.
+    pub fn ratio(&self) -> f64 {
+        let expected = self.proposals_expected + self.votes_expected;
+        if expected == 0 {
+            return 1.0;
+        }
+        (self.proposals_made + self.votes_cast) as f64 / expected as f64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Duty {
+    Proposal,
+    Vote,
+}
+
+#[derive(Debug, Default)]
+struct Record {
+    /// (slot, duty, performed) for the slots inside the window.
+    duties: VecDeque<(u64, Duty, bool)>,
+}
+
+impl Record {
+    fn uptime(&self) -> Uptime {
+        let mut uptime = Uptime::default();
+        for (_, duty, performed) in &self.duties {
+            match duty {
+                Duty::Proposal => {
+                    uptime.proposals_expected += 1;
+                    uptime.proposals_made += *performed as u64;
+                }
+                Duty::Vote => {
+                    uptime.votes_expected += 1;
+                    uptime.votes_cast += *performed as u64;
+                }
+            }
+        }
+        uptime
+    }
+}
+
+/// Sliding-window record of missed proposals and votes per validator. Once a
+/// validator misses `max_missed` duties within `window` slots it is reported
+/// for downtime and its window starts over.
+pub struct LivenessTracker {
+    window: u64,
+    max_missed: u64,
+    records: HashMap<Vec<u8>, Record>,
+}
+
+impl LivenessTracker {
+    pub fn new(window: u64, max_missed: u64) -> Self {
+        Self {
+            window: window.max(1),
+            max_missed: max_missed.max(1),
+            records: HashMap::new(),
+        }
+    }
+
+    /// Slots the tracker looks back over.
+    pub fn window(&self) -> u64 {
+        self.window
+    }
+
+    pub fn record_proposal(
+        &mut self,
+        validator: &[u8],
+        slot: u64,
+        proposed: bool,
+    ) -> Option<Misbehavior> {
+        self.record(validator, slot, Duty::Proposal, proposed)
+    }
+
+    pub fn record_vote(&mut self, validator: &[u8], slot: u64, voted: bool) -> Option<Misbehavior> {
+        self.record(validator, slot, Duty::Vote, voted)
+    }
+
+    fn record(
+        &mut self,
+        validator: &[u8],
+        slot: u64,
+        duty: Duty,
+        performed: bool,
+    ) -> Option<Misbehavior> {
+        let window = self.window;
+        let record = self.records.entry(validator.to_vec()).or_default();
+        record.duties.push_back((slot, duty, performed));
+        while record
+            .duties
+            .front()
+            .is_some_and(|(s, _, _)| *s + window <= slot)
+        {
+            record.duties.pop_front();
+        }
+
+        let missed = record.uptime().missed();
+        if missed < self.max_missed {
+            return None;
+        }
+        record.duties.clear();
+        Some(Misbehavior::Downtime(missed))
+    }
+
+    pub fn uptime(&self, validator: &[u8]) -> Option<Uptime> {
+        self.records.get(validator).map(Record::uptime)
+    }
+
+    /// Uptime of every tracked validator, sorted by public key.
+    pub fn uptimes(&self) -> Vec<(Vec<u8>, Uptime)> {
+        let mut uptimes: Vec<(Vec<u8>, Uptime)> = self
+            .records
+            .iter()
+            .map(|(pk, record)| (pk.clone(), record.uptime()))
+            .collect();
+        uptimes.sort_by(|a, b| a.0.cmp(&b.0));
+        uptimes
+    }
+}
+
+impl Default for LivenessTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_LIVENESS_WINDOW, DEFAULT_MAX_MISSED)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_misses_outside_window_are_forgotten() {
+        let mut tracker = LivenessTracker::new(10, 3);
+        let validator = [1u8];
+
+        assert!(tracker.record_proposal(&validator, 0, false).is_none());
+        assert!(tracker.record_vote(&validator, 5, false).is_none());
+        // Slot 0 has slid out of the window by slot 10
+        assert!(tracker.record_vote(&validator, 10, false).is_none());
+        assert!(tracker.record_vote(&validator, 12, true).is_none());
+
+        let uptime = tracker.uptime(&validator).unwrap();
+        assert_eq!(uptime.missed(), 2);
+        assert_eq!(uptime.votes_expected, 3);
+        assert!((uptime.ratio() - 1.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_threshold_reports_downtime_once() {
+        let mut tracker = LivenessTracker::new(10, 3);
+        let validator = [1u8];
+
+        assert!(tracker.record_proposal(&validator, 1, false).is_none());
+        assert!(tracker.record_vote(&validator, 2, false).is_none());
+        assert_eq!(
+            tracker.record_vote(&validator, 3, false),
+            Some(Misbehavior::Downtime(3))
+        );
+        // Window restarts after reporting
+        assert!(tracker.record_vote(&validator, 4, false).is_none());
+        assert_eq!(tracker.uptime(&validator).unwrap().missed(), 1);
+        assert!(tracker.uptime(&[2]).is_none());
+    }
+}
@@ -1,3 +1,9 @@
+use consensus::staking::StakeManager;
+use ledger::evidence::Evidence;
+
+/// Epochs a validator stays jailed after a downtime offense.
+pub const DEFAULT_JAIL_EPOCHS: u64 = 2;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Misbehavior {
     DoubleSign,
@@ -8,6 +14,15 @@ pub struct SlashingManager {
     // Configuration
     pub double_sign_penalty_percent: u8,
     pub downtime_penalty_per_block: u64, // simplified flat amount per block
+    pub jail_epochs: u64,
+}
+
+/// What happened to a validator when a penalty was applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlashOutcome {
+    pub burned: u64,
+    pub jailed_until: Option<u64>,
+    pub tombstoned: bool,
 }
 
 impl SlashingManager {
@@ -15,9 +30,15 @@ impl SlashingManager {
         Self {
             double_sign_penalty_percent,
             downtime_penalty_per_block,
+            jail_epochs: DEFAULT_JAIL_EPOCHS,
         }
     }
 
+    pub fn with_jail_epochs(mut self, jail_epochs: u64) -> Self {
+        self.jail_epochs = jail_epochs;
+        self
+    }
+
     pub fn calculate_penalty(&self, misbehavior: Misbehavior, stake: u64) -> u64 {
         match misbehavior {
             Misbehavior::DoubleSign => {
@@ -31,6 +52,64 @@ impl SlashingManager {
             }
         }
     }
+
+    /// Slashes `validator` for an offense committed at `infraction_epoch`.
+    /// Double signing tombstones the validator; downtime jails it for
+    /// `jail_epochs` from the current epoch. Tombstoned validators are skipped
+    /// so the same evidence can't be punished twice.
+    pub fn apply(
+        &self,
+        stake_manager: &mut StakeManager,
+        validator: &[u8],
+        misbehavior: Misbehavior,
+        infraction_epoch: u64,
+    ) -> Option<SlashOutcome> {
+        if stake_manager.get_validator(validator)?.is_tombstoned {
+            return None;
+        }
+
+        let slashable = stake_manager.slashable_stake(validator, infraction_epoch);
+        let penalty = self.calculate_penalty(misbehavior.clone(), slashable);
+        let burned = stake_manager.slash(validator, penalty, infraction_epoch);
+
+        let outcome = match misbehavior {
+            Misbehavior::DoubleSign => {
+                stake_manager.tombstone(validator);
+                SlashOutcome {
+                    burned,
+                    jailed_until: None,
+                    tombstoned: true,
+                }
+            }
+            Misbehavior::Downtime(_) => {
+                let until = stake_manager.current_epoch() + self.jail_epochs;
+                stake_manager.jail(validator, until);
+                SlashOutcome {
+                    burned,
+                    jailed_until: Some(until),
+                    tombstoned: false,
+                }
+            }
+        };
+        Some(outcome)
+    }
+
+    /// Slashes the offender named in `evidence` (taken from a block or gossip)
+    /// for double signing. Invalid evidence is ignored.
+    pub fn apply_evidence(
+        &self,
+        stake_manager: &mut StakeManager,
+        evidence: &Evidence,
+        infraction_epoch: u64,
+    ) -> Option<SlashOutcome> {
+        evidence.verify().ok()?;
+        self.apply(
+            stake_manager,
+            evidence.offender(),
+            Misbehavior::DoubleSign,
+            infraction_epoch,
+        )
+    }
 }
 
 #[cfg(test)]
@@ -51,4 +130,46 @@ mod tests {
         let penalty = manager.calculate_penalty(Misbehavior::Downtime(5), 0);
         assert_eq!(penalty, 50); // 5 blocks * 10 = 50
     }
+
+    fn stake_manager() -> StakeManager {
+        let mut stake = StakeManager::new();
+        stake.add_stake(vec![1], 1000);
+        stake.add_stake(vec![2], 1000);
+        stake.process_epoch(1);
+        stake
+    }
+
+    #[test]
+    fn test_apply_double_sign_tombstones() {
+        let manager = SlashingManager::new(5, 10);
+        let mut stake = stake_manager();
+
+        let outcome = manager
+            .apply(&mut stake, &[1], Misbehavior::DoubleSign, 1)
+            .unwrap();
+        assert_eq!(outcome.burned, 50);
+        assert!(outcome.tombstoned);
+        assert_eq!(stake.get_validator(&[1]).unwrap().stake, 950);
+        assert_eq!(stake.get_voting_power(&[1]), 0);
+
+        // Already tombstoned: evidence is ignored
+        assert!(manager
+            .apply(&mut stake, &[1], Misbehavior::DoubleSign, 1)
+            .is_none());
+    }
+
+    #[test]
+    fn test_apply_downtime_jails() {
+        let manager = SlashingManager::new(5, 10).with_jail_epochs(3);
+        let mut stake = stake_manager();
+
+        let outcome = manager
+            .apply(&mut stake, &[2], Misbehavior::Downtime(4), 1)
+            .unwrap();
+        assert_eq!(outcome.burned, 40);
+        assert_eq!(outcome.jailed_until, Some(4));
+        assert!(!outcome.tombstoned);
+        assert_eq!(stake.process_epoch(2).len(), 1);
+        assert!(stake.unjail(&[2], 4).is_ok());
+    }
 }
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+
+/// How a simulated validator behaves when it is the slot's proposer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Behavior {
+    Honest,
+    /// Signs two conflicting blocks for its slot and sends one to each half of
+    /// the network.
+    Equivocate,
+    /// Builds its blocks but never sends them to anyone.
+    Withhold,
+}
+
+/// Splits the network into groups that can't reach each other between
+/// `start` and `end` (virtual milliseconds). Nodes not listed in any group
+/// are isolated.
+#[derive(Debug, Clone)]
+pub struct Partition {
+    pub start: u64,
+    pub end: u64,
+    pub groups: Vec<Vec<usize>>,
+}
+
+impl Partition {
+    pub fn separates(&self, time: u64, a: usize, b: usize) -> bool {
+        if time < self.start || time >= self.end {
+            return false;
+        }
+        let group_of = |node| self.groups.iter().position(|g| g.contains(&node));
+        match (group_of(a), group_of(b)) {
+            (Some(x), Some(y)) => x != y,
+            _ => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SimConfig {
+    /// Seeds keys, proposer sampling and the network, so a config always
+    /// replays the same run.
+    pub seed: u64,
+    pub validators: usize,
+    pub stake_per_validator: u64,
+    /// Virtual milliseconds per slot.
+    pub slot_duration: u64,
+    pub slots_per_epoch: u64,
+    pub min_latency: u64,
+    pub max_latency: u64,
+    /// Probability in [0, 1] that any single message is lost.
+    pub drop_rate: f64,
+    pub partitions: Vec<Partition>,
+    pub behaviors: HashMap<usize, Behavior>,
+}
+
+impl SimConfig {
+    pub fn new(validators: usize, seed: u64) -> Self {
+        Self {
+            seed,
+            validators,
+            stake_per_validator: 100,
+            slot_duration: 1_000,
+            slots_per_epoch: 8,
+            min_latency: 10,
+            max_latency: 100,
+            drop_rate: 0.0,
+            partitions: Vec::new(),
+            behaviors: HashMap::new(),
+        }
+    }
+
+    pub fn with_latency(mut self, min: u64, max: u64) -> Self {
+        self.min_latency = min;
+        self.max_latency = max.max(min);
+        self
+    }
+
+    pub fn with_drop_rate(mut self, drop_rate: f64) -> Self {
+        self.drop_rate = drop_rate.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_partition(mut self, start: u64, end: u64, groups: Vec<Vec<usize>>) -> Self {
+        self.partitions.push(Partition { start, end, groups });
+        self
+    }
+
+    pub fn with_behavior(mut self, validator: usize, behavior: Behavior) -> Self {
+        self.behaviors.insert(validator, behavior);
+        self
+    }
+
+    pub fn with_slots_per_epoch(mut self, slots_per_epoch: u64) -> Self {
+        self.slots_per_epoch = slots_per_epoch.max(1);
+        self
+    }
+
+    pub fn behavior(&self, validator: usize) -> Behavior {
+        self.behaviors
+            .get(&validator)
+            .copied()
+            .unwrap_or(Behavior::Honest)
+    }
+}
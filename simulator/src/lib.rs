@@ -0,0 +1,4 @@
+pub mod config;
+pub mod network;
+pub mod node;
+pub mod sim;
@@ -0,0 +1,49 @@
+use crate::config::{Partition, SimConfig};
+use ledger::block::Block;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Block(Box<Block>),
+    /// Asks the receiver for a block we saw referenced as a parent.
+    GetBlock(String),
+}
+
+/// Decides if and when a message arrives. All randomness comes from one
+/// seeded RNG, so the same sequence of sends always yields the same fate.
+pub struct SimNetwork {
+    rng: StdRng,
+    min_latency: u64,
+    max_latency: u64,
+    drop_rate: f64,
+    partitions: Vec<Partition>,
+    pub sent: u64,
+    pub dropped: u64,
+}
+
+impl SimNetwork {
+    pub fn new(config: &SimConfig) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(config.seed),
+            min_latency: config.min_latency,
+            max_latency: config.max_latency,
+            drop_rate: config.drop_rate,
+            partitions: config.partitions.clone(),
+            sent: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Delivery time for a message sent at `now`, or `None` if it is lost.
+    pub fn delivery_time(&mut self, now: u64, from: usize, to: usize) -> Option<u64> {
+        self.sent += 1;
+        let latency = self.rng.gen_range(self.min_latency..=self.max_latency);
+        let lost = self.rng.gen_bool(self.drop_rate);
+        if lost || self.partitions.iter().any(|p| p.separates(now, from, to)) {
+            self.dropped += 1;
+            return None;
+        }
+        Some(now + latency)
+    }
+}
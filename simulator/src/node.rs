@@ -0,0 +1,199 @@
+use crate::config::Behavior;
+use crate::network::Message;
+use consensus::clock::{ManualTimeSource, SlotClock};
+use consensus::engine::ConsensusEngine;
+use consensus::pos::PosEngine;
+use crypto::KeyPair;
+use ledger::block::{Block, BlockHeader};
+use ledger::evidence::Evidence;
+use security::evidence::EquivocationDetector;
+use security::slashing::SlashingManager;
+use std::collections::{HashMap, HashSet};
+
+/// Parent hash of the first block.
+pub const GENESIS_HASH: &str = "0";
+/// Chain id every simulated block is signed for.
+pub const CHAIN_ID: &str = "simnet";
+
+#[derive(Debug, Clone)]
+pub enum Outgoing {
+    Broadcast(Message),
+    /// Sent back only to the peer the triggering message came from.
+    Reply(Message),
+}
+
+/// One validator: its own view of the block tree, stake and slashing state.
+pub struct SimNode {
+    pub index: usize,
+    keypair: KeyPair,
+    behavior: Behavior,
+    engine: PosEngine,
+    clock: SlotClock<ManualTimeSource>,
+    slasher: SlashingManager,
+    blocks: HashMap<String, Block>,
+    /// Blocks waiting for their parent, keyed by the parent hash.
+    orphans: HashMap<String, Vec<Block>>,
+    detector: EquivocationDetector,
+    evidence_seen: HashSet<String>,
+    head: Option<String>,
+    pub equivocations_detected: usize,
+}
+
+impl SimNode {
+    pub fn new(
+        index: usize,
+        keypair: KeyPair,
+        behavior: Behavior,
+        engine: PosEngine,
+        clock: SlotClock<ManualTimeSource>,
+    ) -> Self {
+        Self {
+            index,
+            keypair,
+            behavior,
+            engine,
+            clock,
+            slasher: SlashingManager::new(5, 0),
+            blocks: HashMap::new(),
+            orphans: HashMap::new(),
+            detector: EquivocationDetector::new(),
+            evidence_seen: HashSet::new(),
+            head: None,
+            equivocations_detected: 0,
+        }
+    }
+
+    pub fn public_key(&self) -> &[u8] {
+        &self.keypair.public_key
+    }
+
+    pub fn behavior(&self) -> Behavior {
+        self.behavior
+    }
+
+    pub fn engine(&self) -> &PosEngine {
+        &self.engine
+    }
+
+    pub fn head(&self) -> Option<&Block> {
+        self.head.as_ref().and_then(|hash| self.blocks.get(hash))
+    }
+
+    /// Number of blocks from the head back to genesis.
+    pub fn chain_length(&self) -> usize {
+        let mut length = 0;
+        let mut cursor = self.head();
+        while let Some(block) = cursor {
+            length += 1;
+            cursor = self.blocks.get(&block.header.parent_hash);
+        }
+        length
+    }
+
+    pub fn on_epoch(&mut self, epoch: u64) {
+        self.engine.stake_manager_mut().process_epoch(epoch);
+    }
+
+    /// Builds this node's block(s) for `slot` if it is the proposer and adds
+    /// the first to its own tree. Equivocators return two conflicting blocks.
+    pub fn propose(&mut self, slot: u64) -> Vec<Block> {
+        if self.engine.proposer_for_slot(slot).as_deref() != Some(self.public_key()) {
+            return Vec::new();
+        }
+        let parent = self
+            .head
+            .clone()
+            .unwrap_or_else(|| GENESIS_HASH.to_string());
+        let mut blocks = vec![self.sign_block(parent.clone(), slot, "honest")];
+        if self.behavior == Behavior::Equivocate {
+            blocks.push(self.sign_block(parent, slot, "equivocation"));
+        }
+        self.accept(blocks[0].clone());
+        blocks
+    }
+
+    fn sign_block(&self, parent_hash: String, slot: u64, state_root: &str) -> Block {
+        let mut block = Block::new(
+            CHAIN_ID,
+            parent_hash,
+            self.clock.slot_start(slot),
+            slot,
+            state_root.to_string(),
+            Vec::new(),
+            self.keypair.public_key.clone(),
+        );
+        block.header.signature = self.keypair.sign(&block.header.signing_bytes()).unwrap();
+        block
+    }
+
+    pub fn handle(&mut self, message: Message) -> Vec<Outgoing> {
+        match message {
+            Message::GetBlock(hash) => self
+                .blocks
+                .get(&hash)
+                .map(|block| Outgoing::Reply(Message::Block(Box::new(block.clone()))))
+                .into_iter()
+                .collect(),
+            Message::Block(block) => self.receive_block(*block),
+        }
+    }
+
+    fn receive_block(&mut self, block: Block) -> Vec<Outgoing> {
+        let hash = block.hash();
+        let orphaned = self
+            .orphans
+            .get(&block.header.parent_hash)
+            .is_some_and(|waiting| waiting.iter().any(|b| b.hash() == hash));
+        if self.blocks.contains_key(&hash) || orphaned || self.evidence_seen.contains(&hash) {
+            return Vec::new();
+        }
+        if self.clock.verify_block_time(&block.header).is_err() {
+            return Vec::new();
+        }
+
+        if let Some(evidence) = self.detector.observe_header(&block.header) {
+            self.report_double_sign(&evidence);
+            self.evidence_seen.insert(hash);
+            // Pass the conflicting block on so other nodes see the evidence too
+            return vec![Outgoing::Broadcast(Message::Block(Box::new(block)))];
+        }
+
+        let parent = &block.header.parent_hash;
+        if parent != GENESIS_HASH && !self.blocks.contains_key(parent) {
+            let request = Outgoing::Reply(Message::GetBlock(parent.clone()));
+            self.orphans.entry(parent.clone()).or_default().push(block);
+            return vec![request];
+        }
+
+        let mut outgoing = Vec::new();
+        let mut ready = vec![block];
+        while let Some(block) = ready.pop() {
+            if self.engine.verify_block_header(&block.header).is_err() {
+                continue;
+            }
+            let hash = block.hash();
+            outgoing.push(Outgoing::Broadcast(Message::Block(Box::new(block.clone()))));
+            self.accept(block);
+            ready.extend(self.orphans.remove(&hash).unwrap_or_default());
+        }
+        outgoing
+    }
+
+    fn accept(&mut self, block: Block) {
+        self.detector.observe_header(&block.header);
+        self.blocks.insert(block.hash(), block);
+
+        let headers: Vec<BlockHeader> = self.blocks.values().map(|b| b.header.clone()).collect();
+        self.head = self.engine.choose_fork(&headers).map(BlockHeader::hash);
+    }
+
+    fn report_double_sign(&mut self, evidence: &Evidence) {
+        let epoch = self.clock.epoch_of(evidence.slot());
+        let outcome = self
+            .slasher
+            .apply_evidence(self.engine.stake_manager_mut(), evidence, epoch);
+        if outcome.is_some() {
+            self.equivocations_detected += 1;
+        }
+    }
+}
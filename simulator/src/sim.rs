@@ -0,0 +1,341 @@
+use crate::config::{Behavior, SimConfig};
+use crate::network::{Message, SimNetwork};
+use crate::node::{Outgoing, SimNode};
+use consensus::clock::{ManualTimeSource, SlotClock};
+use consensus::pos::PosEngine;
+use consensus::staking::StakeManager;
+use crypto::KeyPair;
+use ledger::block::Block;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::time::Duration;
+
+#[derive(Debug)]
+enum Event {
+    SlotStart(u64),
+    Deliver {
+        from: usize,
+        to: usize,
+        message: Box<Message>,
+    },
+}
+
+/// Events run in (time, insertion order), which keeps runs reproducible.
+#[derive(Debug)]
+struct Scheduled {
+    time: u64,
+    seq: u64,
+    event: Event,
+}
+
+impl PartialEq for Scheduled {
+    fn eq(&self, other: &Self) -> bool {
+        (self.time, self.seq) == (other.time, other.seq)
+    }
+}
+
+impl Eq for Scheduled {}
+
+impl PartialOrd for Scheduled {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scheduled {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.time, self.seq).cmp(&(other.time, other.seq))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimReport {
+    pub slots: u64,
+    pub blocks_proposed: u64,
+    pub messages_sent: u64,
+    pub messages_dropped: u64,
+    /// Head block hash of each node.
+    pub heads: Vec<Option<String>>,
+    pub chain_lengths: Vec<usize>,
+    /// Double signs each node detected and slashed.
+    pub equivocations_detected: Vec<usize>,
+}
+
+impl SimReport {
+    /// Whether every node ended up on the same head.
+    pub fn converged(&self) -> bool {
+        self.heads.windows(2).all(|pair| pair[0] == pair[1])
+    }
+}
+
+/// Runs N validators over a simulated network on a virtual clock. Nothing
+/// touches wall time, sockets or OS randomness, so a given `SimConfig`
+/// always produces the same `SimReport`.
+pub struct Simulation {
+    config: SimConfig,
+    time: ManualTimeSource,
+    now: u64,
+    seq: u64,
+    next_slot: u64,
+    queue: BinaryHeap<Reverse<Scheduled>>,
+    network: SimNetwork,
+    nodes: Vec<SimNode>,
+    blocks_proposed: u64,
+}
+
+impl Simulation {
+    pub fn new(config: SimConfig) -> Self {
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        let keys: Vec<KeyPair> = (0..config.validators)
+            .map(|_| KeyPair::from_seed(rng.gen()))
+            .collect();
+        let engine_seed: [u8; 32] = rng.gen();
+
+        let time = ManualTimeSource::new(0);
+        let nodes = keys
+            .iter()
+            .enumerate()
+            .map(|(index, keypair)| {
+                let mut stake = StakeManager::new().with_churn_limit(config.validators);
+                for key in &keys {
+                    stake.add_stake(key.public_key.clone(), config.stake_per_validator);
+                }
+                stake.process_epoch(0);
+                let clock = SlotClock::with_time_source(
+                    0,
+                    Duration::from_millis(config.slot_duration),
+                    config.slots_per_epoch,
+                    time.clone(),
+                );
+                SimNode::new(
+                    index,
+                    keypair.clone(),
+                    config.behavior(index),
+                    PosEngine::new(stake).with_seed(engine_seed),
+                    clock,
+                )
+            })
+            .collect();
+
+        Self {
+            network: SimNetwork::new(&config),
+            config,
+            time,
+            now: 0,
+            seq: 0,
+            next_slot: 0,
+            queue: BinaryHeap::new(),
+            nodes,
+            blocks_proposed: 0,
+        }
+    }
+
+    pub fn nodes(&self) -> &[SimNode] {
+        &self.nodes
+    }
+
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    fn schedule(&mut self, time: u64, event: Event) {
+        self.seq += 1;
+        self.queue.push(Reverse(Scheduled {
+            time,
+            seq: self.seq,
+            event,
+        }));
+    }
+
+    fn send(&mut self, from: usize, to: usize, message: Message) {
+        if let Some(at) = self.network.delivery_time(self.now, from, to) {
+            self.schedule(
+                at,
+                Event::Deliver {
+                    from,
+                    to,
+                    message: Box::new(message),
+                },
+            );
+        }
+    }
+
+    fn broadcast(&mut self, from: usize, message: Message) {
+        for to in 0..self.nodes.len() {
+            if to != from {
+                self.send(from, to, message.clone());
+            }
+        }
+    }
+
+    /// Runs `slots` more slots (and delivers everything sent during them).
+    pub fn run_slots(&mut self, slots: u64) -> SimReport {
+        let end_slot = self.next_slot + slots;
+        for slot in self.next_slot..end_slot {
+            self.schedule(slot * self.config.slot_duration, Event::SlotStart(slot));
+        }
+        self.next_slot = end_slot;
+        let end_time = end_slot * self.config.slot_duration;
+
+        while let Some(Reverse(scheduled)) = self.queue.peek() {
+            if scheduled.time >= end_time {
+                break;
+            }
+            let Reverse(scheduled) = self.queue.pop().unwrap();
+            self.now = scheduled.time;
+            self.time.set(self.now);
+            match scheduled.event {
+                Event::SlotStart(slot) => self.start_slot(slot),
+                Event::Deliver { from, to, message } => {
+                    for outgoing in self.nodes[to].handle(*message) {
+                        match outgoing {
+                            Outgoing::Broadcast(message) => self.broadcast(to, message),
+                            Outgoing::Reply(message) => self.send(to, from, message),
+                        }
+                    }
+                }
+            }
+        }
+        self.report()
+    }
+
+    fn start_slot(&mut self, slot: u64) {
+        if slot > 0 && slot.is_multiple_of(self.config.slots_per_epoch) {
+            let epoch = slot / self.config.slots_per_epoch;
+            for node in &mut self.nodes {
+                node.on_epoch(epoch);
+            }
+        }
+
+        for index in 0..self.nodes.len() {
+            let blocks = self.nodes[index].propose(slot);
+            if blocks.is_empty() {
+                continue;
+            }
+            self.blocks_proposed += 1;
+            match self.nodes[index].behavior() {
+                Behavior::Honest => {
+                    self.broadcast(index, Message::Block(Box::new(blocks[0].clone())))
+                }
+                Behavior::Withhold => {}
+                Behavior::Equivocate => self.split_send(index, &blocks),
+            }
+        }
+    }
+
+    /// Sends the first block to the lower half of the other nodes and the
+    /// second to the upper half.
+    fn split_send(&mut self, from: usize, blocks: &[Block]) {
+        let peers: Vec<usize> = (0..self.nodes.len()).filter(|&i| i != from).collect();
+        let half = peers.len() / 2;
+        for (i, to) in peers.into_iter().enumerate() {
+            let block = if i < half { &blocks[0] } else { &blocks[1] };
+            self.send(from, to, Message::Block(Box::new(block.clone())));
+        }
+    }
+
+    pub fn report(&self) -> SimReport {
+        SimReport {
+            slots: self.next_slot,
+            blocks_proposed: self.blocks_proposed,
+            messages_sent: self.network.sent,
+            messages_dropped: self.network.dropped,
+            heads: self
+                .nodes
+                .iter()
+                .map(|n| n.head().map(Block::hash))
+                .collect(),
+            chain_lengths: self.nodes.iter().map(SimNode::chain_length).collect(),
+            equivocations_detected: self
+                .nodes
+                .iter()
+                .map(|n| n.equivocations_detected)
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use consensus::engine::ConsensusEngine;
+
+    #[test]
+    fn test_same_seed_replays_same_run() {
+        let config = SimConfig::new(5, 7)
+            .with_latency(50, 400)
+            .with_drop_rate(0.1);
+        let a = Simulation::new(config.clone()).run_slots(30);
+        let b = Simulation::new(config).run_slots(30);
+        assert_eq!(a, b);
+        assert!(a.messages_dropped > 0);
+    }
+
+    #[test]
+    fn test_honest_network_converges() {
+        let mut sim = Simulation::new(SimConfig::new(4, 1));
+        let report = sim.run_slots(20);
+        // Every slot has a proposer and every block reaches everyone in time
+        assert_eq!(report.blocks_proposed, 20);
+        assert!(report.converged());
+        assert_eq!(report.chain_lengths, vec![20; 4]);
+    }
+
+    #[test]
+    fn test_partition_heals_to_single_head() {
+        let config =
+            SimConfig::new(4, 3).with_partition(2_000, 10_000, vec![vec![0, 1], vec![2, 3]]);
+        let mut sim = Simulation::new(config);
+        let split = sim.run_slots(10);
+        assert!(!split.converged());
+
+        // After the partition the missing branch is fetched and fork choice agrees
+        let healed = sim.run_slots(5);
+        assert!(healed.converged());
+    }
+
+    #[test]
+    fn test_equivocator_is_detected_and_slashed() {
+        let config = SimConfig::new(4, 11).with_behavior(0, Behavior::Equivocate);
+        let mut sim = Simulation::new(config);
+        let report = sim.run_slots(16);
+
+        let equivocator = sim.nodes()[0].public_key().to_vec();
+        for node in &sim.nodes()[1..] {
+            let validator = node
+                .engine()
+                .stake_manager()
+                .get_validator(&equivocator)
+                .unwrap();
+            assert!(validator.is_tombstoned);
+            // Removed from the proposer schedule at the next epoch boundary
+            assert!(!node
+                .engine()
+                .stake_manager()
+                .validator_set()
+                .contains(&equivocator));
+        }
+        assert!(report.equivocations_detected[1..].iter().all(|&n| n == 1));
+    }
+
+    #[test]
+    fn test_withholding_proposer_only_costs_its_slots() {
+        let config = SimConfig::new(4, 5).with_behavior(2, Behavior::Withhold);
+        let mut sim = Simulation::new(config);
+        let report = sim.run_slots(20);
+
+        let withholder = sim.nodes()[2].public_key().to_vec();
+        let withheld = (0..20)
+            .filter(|&slot| {
+                sim.nodes()[0].engine().proposer_for_slot(slot) == Some(withholder.clone())
+            })
+            .count();
+        assert!(withheld > 0);
+        // Honest nodes still agree and only lose the withheld slots
+        let honest: Vec<_> = [0, 1, 3].iter().map(|&i| report.heads[i].clone()).collect();
+        assert!(honest.windows(2).all(|p| p[0] == p[1]));
+        assert_eq!(report.chain_lengths[0], 20 - withheld);
+    }
+}
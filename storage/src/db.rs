@@ -19,6 +19,7 @@ pub trait StateStore {
     fn flush(&self) -> Result<(), StorageError>;
 }
 
+#[derive(Clone)]
 pub struct SledStore {
     db: Db,
 }
@@ -14,7 +14,8 @@ pub enum OpCode {
     LOAD,  // Load from Memory
     STORE, // Store to Memory
     JUMP,
-    JUMPI, // Jump if non-zero
+    JUMPI,  // Jump if non-zero
+    RANDAO, // Push the block's randomness mix (low 8 bytes)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
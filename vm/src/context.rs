@@ -0,0 +1,25 @@
+use ledger::block::BlockHeader;
+use ledger::randao::RandaoMix;
+
+/// Read-only facts about the block a program runs in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockContext {
+    pub slot: u64,
+    pub timestamp: u64,
+    pub randao_mix: RandaoMix,
+}
+
+impl BlockContext {
+    pub fn from_header(header: &BlockHeader) -> Self {
+        Self {
+            slot: header.slot,
+            timestamp: header.timestamp,
+            randao_mix: header.randao_mix,
+        }
+    }
+
+    /// First 8 bytes of the mix, which is what `OpCode::RANDAO` pushes.
+    pub fn random_u64(&self) -> u64 {
+        u64::from_le_bytes(self.randao_mix[..8].try_into().unwrap())
+    }
+}
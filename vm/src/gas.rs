@@ -40,6 +40,7 @@ impl GasMeter {
             OpCode::STORE => 10, // Memory access expensive
             OpCode::JUMP => 8,
             OpCode::JUMPI => 10,
+            OpCode::RANDAO => 2,
         }
     }
 }
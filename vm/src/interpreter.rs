@@ -1,4 +1,5 @@
 use crate::bytecode::{OpCode, Program};
+use crate::context::BlockContext;
 use crate::gas::{GasError, GasMeter};
 use thiserror::Error;
 
@@ -20,6 +21,7 @@ pub struct VM {
     memory: Vec<u8>,
     pc: usize,
     gas_meter: GasMeter,
+    context: BlockContext,
 }
 
 impl VM {
@@ -29,9 +31,15 @@ impl VM {
             memory: vec![0; 1024], // Simple linear memory 1KB
             pc: 0,
             gas_meter: GasMeter::new(gas_limit),
+            context: BlockContext::default(),
         }
     }
 
+    pub fn with_context(mut self, context: BlockContext) -> Self {
+        self.context = context;
+        self
+    }
+
     pub fn execute(&mut self, program: &Program) -> Result<Option<u64>, VMError> {
         self.pc = 0;
         loop {
@@ -48,6 +56,7 @@ impl VM {
             match op {
                 OpCode::STOP => return Ok(self.stack.last().copied()), // Return top of stack
                 OpCode::PUSH(val) => self.stack.push(val),
+                OpCode::RANDAO => self.stack.push(self.context.random_u64()),
                 OpCode::POP => {
                     self.stack.pop().ok_or(VMError::StackUnderflow)?;
                 }
@@ -160,4 +169,17 @@ mod tests {
         let res = vm.execute(&prog).unwrap();
         assert_eq!(res, Some(42));
     }
+
+    #[test]
+    fn test_randao_reads_block_context() {
+        let context = BlockContext {
+            randao_mix: [1u8; 32],
+            ..Default::default()
+        };
+        let mut vm = VM::new(100).with_context(context);
+        let prog = Program {
+            code: vec![OpCode::RANDAO, OpCode::STOP],
+        };
+        assert_eq!(vm.execute(&prog).unwrap(), Some(context.random_u64()));
+    }
 }
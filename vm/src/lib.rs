@@ -1,3 +1,4 @@
 pub mod bytecode;
+pub mod context;
 pub mod gas;
 pub mod interpreter;